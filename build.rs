@@ -4,13 +4,27 @@ use std::path::{Path, PathBuf};
 
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
-    println!("cargo:rerun-if-changed=src/translator.rs");
-    println!("cargo:rerun-if-changed=src/translator.cpp");
-    println!("cargo:rerun-if-changed=src/generator.rs");
-    println!("cargo:rerun-if-changed=src/generator.cpp");
+    println!("cargo:rerun-if-changed=src/translator/translator.rs");
+    println!("cargo:rerun-if-changed=cpp/translator.cc");
+    println!("cargo:rerun-if-changed=src/generator/generator.rs");
+    println!("cargo:rerun-if-changed=cpp/generator.cc");
+    println!("cargo:rerun-if-changed=src/whisper/whisper.rs");
+    println!("cargo:rerun-if-changed=cpp/whisper.cc");
+    println!("cargo:rerun-if-changed=src/encoder/encoder.rs");
+    println!("cargo:rerun-if-changed=cpp/encoder.cc");
+    println!("cargo:rerun-if-changed=src/source.rs");
+    println!("cargo:rerun-if-changed=cpp/source.cc");
+    println!("cargo:rerun-if-changed=src/device.rs");
+    println!("cargo:rerun-if-changed=cpp/device.cc");
     println!("cargo:rerun-if-changed=include/convert.h");
+    println!("cargo:rerun-if-changed=include/exception.h");
+    println!("cargo:rerun-if-changed=include/model_state.h");
     println!("cargo:rerun-if-changed=include/translator.h");
     println!("cargo:rerun-if-changed=include/generator.h");
+    println!("cargo:rerun-if-changed=include/whisper.h");
+    println!("cargo:rerun-if-changed=include/encoder.h");
+    println!("cargo:rerun-if-changed=include/source.h");
+    println!("cargo:rerun-if-changed=include/device.h");
     println!("cargo:rerun-if-changed=CTranslate2");
     println!("cargo:rerun-if-env-changed=LIBRARY_PATH");
 
@@ -47,8 +61,18 @@ fn main() {
 
     cxx_build::bridges(vec![
         "src/generator/generator.rs",
+        "src/translator/translator.rs",
+        "src/whisper/whisper.rs",
+        "src/encoder/encoder.rs",
+        "src/source.rs",
+        "src/device.rs",
     ])
     .file("cpp/generator.cc")
+    .file("cpp/translator.cc")
+    .file("cpp/whisper.cc")
+    .file("cpp/encoder.cc")
+    .file("cpp/source.cc")
+    .file("cpp/device.cc")
     .flag_if_supported("-std=c++17")
     .include("CTranslate2/include")
     .compile("ctrans2");
@@ -0,0 +1,53 @@
+// device.cc
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#include "ctrans2/include/device.h"
+#include "ctrans2/src/device.rs.h"
+
+#include <ctranslate2/devices.h>
+#include <ctranslate2/types.h>
+#include <ctranslate2/utils.h>
+
+using rust::String;
+using rust::Vec;
+
+int get_cuda_device_count() { return ctranslate2::get_gpu_count(); }
+
+// Lists the compute types the device can run efficiently, named as in
+// CTranslate2.
+Vec<String> get_supported_compute_types(bool cuda, int device_index) {
+  const auto device =
+      cuda ? ctranslate2::Device::CUDA : ctranslate2::Device::CPU;
+  const bool float16 = ctranslate2::mayiuse_float16(device, device_index);
+  const bool int16 = ctranslate2::mayiuse_int16(device, device_index);
+  const bool int8 = ctranslate2::mayiuse_int8(device, device_index);
+  const bool bfloat16 = ctranslate2::mayiuse_bfloat16(device, device_index);
+
+  Vec<String> res;
+  res.push_back("float32");
+  if (float16) {
+    res.push_back("float16");
+  }
+  if (int16) {
+    res.push_back("int16");
+  }
+  if (bfloat16) {
+    res.push_back("bfloat16");
+  }
+  if (int8) {
+    res.push_back("int8");
+    res.push_back("int8_float32");
+    if (float16) {
+      res.push_back("int8_float16");
+    }
+    if (bfloat16) {
+      res.push_back("int8_bfloat16");
+    }
+  }
+  return res;
+}
@@ -0,0 +1,103 @@
+// encoder.cc
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#include "ctrans2/include/encoder.h"
+#include "ctrans2/include/convert.h"
+#include "ctrans2/src/encoder/encoder.rs.h"
+
+using rust::Str;
+using rust::Vec;
+
+// Copies the given tensor to the host memory as 32-bit floats.
+inline Vec<float> to_rust_data(const ctranslate2::StorageView &v) {
+  return to_rust(
+      v.to(ctranslate2::Device::CPU).to_float32().to_vector<float>());
+}
+
+inline Vec<size_t> to_rust_shape(const ctranslate2::StorageView &v) {
+  Vec<size_t> res;
+  for (const auto &dim : v.shape()) {
+    res.push_back(static_cast<size_t>(dim));
+  }
+  return res;
+}
+
+inline EncoderForwardOutput
+to_rust(const ctranslate2::EncoderForwardOutput &output) {
+  EncoderForwardOutput res{
+      to_rust_data(output.last_hidden_state),
+      to_rust_shape(output.last_hidden_state),
+      Vec<float>(),
+      Vec<size_t>(),
+      output.pooler_output.has_value(),
+  };
+  if (output.pooler_output) {
+    res.pooler_output = to_rust_data(*output.pooler_output);
+    res.pooler_output_shape = to_rust_shape(*output.pooler_output);
+  }
+  return res;
+}
+
+EncoderForwardOutput
+Encoder::forward_batch(Vec<EncVecStr> tokens,
+                       Vec<EncVecUSize> token_type_ids) const {
+  return to_rust(this->impl
+                     ->forward_batch_async(from_rust(tokens),
+                                           from_rust_ids(token_type_ids))
+                     .get());
+}
+
+EncoderForwardOutput
+Encoder::forward_batch_ids(Vec<EncVecUSize> ids,
+                           Vec<EncVecUSize> token_type_ids) const {
+  return to_rust(this->impl
+                     ->forward_batch_async(from_rust_ids(ids),
+                                           from_rust_ids(token_type_ids))
+                     .get());
+}
+
+inline ctranslate2::ComputeType to_compute_type(EncComputeType compute_type) {
+  switch (compute_type) {
+  case EncComputeType::Auto:
+    return ctranslate2::ComputeType::AUTO;
+  case EncComputeType::Float32:
+    return ctranslate2::ComputeType::FLOAT32;
+  case EncComputeType::Int8:
+    return ctranslate2::ComputeType::INT8;
+  case EncComputeType::Int8Float16:
+    return ctranslate2::ComputeType::INT8_FLOAT16;
+  case EncComputeType::Int16:
+    return ctranslate2::ComputeType::INT16;
+  case EncComputeType::Float16:
+    return ctranslate2::ComputeType::FLOAT16;
+  case EncComputeType::BFloat16:
+    return ctranslate2::ComputeType::BFLOAT16;
+  case EncComputeType::Int8BFloat16:
+    return ctranslate2::ComputeType::INT8_BFLOAT16;
+  case EncComputeType::Int8Float32:
+    return ctranslate2::ComputeType::INT8_FLOAT32;
+  default:
+    return ctranslate2::ComputeType::DEFAULT;
+  }
+}
+
+std::unique_ptr<Encoder> new_encoder(Str model_path, bool cuda,
+                                     EncoderConfig config) {
+  ctranslate2::ReplicaPoolConfig pool_config;
+  pool_config.num_threads_per_replica = config.num_threads_per_replica;
+  pool_config.max_queued_batches = config.max_queued_batches;
+  pool_config.cpu_core_offset = config.cpu_core_offset;
+
+  return std::make_unique<Encoder>(std::make_shared<ctranslate2::Encoder>(
+      from_rust(model_path),
+      cuda ? ctranslate2::Device::CUDA : ctranslate2::Device::CPU,
+      to_compute_type(config.compute_type),
+      std::vector<int>(config.device_indices.begin(),
+                       config.device_indices.end()),
+      pool_config));
+}
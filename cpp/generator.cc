@@ -0,0 +1,294 @@
+// generator.cc
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#include "ctrans2/include/generator.h"
+#include "ctrans2/include/convert.h"
+#include "ctrans2/src/generator/generator.rs.h"
+
+#include <mutex>
+
+using rust::Str;
+using rust::Vec;
+
+inline ctranslate2::GenerationOptions
+to_generation_options(const GenerationOptions &options) {
+  ctranslate2::GenerationOptions opts;
+  opts.beam_size = options.beam_size;
+  opts.patience = options.patience;
+  opts.length_penalty = options.length_penalty;
+  opts.repetition_penalty = options.repetition_penalty;
+  opts.no_repeat_ngram_size = options.no_repeat_ngram_size;
+  opts.disable_unk = options.disable_unk;
+  opts.suppress_sequences = from_rust(options.suppress_sequences);
+  opts.end_token = end_token_from_rust(options.end_token);
+  opts.return_end_token = options.return_end_token;
+  opts.max_length = options.max_length;
+  opts.min_length = options.min_length;
+  opts.sampling_topk = options.sampling_topk;
+  opts.sampling_topp = options.sampling_topp;
+  opts.sampling_temperature = options.sampling_temperature;
+  opts.num_hypotheses = options.num_hypotheses;
+  opts.return_scores = options.return_scores;
+  opts.return_log_prob = options.return_log_prob;
+  opts.return_logits_vocab = options.return_logits_vocab;
+  opts.return_alternatives = options.return_alternatives;
+  opts.min_alternative_expansion_prob = options.min_alternative_expansion_prob;
+  opts.static_prompt = from_rust(options.static_prompt);
+  opts.cache_static_prompt = options.cache_static_prompt;
+  opts.include_prompt_in_result = options.include_prompt_in_result;
+  return opts;
+}
+
+inline ctranslate2::BatchType to_batch_type(GenerationBatchType batch_type) {
+  return batch_type == GenerationBatchType::Examples
+             ? ctranslate2::BatchType::Examples
+             : ctranslate2::BatchType::Tokens;
+}
+
+inline GenerationStepResult
+to_rust(const ctranslate2::GenerationStepResult &r) {
+  return GenerationStepResult{
+      r.step,
+      r.batch_id,
+      r.token_id,
+      r.hypothesis_id,
+      to_rust(r.token),
+      r.log_prob.value_or(0),
+      r.log_prob.has_value(),
+      r.is_last,
+  };
+}
+
+// Stacks the logits of the decoding steps of each sequence into a single
+// buffer of 32-bit floats in the host memory.
+inline Vec<GenLogits>
+to_rust(const std::vector<std::vector<ctranslate2::StorageView>> &logits) {
+  Vec<GenLogits> res;
+  for (const auto &steps : logits) {
+    GenLogits item{Vec<float>(), steps.size(), 0};
+    for (const auto &step : steps) {
+      const auto values = step.to(ctranslate2::Device::CPU)
+                              .to_float32()
+                              .to_vector<float>();
+      item.vocab_size = values.size();
+      for (const auto value : values) {
+        item.data.push_back(value);
+      }
+    }
+    res.push_back(std::move(item));
+  }
+  return res;
+}
+
+// Calls to the Rust callback are serialized since the batches can be
+// decoded in parallel by different replicas.
+template <typename Callback>
+inline std::function<bool(ctranslate2::GenerationStepResult)>
+make_step_callback(Callback callback) {
+  auto mutex = std::make_shared<std::mutex>();
+  return [callback, mutex](ctranslate2::GenerationStepResult step) mutable {
+    std::lock_guard<std::mutex> lock(*mutex);
+    return callback(to_rust(step));
+  };
+}
+
+// Waits for every future before collecting the results, so that no batch is
+// still running (and possibly calling back into Rust) when an error is thrown.
+inline Vec<GenerationResult>
+collect_results(std::vector<std::future<ctranslate2::GenerationResult>> &futures) {
+  for (auto &future : futures) {
+    future.wait();
+  }
+
+  Vec<GenerationResult> res;
+  for (auto &future : futures) {
+    const auto &r = future.get();
+    res.push_back(GenerationResult{
+        to_rust<GenVecString>(r.sequences),
+        to_rust<GenVecUSize>(r.sequences_ids),
+        to_rust(r.scores),
+        to_rust(r.logits),
+    });
+  }
+  return res;
+}
+
+Vec<GenerationResult> GenerationStream::wait() {
+  return collect_results(this->futures);
+}
+
+// Submits the start tokens or IDs with the batching options.
+template <typename Start>
+inline std::vector<std::future<ctranslate2::GenerationResult>>
+submit(ctranslate2::Generator &generator, const Start &start,
+       const ctranslate2::GenerationOptions &opts,
+       const GenerationOptions &options) {
+  return generator.generate_batch_async(start, opts, options.max_batch_size,
+                                        to_batch_type(options.batch_type));
+}
+
+inline ctranslate2::GenerationOptions
+to_generation_options(const GenerationOptions &options,
+                      GenerationCallbackBox &callback) {
+  auto opts = to_generation_options(options);
+  opts.callback = make_step_callback([&callback](GenerationStepResult step) {
+    return callback.execute(std::move(step));
+  });
+  return opts;
+}
+
+// The callback is owned by the generation job and released with it.
+inline ctranslate2::GenerationOptions
+to_generation_options(const GenerationOptions &options,
+                      rust::Box<GenerationCallbackBox> callback) {
+  auto shared =
+      std::make_shared<rust::Box<GenerationCallbackBox>>(std::move(callback));
+  auto opts = to_generation_options(options);
+  opts.callback = make_step_callback([shared](GenerationStepResult step) {
+    return (*shared)->execute(std::move(step));
+  });
+  return opts;
+}
+
+Vec<GenerationResult>
+Generator::generate_batch(Vec<GenVecStr> start_tokens,
+                          GenerationOptions options) const {
+  const auto lock = this->state->lock();
+  auto futures = submit(*this->impl, from_rust(start_tokens),
+                        to_generation_options(options), options);
+  return collect_results(futures);
+}
+
+Vec<GenerationResult>
+Generator::generate_batch_ids(Vec<GenVecUSize> start_ids,
+                              GenerationOptions options) const {
+  const auto lock = this->state->lock();
+  auto futures = submit(*this->impl, from_rust_ids(start_ids),
+                        to_generation_options(options), options);
+  return collect_results(futures);
+}
+
+Vec<GenerationResult>
+Generator::generate_batch_with_callback(Vec<GenVecStr> start_tokens,
+                                        GenerationOptions options,
+                                        GenerationCallbackBox &callback) const {
+  const auto lock = this->state->lock();
+  auto futures = submit(*this->impl, from_rust(start_tokens),
+                        to_generation_options(options, callback), options);
+  return collect_results(futures);
+}
+
+Vec<GenerationResult> Generator::generate_batch_ids_with_callback(
+    Vec<GenVecUSize> start_ids, GenerationOptions options,
+    GenerationCallbackBox &callback) const {
+  const auto lock = this->state->lock();
+  auto futures = submit(*this->impl, from_rust_ids(start_ids),
+                        to_generation_options(options, callback), options);
+  return collect_results(futures);
+}
+
+std::unique_ptr<GenerationStream>
+Generator::generate_batch_async(Vec<GenVecStr> start_tokens,
+                                GenerationOptions options) const {
+  const auto lock = this->state->lock();
+  return std::make_unique<GenerationStream>(
+      submit(*this->impl, from_rust(start_tokens),
+             to_generation_options(options), options));
+}
+
+std::unique_ptr<GenerationStream>
+Generator::generate_batch_ids_async(Vec<GenVecUSize> start_ids,
+                                    GenerationOptions options) const {
+  const auto lock = this->state->lock();
+  return std::make_unique<GenerationStream>(
+      submit(*this->impl, from_rust_ids(start_ids),
+             to_generation_options(options), options));
+}
+
+Vec<GenScoringResult> Generator::score_batch(Vec<GenVecStr> tokens,
+                                             GenScoringOptions options) const {
+  const auto lock = this->state->lock();
+  ctranslate2::ScoringOptions opts;
+  opts.max_input_length = options.max_input_length;
+  opts.offset = options.offset;
+
+  auto futures = this->impl->score_batch_async(
+      from_rust(tokens), opts, options.max_batch_size,
+      to_batch_type(options.batch_type));
+
+  Vec<GenScoringResult> res;
+  for (auto &future : futures) {
+    const auto &r = future.get();
+    res.push_back(GenScoringResult{to_rust(r.tokens), to_rust(r.tokens_score)});
+  }
+  return res;
+}
+
+std::unique_ptr<GenerationStream>
+Generator::generate_stream(Vec<GenVecStr> start_tokens,
+                           GenerationOptions options,
+                           rust::Box<GenerationCallbackBox> callback) const {
+  const auto lock = this->state->lock();
+  return std::make_unique<GenerationStream>(
+      submit(*this->impl, from_rust(start_tokens),
+             to_generation_options(options, std::move(callback)), options));
+}
+
+std::unique_ptr<GenerationStream>
+Generator::generate_stream_ids(Vec<GenVecUSize> start_ids,
+                               GenerationOptions options,
+                               rust::Box<GenerationCallbackBox> callback) const {
+  const auto lock = this->state->lock();
+  return std::make_unique<GenerationStream>(
+      submit(*this->impl, from_rust_ids(start_ids),
+             to_generation_options(options, std::move(callback)), options));
+}
+
+inline ctranslate2::ComputeType to_compute_type(GenComputeType compute_type) {
+  switch (compute_type) {
+  case GenComputeType::Auto:
+    return ctranslate2::ComputeType::AUTO;
+  case GenComputeType::Float32:
+    return ctranslate2::ComputeType::FLOAT32;
+  case GenComputeType::Int8:
+    return ctranslate2::ComputeType::INT8;
+  case GenComputeType::Int8Float16:
+    return ctranslate2::ComputeType::INT8_FLOAT16;
+  case GenComputeType::Int16:
+    return ctranslate2::ComputeType::INT16;
+  case GenComputeType::Float16:
+    return ctranslate2::ComputeType::FLOAT16;
+  case GenComputeType::BFloat16:
+    return ctranslate2::ComputeType::BFLOAT16;
+  case GenComputeType::Int8BFloat16:
+    return ctranslate2::ComputeType::INT8_BFLOAT16;
+  case GenComputeType::Int8Float32:
+    return ctranslate2::ComputeType::INT8_FLOAT32;
+  default:
+    return ctranslate2::ComputeType::DEFAULT;
+  }
+}
+
+std::unique_ptr<Generator> new_generator(const ModelReaderHandle &reader,
+                                         bool cuda, GeneratorConfig config) {
+  ctranslate2::ReplicaPoolConfig pool_config;
+  pool_config.num_threads_per_replica = config.num_threads_per_replica;
+  pool_config.max_queued_batches = config.max_queued_batches;
+  pool_config.cpu_core_offset = config.cpu_core_offset;
+
+  ctranslate2::models::ModelLoader loader(reader.impl);
+  loader.device = cuda ? ctranslate2::Device::CUDA : ctranslate2::Device::CPU;
+  loader.device_indices = std::vector<int>(config.device_indices.begin(),
+                                           config.device_indices.end());
+  loader.compute_type = to_compute_type(config.compute_type);
+
+  // The loader is kept to load the models again after they are unloaded.
+  return std::make_unique<Generator>(
+      std::make_shared<ctranslate2::Generator>(loader, pool_config),
+      std::make_unique<ModelState>(loader));
+}
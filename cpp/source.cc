@@ -0,0 +1,66 @@
+// source.cc
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#include "ctrans2/include/source.h"
+#include "ctrans2/include/convert.h"
+#include "ctrans2/src/source.rs.h"
+
+#include <sstream>
+
+using rust::Str;
+using rust::Vec;
+
+// Reads the files of a model with a reader implemented in Rust.
+class RustModelReader : public ctranslate2::models::ModelReader {
+private:
+  rust::Box<ModelReaderBox> reader;
+
+public:
+  RustModelReader(rust::Box<ModelReaderBox> reader)
+      : reader(std::move(reader)) {}
+
+  std::string get_model_id() const override {
+    return static_cast<std::string>(this->reader->model_id());
+  }
+
+  std::unique_ptr<std::istream> get_file(const std::string &filename,
+                                         const bool binary) override {
+    auto file = this->reader->read_file(filename);
+    if (!file.found) {
+      return nullptr;
+    }
+    return std::make_unique<std::istringstream>(
+        std::string(file.content.begin(), file.content.end()),
+        binary ? std::ios::in | std::ios::binary : std::ios::in);
+  }
+};
+
+std::unique_ptr<ModelReaderHandle> new_file_reader(Str model_dir) {
+  return std::make_unique<ModelReaderHandle>(
+      std::make_shared<ctranslate2::models::ModelFileReader>(
+          from_rust(model_dir)));
+}
+
+std::unique_ptr<ModelReaderHandle> new_memory_reader(Str model_id,
+                                                     Vec<ModelFile> files) {
+  auto reader = std::make_shared<ctranslate2::models::ModelMemoryReader>(
+      from_rust(model_id));
+  for (const auto &file : files) {
+    reader->register_file(
+        from_rust(file.name),
+        std::string(reinterpret_cast<const char *>(file.content.data()),
+                    file.content.size()));
+  }
+  return std::make_unique<ModelReaderHandle>(reader);
+}
+
+std::unique_ptr<ModelReaderHandle>
+new_rust_reader(rust::Box<ModelReaderBox> reader) {
+  return std::make_unique<ModelReaderHandle>(
+      std::make_shared<RustModelReader>(std::move(reader)));
+}
@@ -0,0 +1,248 @@
+// translator.cc
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#include "ctrans2/include/translator.h"
+#include "ctrans2/include/convert.h"
+#include "ctrans2/src/translator/translator.rs.h"
+
+#include <ctranslate2/models/sequence_to_sequence.h>
+
+using rust::Str;
+using rust::Vec;
+
+inline ctranslate2::TranslationOptions
+to_translation_options(const TranslationOptions &options) {
+  ctranslate2::TranslationOptions opts;
+  opts.beam_size = options.beam_size;
+  opts.patience = options.patience;
+  opts.length_penalty = options.length_penalty;
+  opts.coverage_penalty = options.coverage_penalty;
+  opts.repetition_penalty = options.repetition_penalty;
+  opts.no_repeat_ngram_size = options.no_repeat_ngram_size;
+  opts.disable_unk = options.disable_unk;
+  opts.suppress_sequences = from_rust(options.suppress_sequences);
+  opts.end_token = end_token_from_rust(options.end_token);
+  opts.prefix_bias_beta = options.prefix_bias_beta;
+  opts.return_end_token = options.return_end_token;
+  opts.max_input_length = options.max_input_length;
+  opts.max_decoding_length = options.max_decoding_length;
+  opts.min_decoding_length = options.min_decoding_length;
+  opts.sampling_topk = options.sampling_topk;
+  opts.sampling_topp = options.sampling_topp;
+  opts.sampling_temperature = options.sampling_temperature;
+  opts.use_vmap = options.use_vmap;
+  opts.num_hypotheses = options.num_hypotheses;
+  opts.return_scores = options.return_scores;
+  opts.return_attention = options.return_attention;
+  opts.return_alternatives = options.return_alternatives;
+  opts.min_alternative_expansion_prob = options.min_alternative_expansion_prob;
+  opts.replace_unknowns = options.replace_unknowns;
+  return opts;
+}
+
+inline ctranslate2::BatchType to_batch_type(TranslationBatchType batch_type) {
+  return batch_type == TranslationBatchType::Examples
+             ? ctranslate2::BatchType::Examples
+             : ctranslate2::BatchType::Tokens;
+}
+
+inline const ctranslate2::models::SequenceToSequenceModel &
+sequence_to_sequence(const ctranslate2::models::Model &model) {
+  return static_cast<const ctranslate2::models::SequenceToSequenceModel &>(
+      model);
+}
+
+inline std::shared_ptr<const ctranslate2::models::Model>
+get_model(const ctranslate2::Translator &translator) {
+  return translator.get_first_replica().model();
+}
+
+// Maps the tokens of the hypotheses to their IDs in the target vocabulary,
+// which are those of the tokenizer since the IDs are passed through unchanged.
+inline Vec<TransVecUSize>
+to_ids(const ctranslate2::Vocabulary &vocabulary,
+       const std::vector<std::vector<std::string>> &hypotheses) {
+  Vec<TransVecUSize> res;
+  for (const auto &hypothesis : hypotheses) {
+    Vec<size_t> ids;
+    for (const auto &token : hypothesis) {
+      ids.push_back(vocabulary.to_id(token));
+    }
+    res.push_back(TransVecUSize{ids});
+  }
+  return res;
+}
+
+inline Vec<TranslationResult> collect_results(
+    std::vector<std::future<ctranslate2::TranslationResult>> &futures,
+    const ctranslate2::models::Model &model) {
+  for (auto &future : futures) {
+    future.wait();
+  }
+
+  const auto &vocabulary =
+      sequence_to_sequence(model).get_target_vocabulary();
+  Vec<TranslationResult> res;
+  for (auto &future : futures) {
+    const auto &r = future.get();
+    res.push_back(TranslationResult{
+        to_rust<TransVecString>(r.hypotheses),
+        to_ids(vocabulary, r.hypotheses),
+        to_rust(r.scores),
+        to_rust<TransAttention, TransVecF32>(r.attention),
+    });
+  }
+  return res;
+}
+
+Vec<TranslationResult> TranslationStream::wait() {
+  return collect_results(this->futures, *this->model);
+}
+
+Vec<TranslationResult>
+Translator::translate_batch(Vec<TransVecStr> source,
+                            Vec<TransVecStr> target_prefix,
+                            TranslationOptions options) const {
+  const auto lock = this->state->lock();
+  const auto model = get_model(*this->impl);
+  auto futures = this->impl->translate_batch_async(
+      from_rust(source), from_rust(target_prefix),
+      to_translation_options(options), options.max_batch_size,
+      to_batch_type(options.batch_type));
+  return collect_results(futures, *model);
+}
+
+// Maps the IDs to the tokens of the given vocabulary. Since the model maps the
+// tokens back with the same vocabulary, the IDs are passed through unchanged.
+inline std::vector<std::vector<std::string>>
+to_tokens(const ctranslate2::Vocabulary &vocabulary,
+          const Vec<TransVecUSize> &ids) {
+  std::vector<std::vector<std::string>> res;
+  for (const auto &item : ids) {
+    std::vector<std::string> tokens;
+    for (const auto id : item.v) {
+      if (id >= vocabulary.size()) {
+        throw std::invalid_argument("token ID " + std::to_string(id) +
+                                    " is out of the vocabulary");
+      }
+      tokens.push_back(vocabulary.to_token(id));
+    }
+    res.push_back(std::move(tokens));
+  }
+  return res;
+}
+
+Vec<TranslationResult>
+Translator::translate_batch_ids(Vec<TransVecUSize> source,
+                                Vec<TransVecUSize> target_prefix,
+                                TranslationOptions options) const {
+  const auto lock = this->state->lock();
+  const auto model = get_model(*this->impl);
+  const auto &vocabularies = sequence_to_sequence(*model);
+  auto futures = this->impl->translate_batch_async(
+      to_tokens(vocabularies.get_source_vocabulary(), source),
+      to_tokens(vocabularies.get_target_vocabulary(), target_prefix),
+      to_translation_options(options), options.max_batch_size,
+      to_batch_type(options.batch_type));
+  return collect_results(futures, *model);
+}
+
+std::unique_ptr<TranslationStream>
+Translator::translate_batch_async(Vec<TransVecStr> source,
+                                  Vec<TransVecStr> target_prefix,
+                                  TranslationOptions options) const {
+  const auto lock = this->state->lock();
+  return std::make_unique<TranslationStream>(
+      this->impl->translate_batch_async(
+          from_rust(source), from_rust(target_prefix),
+          to_translation_options(options), options.max_batch_size,
+          to_batch_type(options.batch_type)),
+      get_model(*this->impl));
+}
+
+std::unique_ptr<TranslationStream>
+Translator::translate_batch_ids_async(Vec<TransVecUSize> source,
+                                      Vec<TransVecUSize> target_prefix,
+                                      TranslationOptions options) const {
+  const auto lock = this->state->lock();
+  const auto model = get_model(*this->impl);
+  const auto &vocabularies = sequence_to_sequence(*model);
+  return std::make_unique<TranslationStream>(
+      this->impl->translate_batch_async(
+          to_tokens(vocabularies.get_source_vocabulary(), source),
+          to_tokens(vocabularies.get_target_vocabulary(), target_prefix),
+          to_translation_options(options), options.max_batch_size,
+          to_batch_type(options.batch_type)),
+      model);
+}
+
+Vec<TransScoringResult>
+Translator::score_batch(Vec<TransVecStr> source, Vec<TransVecStr> target,
+                        TransScoringOptions options) const {
+  const auto lock = this->state->lock();
+  ctranslate2::ScoringOptions opts;
+  opts.max_input_length = options.max_input_length;
+  opts.offset = options.offset;
+
+  auto futures = this->impl->score_batch_async(
+      from_rust(source), from_rust(target), opts, options.max_batch_size,
+      to_batch_type(options.batch_type));
+
+  Vec<TransScoringResult> res;
+  for (auto &future : futures) {
+    const auto &r = future.get();
+    res.push_back(
+        TransScoringResult{to_rust(r.tokens), to_rust(r.tokens_score)});
+  }
+  return res;
+}
+
+inline ctranslate2::ComputeType to_compute_type(TransComputeType compute_type) {
+  switch (compute_type) {
+  case TransComputeType::Auto:
+    return ctranslate2::ComputeType::AUTO;
+  case TransComputeType::Float32:
+    return ctranslate2::ComputeType::FLOAT32;
+  case TransComputeType::Int8:
+    return ctranslate2::ComputeType::INT8;
+  case TransComputeType::Int8Float16:
+    return ctranslate2::ComputeType::INT8_FLOAT16;
+  case TransComputeType::Int16:
+    return ctranslate2::ComputeType::INT16;
+  case TransComputeType::Float16:
+    return ctranslate2::ComputeType::FLOAT16;
+  case TransComputeType::BFloat16:
+    return ctranslate2::ComputeType::BFLOAT16;
+  case TransComputeType::Int8BFloat16:
+    return ctranslate2::ComputeType::INT8_BFLOAT16;
+  case TransComputeType::Int8Float32:
+    return ctranslate2::ComputeType::INT8_FLOAT32;
+  default:
+    return ctranslate2::ComputeType::DEFAULT;
+  }
+}
+
+std::unique_ptr<Translator> new_translator(const ModelReaderHandle &reader,
+                                           bool cuda,
+                                           TranslatorConfig config) {
+  ctranslate2::ReplicaPoolConfig pool_config;
+  pool_config.num_threads_per_replica = config.num_threads_per_replica;
+  pool_config.max_queued_batches = config.max_queued_batches;
+  pool_config.cpu_core_offset = config.cpu_core_offset;
+
+  ctranslate2::models::ModelLoader loader(reader.impl);
+  loader.device = cuda ? ctranslate2::Device::CUDA : ctranslate2::Device::CPU;
+  loader.device_indices = std::vector<int>(config.device_indices.begin(),
+                                           config.device_indices.end());
+  loader.compute_type = to_compute_type(config.compute_type);
+
+  // The loader is kept to load the models again after they are unloaded.
+  return std::make_unique<Translator>(
+      std::make_shared<ctranslate2::Translator>(loader, pool_config),
+      std::make_unique<ModelState>(loader));
+}
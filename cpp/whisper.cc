@@ -0,0 +1,152 @@
+// whisper.cc
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#include "ctrans2/include/whisper.h"
+#include "ctrans2/include/convert.h"
+#include "ctrans2/src/whisper/whisper.rs.h"
+
+using rust::Slice;
+using rust::Str;
+using rust::Vec;
+
+inline ctranslate2::StorageView to_storage_view(Slice<const float> features,
+                                                Slice<const size_t> shape) {
+  return ctranslate2::StorageView(
+      ctranslate2::Shape(shape.begin(), shape.end()),
+      std::vector<float>(features.begin(), features.end()));
+}
+
+bool Whisper::is_multilingual() const { return this->impl->is_multilingual(); }
+
+size_t Whisper::n_mels() const { return this->impl->n_mels(); }
+
+size_t Whisper::num_languages() const { return this->impl->num_languages(); }
+
+Vec<WhisperGenerationResult> Whisper::generate(Slice<const float> features,
+                                               Slice<const size_t> shape,
+                                               Vec<WhisperVecStr> prompts,
+                                               WhisperOptions options) const {
+  ctranslate2::models::WhisperOptions opts;
+  opts.beam_size = options.beam_size;
+  opts.patience = options.patience;
+  opts.length_penalty = options.length_penalty;
+  opts.repetition_penalty = options.repetition_penalty;
+  opts.no_repeat_ngram_size = options.no_repeat_ngram_size;
+  opts.max_length = options.max_length;
+  opts.sampling_topk = options.sampling_topk;
+  opts.sampling_temperature = options.sampling_temperature;
+  opts.num_hypotheses = options.num_hypotheses;
+  opts.return_scores = options.return_scores;
+  opts.return_no_speech_prob = options.return_no_speech_prob;
+  opts.max_initial_timestamp_index = options.max_initial_timestamp_index;
+  opts.suppress_blank = options.suppress_blank;
+  opts.suppress_tokens = from_rust(options.suppress_tokens);
+
+  auto futures = this->impl->generate(to_storage_view(features, shape),
+                                      from_rust(prompts), opts);
+
+  Vec<WhisperGenerationResult> res;
+  for (auto &future : futures) {
+    const auto &r = future.get();
+    res.push_back(WhisperGenerationResult{
+        to_rust<WhisperVecString>(r.sequences),
+        to_rust<WhisperVecUSize>(r.sequences_ids),
+        to_rust(r.scores),
+        r.no_speech_prob,
+    });
+  }
+  return res;
+}
+
+Vec<WhisperLanguageProbs>
+Whisper::detect_language(Slice<const float> features,
+                         Slice<const size_t> shape) const {
+  auto futures =
+      this->impl->detect_language(to_storage_view(features, shape));
+
+  Vec<WhisperLanguageProbs> res;
+  for (auto &future : futures) {
+    WhisperLanguageProbs probs;
+    for (const auto &p : future.get()) {
+      probs.v.push_back(WhisperLanguageProb{to_rust(p.first), p.second});
+    }
+    res.push_back(std::move(probs));
+  }
+  return res;
+}
+
+Vec<WhisperAlignmentResult>
+Whisper::align(Slice<const float> features, Slice<const size_t> shape,
+               Vec<size_t> start_sequence, Vec<WhisperVecUSize> text_tokens,
+               Vec<size_t> num_frames, int64_t median_filter_width) const {
+  std::vector<std::vector<size_t>> tokens;
+  for (const auto &item : text_tokens) {
+    tokens.push_back(from_rust(item.v));
+  }
+
+  auto futures = this->impl->align(
+      to_storage_view(features, shape), from_rust(start_sequence), tokens,
+      from_rust(num_frames), median_filter_width);
+
+  Vec<WhisperAlignmentResult> res;
+  for (auto &future : futures) {
+    const auto &r = future.get();
+    Vec<WhisperAlignment> alignments;
+    for (const auto &a : r.alignments) {
+      alignments.push_back(WhisperAlignment{a.first, a.second});
+    }
+    res.push_back(WhisperAlignmentResult{
+        std::move(alignments),
+        to_rust(r.text_token_probs),
+    });
+  }
+  return res;
+}
+
+inline ctranslate2::ComputeType
+to_compute_type(WhisperComputeType compute_type) {
+  switch (compute_type) {
+  case WhisperComputeType::Auto:
+    return ctranslate2::ComputeType::AUTO;
+  case WhisperComputeType::Float32:
+    return ctranslate2::ComputeType::FLOAT32;
+  case WhisperComputeType::Int8:
+    return ctranslate2::ComputeType::INT8;
+  case WhisperComputeType::Int8Float16:
+    return ctranslate2::ComputeType::INT8_FLOAT16;
+  case WhisperComputeType::Int16:
+    return ctranslate2::ComputeType::INT16;
+  case WhisperComputeType::Float16:
+    return ctranslate2::ComputeType::FLOAT16;
+  case WhisperComputeType::BFloat16:
+    return ctranslate2::ComputeType::BFLOAT16;
+  case WhisperComputeType::Int8BFloat16:
+    return ctranslate2::ComputeType::INT8_BFLOAT16;
+  case WhisperComputeType::Int8Float32:
+    return ctranslate2::ComputeType::INT8_FLOAT32;
+  default:
+    return ctranslate2::ComputeType::DEFAULT;
+  }
+}
+
+std::unique_ptr<Whisper> new_whisper(Str model_path, bool cuda,
+                                     WhisperConfig config) {
+  ctranslate2::ReplicaPoolConfig pool_config;
+  pool_config.num_threads_per_replica = config.num_threads_per_replica;
+  pool_config.max_queued_batches = config.max_queued_batches;
+  pool_config.cpu_core_offset = config.cpu_core_offset;
+
+  return std::make_unique<Whisper>(
+      std::make_shared<ctranslate2::models::Whisper>(
+          from_rust(model_path),
+          cuda ? ctranslate2::Device::CUDA : ctranslate2::Device::CPU,
+          to_compute_type(config.compute_type),
+          std::vector<int>(config.device_indices.begin(),
+                           config.device_indices.end()),
+          pool_config));
+}
@@ -0,0 +1,95 @@
+// generate.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Generates texts with a decoder-only model.
+//!
+//! ```sh
+//! cargo run --example generate -- <model_dir> "Hello, my name is"
+//! ```
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use ctrans2::{ComputeType, Config, Device, GenerationOptions, Generator};
+
+#[derive(Parser, Debug)]
+#[command(about = "Generates texts with a CTranslate2 decoder-only model")]
+struct Args {
+    /// Path to the model directory.
+    model: PathBuf,
+    /// Prompts to generate texts from.
+    #[arg(required = true)]
+    prompts: Vec<String>,
+    /// Device to use (cpu or cuda).
+    #[arg(long, default_value = "cpu")]
+    device: Device,
+    /// Computation type, such as int8 or float16.
+    #[arg(long, default_value = "default")]
+    compute_type: ComputeType,
+    /// Maximum generation length.
+    #[arg(long, default_value_t = 128)]
+    max_length: usize,
+    /// Beam size (1 runs greedy search or random sampling).
+    #[arg(long, default_value_t = 1)]
+    beam_size: usize,
+    /// Randomly sample from the top K candidates.
+    #[arg(long, default_value_t = 1)]
+    sampling_topk: usize,
+    /// Sampling temperature.
+    #[arg(long, default_value_t = 1.)]
+    sampling_temperature: f32,
+    /// Number of hypotheses to return.
+    #[arg(long, default_value_t = 1)]
+    num_hypotheses: usize,
+    /// Print the tokens as they are generated.
+    #[arg(long)]
+    stream: bool,
+}
+
+fn main() -> ctrans2::Result<()> {
+    let args = Args::parse();
+
+    let generator = Generator::new(
+        &args.model,
+        args.device,
+        Config {
+            compute_type: args.compute_type,
+            ..Default::default()
+        },
+    )?;
+    let options = GenerationOptions {
+        max_length: args.max_length,
+        beam_size: args.beam_size,
+        sampling_topk: args.sampling_topk,
+        sampling_temperature: args.sampling_temperature,
+        num_hypotheses: args.num_hypotheses,
+        return_scores: true,
+        ..Default::default()
+    };
+
+    if args.stream {
+        for prompt in args.prompts {
+            for piece in generator.generate_tokens(prompt.as_str(), &options)? {
+                print!("{}", piece?);
+                io::stdout().flush().ok();
+            }
+            println!();
+        }
+        return Ok(());
+    }
+
+    let results = generator.generate_batch(args.prompts, &options)?;
+    for (i, res) in results.iter().enumerate() {
+        for (text, score) in res.texts.iter().zip(&res.scores) {
+            println!("[{i}] ({score:.4}) {text}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,76 @@
+// translate.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Translates texts with a sequence-to-sequence model.
+//!
+//! ```sh
+//! cargo run --example translate -- <model_dir> "Hello world!" --target-prefix deu_Latn
+//! ```
+
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use ctrans2::{ComputeType, Config, Device, TranslationOptions, Translator};
+
+#[derive(Parser, Debug)]
+#[command(about = "Translates texts with a CTranslate2 sequence-to-sequence model")]
+struct Args {
+    /// Path to the model directory.
+    model: PathBuf,
+    /// Texts to translate.
+    #[arg(required = true)]
+    sources: Vec<String>,
+    /// Device to use (cpu or cuda).
+    #[arg(long, default_value = "cpu")]
+    device: Device,
+    /// Computation type, such as int8 or float16.
+    #[arg(long, default_value = "default")]
+    compute_type: ComputeType,
+    /// Tokens forcing the beginning of the translations, e.g. the target language code.
+    #[arg(long)]
+    target_prefix: Vec<String>,
+    /// Beam size.
+    #[arg(long, default_value_t = 2)]
+    beam_size: usize,
+    /// Maximum decoding length.
+    #[arg(long, default_value_t = 256)]
+    max_decoding_length: usize,
+    /// Number of hypotheses to return.
+    #[arg(long, default_value_t = 1)]
+    num_hypotheses: usize,
+}
+
+fn main() -> ctrans2::Result<()> {
+    let args = Args::parse();
+
+    let translator = Translator::new(
+        &args.model,
+        args.device,
+        Config {
+            compute_type: args.compute_type,
+            ..Default::default()
+        },
+    )?;
+    let options = TranslationOptions {
+        beam_size: args.beam_size,
+        max_decoding_length: args.max_decoding_length,
+        num_hypotheses: args.num_hypotheses,
+        return_scores: true,
+        ..Default::default()
+    };
+
+    let target_prefixes = vec![args.target_prefix.clone(); args.sources.len()];
+    let results = translator.translate_batch(args.sources, target_prefixes, &options)?;
+    for (i, res) in results.iter().enumerate() {
+        for (text, score) in res.texts.iter().zip(&res.scores) {
+            println!("[{i}] ({score:.4}) {text}");
+        }
+    }
+    Ok(())
+}
@@ -10,6 +10,7 @@
 
 #include "rust/cxx.h"
 #include <string>
+#include <variant>
 #include <vector>
 
 inline std::string from_rust(const rust::Str &v) {
@@ -42,6 +43,36 @@ inline std::vector<int> from_rust(const rust::Vec<int> &v) {
   return res;
 }
 
+inline std::vector<size_t> from_rust(const rust::Vec<size_t> &v) {
+  return std::vector<size_t>(v.begin(), v.end());
+}
+
+template <typename VecUSize>
+inline std::vector<std::vector<size_t>>
+from_rust_ids(const rust::Vec<VecUSize> &v) {
+  std::vector<std::vector<size_t>> res;
+  for (const auto &item : v) {
+    res.push_back(from_rust(item.v));
+  }
+  return res;
+}
+
+template <typename EndToken>
+inline std::variant<std::string, std::vector<std::string>, std::vector<size_t>>
+end_token_from_rust(const EndToken &v) {
+  using Kind = decltype(v.kind);
+  switch (v.kind) {
+  case Kind::Token:
+    return from_rust(v.tokens[0]);
+  case Kind::Tokens:
+    return from_rust(v.tokens);
+  case Kind::Ids:
+    return from_rust(v.ids);
+  default:
+    return std::string();
+  }
+}
+
 inline rust::String to_rust(const std::string &v) { return rust::String(v); }
 
 inline rust::Vec<rust::String> to_rust(const std::vector<std::string> &v) {
@@ -78,6 +109,15 @@ to_rust(const std::vector<std::vector<float>> &v) {
   return res;
 }
 
+template <typename T>
+inline rust::Vec<T> to_rust(const std::vector<std::vector<float>> &v) {
+  rust::Vec<T> res;
+  for (const auto &item : v) {
+    res.push_back(T{to_rust(item)});
+  }
+  return res;
+}
+
 inline rust::Vec<rust::Vec<rust::Vec<float>>>
 to_rust(const std::vector<std::vector<std::vector<float>>> &v) {
   rust::Vec<rust::Vec<rust::Vec<float>>> res;
@@ -87,6 +127,16 @@ to_rust(const std::vector<std::vector<std::vector<float>>> &v) {
   return res;
 }
 
+template <typename T, typename U>
+inline rust::Vec<T>
+to_rust(const std::vector<std::vector<std::vector<float>>> &v) {
+  rust::Vec<T> res;
+  for (const auto &item : v) {
+    res.push_back(T{to_rust<U>(item)});
+  }
+  return res;
+}
+
 inline rust::Vec<size_t> to_rust(const std::vector<size_t> &v) {
   rust::Vec<size_t> res;
   for (const auto &item : v) {
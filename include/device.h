@@ -0,0 +1,17 @@
+// device.h
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#pragma once
+
+#include "exception.h"
+#include "rust/cxx.h"
+
+int get_cuda_device_count();
+
+rust::Vec<rust::String> get_supported_compute_types(bool cuda,
+                                                    int device_index);
@@ -0,0 +1,38 @@
+// encoder.h
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#pragma once
+
+#include "exception.h"
+#include "rust/cxx.h"
+
+#include <ctranslate2/encoder.h>
+#include <memory>
+
+struct EncVecStr;
+struct EncVecUSize;
+struct EncoderConfig;
+struct EncoderForwardOutput;
+
+class Encoder {
+private:
+  std::shared_ptr<ctranslate2::Encoder> impl;
+
+public:
+  Encoder(std::shared_ptr<ctranslate2::Encoder> impl) : impl(impl) {}
+
+  EncoderForwardOutput forward_batch(rust::Vec<EncVecStr> tokens,
+                                     rust::Vec<EncVecUSize> token_type_ids) const;
+
+  EncoderForwardOutput
+  forward_batch_ids(rust::Vec<EncVecUSize> ids,
+                    rust::Vec<EncVecUSize> token_type_ids) const;
+};
+
+std::unique_ptr<Encoder> new_encoder(rust::Str model_path, bool cuda,
+                                     EncoderConfig config);
@@ -0,0 +1,45 @@
+// exception.h
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#pragma once
+
+#include <new>
+#include <stdexcept>
+#include <string>
+
+// Thrown when a request is submitted to a model unloaded by `unload_model`.
+class ModelUnloadedError : public std::runtime_error {
+public:
+  ModelUnloadedError()
+      : std::runtime_error("the model is unloaded; call load_model first") {}
+};
+
+// Converts the C++ exceptions to the errors returned to Rust. The type of the
+// exception is kept as a prefix of the message so that the Rust side can tell
+// invalid requests, allocation failures and unloaded models from the other
+// errors (see src/error.rs).
+namespace rust {
+namespace behavior {
+
+template <typename Try, typename Fail>
+static void trycatch(Try &&func, Fail &&fail) noexcept try {
+  func();
+} catch (const ModelUnloadedError &e) {
+  fail((std::string("model_unloaded: ") + e.what()).c_str());
+} catch (const std::invalid_argument &e) {
+  fail((std::string("invalid_argument: ") + e.what()).c_str());
+} catch (const std::out_of_range &e) {
+  fail((std::string("invalid_argument: ") + e.what()).c_str());
+} catch (const std::bad_alloc &e) {
+  fail((std::string("bad_alloc: ") + e.what()).c_str());
+} catch (const std::exception &e) {
+  fail(e.what());
+}
+
+} // namespace behavior
+} // namespace rust
@@ -8,27 +8,102 @@
 
 #pragma once
 
+#include "exception.h"
+#include "model_state.h"
+#include "source.h"
 #include "rust/cxx.h"
 
 #include <ctranslate2/generator.h>
+#include <future>
 #include <memory>
+#include <vector>
 
 struct GenVecStr;
+struct GenVecUSize;
 struct GeneratorConfig;
 struct GenerationOptions;
 struct GenerationResult;
+struct GenerationCallbackBox;
+struct GenScoringOptions;
+struct GenScoringResult;
+
+class GenerationStream {
+private:
+  std::vector<std::future<ctranslate2::GenerationResult>> futures;
+
+public:
+  GenerationStream(
+      std::vector<std::future<ctranslate2::GenerationResult>> futures)
+      : futures(std::move(futures)) {}
+
+  rust::Vec<GenerationResult> wait();
+};
 
 class Generator {
 private:
   std::shared_ptr<ctranslate2::Generator> impl;
+  std::unique_ptr<ModelState> state;
 
 public:
-  Generator(std::shared_ptr<ctranslate2::Generator> impl) : impl(impl) {}
+  Generator(std::shared_ptr<ctranslate2::Generator> impl,
+            std::unique_ptr<ModelState> state)
+      : impl(impl), state(std::move(state)) {}
 
   rust::Vec<GenerationResult> generate_batch(rust::Vec<GenVecStr> start_tokens,
                                              GenerationOptions options) const;
-  
+
+  rust::Vec<GenerationResult>
+  generate_batch_ids(rust::Vec<GenVecUSize> start_ids,
+                     GenerationOptions options) const;
+
+  rust::Vec<GenerationResult>
+  generate_batch_with_callback(rust::Vec<GenVecStr> start_tokens,
+                               GenerationOptions options,
+                               GenerationCallbackBox &callback) const;
+
+  rust::Vec<GenerationResult>
+  generate_batch_ids_with_callback(rust::Vec<GenVecUSize> start_ids,
+                                   GenerationOptions options,
+                                   GenerationCallbackBox &callback) const;
+
+  std::unique_ptr<GenerationStream>
+  generate_batch_async(rust::Vec<GenVecStr> start_tokens,
+                       GenerationOptions options) const;
+
+  std::unique_ptr<GenerationStream>
+  generate_batch_ids_async(rust::Vec<GenVecUSize> start_ids,
+                           GenerationOptions options) const;
+
+  rust::Vec<GenScoringResult> score_batch(rust::Vec<GenVecStr> tokens,
+                                          GenScoringOptions options) const;
+
+  std::unique_ptr<GenerationStream>
+  generate_stream(rust::Vec<GenVecStr> start_tokens, GenerationOptions options,
+                  rust::Box<GenerationCallbackBox> callback) const;
+
+  std::unique_ptr<GenerationStream>
+  generate_stream_ids(rust::Vec<GenVecUSize> start_ids,
+                      GenerationOptions options,
+                      rust::Box<GenerationCallbackBox> callback) const;
+
+  size_t num_replicas() const { return this->impl->num_replicas(); }
+
+  size_t num_queued_batches() const {
+    return this->impl->num_queued_batches();
+  }
+
+  size_t num_active_batches() const {
+    return this->impl->num_active_batches();
+  }
+
+  bool unload_model(bool to_cpu) const {
+    return this->state->unload(*this->impl, to_cpu);
+  }
+
+  void load_model() const { this->state->load(*this->impl); }
+
+  bool model_is_loaded() const { return this->state->is_loaded(); }
 };
 
-std::unique_ptr<Generator> new_generator(rust::Str model_path, bool cuda,
-                                         GeneratorConfig config);
\ No newline at end of file
+std::unique_ptr<Generator> new_generator(const ModelReaderHandle &reader,
+                                         bool cuda, GeneratorConfig config);
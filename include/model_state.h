@@ -0,0 +1,107 @@
+// model_state.h
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#pragma once
+
+#include "exception.h"
+
+#include <ctranslate2/models/model.h>
+#include <memory>
+#include <mutex>
+#include <shared_mutex>
+#include <vector>
+
+// Whether the models of a replica pool are loaded, with the models parked in
+// the CPU memory while they are unloaded.
+//
+// The requests hold a shared lock while they are submitted, and the blocking
+// ones until they finish, so that the models are not detached from the pool
+// while a batch is being queued. The batches queued or running in the
+// background are counted by `num_active_batches`, and the models are only
+// unloaded when there are none.
+class ModelState {
+private:
+  mutable std::shared_mutex mutex;
+  ctranslate2::models::ModelLoader loader;
+  std::vector<std::shared_ptr<const ctranslate2::models::Model>> cached_models;
+  bool loaded = true;
+
+  void move_cached_models(ctranslate2::Device device,
+                          const std::vector<int> &device_indices) {
+    for (size_t i = 0; i < this->cached_models.size(); ++i) {
+      auto &model =
+          const_cast<ctranslate2::models::Model &>(*this->cached_models[i]);
+      model.set_device(device, device_indices[i]);
+    }
+  }
+
+public:
+  ModelState(ctranslate2::models::ModelLoader loader)
+      : loader(std::move(loader)) {}
+
+  // Locks the models in the pool while a request is submitted.
+  std::shared_lock<std::shared_mutex> lock() const {
+    std::shared_lock<std::shared_mutex> lock(this->mutex);
+    if (!this->loaded) {
+      throw ModelUnloadedError();
+    }
+    return lock;
+  }
+
+  bool is_loaded() const {
+    std::shared_lock<std::shared_mutex> lock(this->mutex);
+    return this->loaded;
+  }
+
+  // Detaches the models from the pool, keeping them in the CPU memory if
+  // `to_cpu` is set. Returns false if some batches are still queued or
+  // running, in which case the models are kept loaded.
+  template <typename Pool> bool unload(Pool &pool, bool to_cpu) {
+    std::unique_lock<std::shared_mutex> lock(this->mutex);
+    if (!this->loaded) {
+      return true;
+    }
+    if (pool.num_active_batches() > 0) {
+      return false;
+    }
+
+    this->cached_models = pool.detach_models();
+    if (!to_cpu) {
+      this->cached_models.clear();
+    } else if (this->loader.device != ctranslate2::Device::CPU) {
+      this->move_cached_models(
+          ctranslate2::Device::CPU,
+          std::vector<int>(this->cached_models.size(), 0));
+    }
+    // Release the memory cached by the CUDA allocator.
+    if (this->loader.device == ctranslate2::Device::CUDA) {
+      pool.clear_cache();
+    }
+    this->loaded = false;
+    return true;
+  }
+
+  // Attaches the models parked in the CPU memory to the pool, or loads them
+  // again from the model directory.
+  template <typename Pool> void load(Pool &pool) {
+    std::unique_lock<std::shared_mutex> lock(this->mutex);
+    if (this->loaded) {
+      return;
+    }
+
+    if (this->cached_models.empty()) {
+      this->cached_models = this->loader.load();
+    } else if (this->loader.device != ctranslate2::Device::CPU) {
+      this->move_cached_models(this->loader.device,
+                               this->loader.device_indices);
+    }
+    pool.set_models(this->cached_models);
+    this->cached_models.clear();
+    this->loaded = true;
+  }
+};
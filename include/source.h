@@ -0,0 +1,35 @@
+// source.h
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#pragma once
+
+#include "exception.h"
+#include "rust/cxx.h"
+
+#include <ctranslate2/models/model_reader.h>
+#include <memory>
+
+struct ModelFile;
+struct ModelReaderBox;
+
+// A model reader passed to the constructors of the models.
+class ModelReaderHandle {
+public:
+  std::shared_ptr<ctranslate2::models::ModelReader> impl;
+
+  ModelReaderHandle(std::shared_ptr<ctranslate2::models::ModelReader> impl)
+      : impl(std::move(impl)) {}
+};
+
+std::unique_ptr<ModelReaderHandle> new_file_reader(rust::Str model_dir);
+
+std::unique_ptr<ModelReaderHandle>
+new_memory_reader(rust::Str model_id, rust::Vec<ModelFile> files);
+
+std::unique_ptr<ModelReaderHandle>
+new_rust_reader(rust::Box<ModelReaderBox> reader);
@@ -8,27 +8,91 @@
 
 #pragma once
 
+#include "exception.h"
+#include "model_state.h"
+#include "source.h"
 #include "rust/cxx.h"
 
 #include <ctranslate2/translator.h>
+#include <future>
 #include <memory>
+#include <vector>
 
-struct VecStr;
+struct TransVecStr;
+struct TransVecUSize;
 struct TranslatorConfig;
 struct TranslationOptions;
 struct TranslationResult;
+struct TransScoringOptions;
+struct TransScoringResult;
+
+class TranslationStream {
+private:
+  std::vector<std::future<ctranslate2::TranslationResult>> futures;
+  // The model whose target vocabulary maps the hypotheses to IDs.
+  std::shared_ptr<const ctranslate2::models::Model> model;
+
+public:
+  TranslationStream(
+      std::vector<std::future<ctranslate2::TranslationResult>> futures,
+      std::shared_ptr<const ctranslate2::models::Model> model)
+      : futures(std::move(futures)), model(std::move(model)) {}
+
+  rust::Vec<TranslationResult> wait();
+};
 
 class Translator {
 private:
   std::shared_ptr<ctranslate2::Translator> impl;
+  std::unique_ptr<ModelState> state;
 
 public:
-  Translator(std::shared_ptr<ctranslate2::Translator> impl) : impl(impl) {}
+  Translator(std::shared_ptr<ctranslate2::Translator> impl,
+             std::unique_ptr<ModelState> state)
+      : impl(impl), state(std::move(state)) {}
 
   rust::Vec<TranslationResult>
-  translate_batch(rust::Vec<VecStr> source, rust::Vec<VecStr> target_prefix,
+  translate_batch(rust::Vec<TransVecStr> source,
+                  rust::Vec<TransVecStr> target_prefix,
                   TranslationOptions options) const;
+
+  rust::Vec<TranslationResult>
+  translate_batch_ids(rust::Vec<TransVecUSize> source,
+                      rust::Vec<TransVecUSize> target_prefix,
+                      TranslationOptions options) const;
+
+  std::unique_ptr<TranslationStream>
+  translate_batch_async(rust::Vec<TransVecStr> source,
+                        rust::Vec<TransVecStr> target_prefix,
+                        TranslationOptions options) const;
+
+  std::unique_ptr<TranslationStream>
+  translate_batch_ids_async(rust::Vec<TransVecUSize> source,
+                            rust::Vec<TransVecUSize> target_prefix,
+                            TranslationOptions options) const;
+
+  rust::Vec<TransScoringResult> score_batch(rust::Vec<TransVecStr> source,
+                                            rust::Vec<TransVecStr> target,
+                                            TransScoringOptions options) const;
+
+  size_t num_replicas() const { return this->impl->num_replicas(); }
+
+  size_t num_queued_batches() const {
+    return this->impl->num_queued_batches();
+  }
+
+  size_t num_active_batches() const {
+    return this->impl->num_active_batches();
+  }
+
+  bool unload_model(bool to_cpu) const {
+    return this->state->unload(*this->impl, to_cpu);
+  }
+
+  void load_model() const { this->state->load(*this->impl); }
+
+  bool model_is_loaded() const { return this->state->is_loaded(); }
 };
 
-std::unique_ptr<Translator> new_translator(rust::Str model_path, bool cuda,
-                                           TranslatorConfig config);
\ No newline at end of file
+std::unique_ptr<Translator> new_translator(const ModelReaderHandle &reader,
+                                           bool cuda, TranslatorConfig config);
@@ -0,0 +1,54 @@
+// whisper.h
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+#pragma once
+
+#include "exception.h"
+#include "rust/cxx.h"
+
+#include <ctranslate2/models/whisper.h>
+#include <memory>
+
+struct WhisperVecStr;
+struct WhisperVecUSize;
+struct WhisperConfig;
+struct WhisperOptions;
+struct WhisperGenerationResult;
+struct WhisperLanguageProbs;
+struct WhisperAlignmentResult;
+
+class Whisper {
+private:
+  std::shared_ptr<ctranslate2::models::Whisper> impl;
+
+public:
+  Whisper(std::shared_ptr<ctranslate2::models::Whisper> impl) : impl(impl) {}
+
+  bool is_multilingual() const;
+
+  size_t n_mels() const;
+
+  size_t num_languages() const;
+
+  rust::Vec<WhisperGenerationResult>
+  generate(rust::Slice<const float> features, rust::Slice<const size_t> shape,
+           rust::Vec<WhisperVecStr> prompts, WhisperOptions options) const;
+
+  rust::Vec<WhisperLanguageProbs>
+  detect_language(rust::Slice<const float> features,
+                  rust::Slice<const size_t> shape) const;
+
+  rust::Vec<WhisperAlignmentResult>
+  align(rust::Slice<const float> features, rust::Slice<const size_t> shape,
+        rust::Vec<size_t> start_sequence,
+        rust::Vec<WhisperVecUSize> text_tokens, rust::Vec<size_t> num_frames,
+        int64_t median_filter_width) const;
+};
+
+std::unique_ptr<Whisper> new_whisper(rust::Str model_path, bool cuda,
+                                     WhisperConfig config);
@@ -0,0 +1,630 @@
+// api.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Request and response bodies of the OpenAI-compatible API.
+
+use std::mem;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tokenizers::Tokenizer;
+
+use ctrans2::{ChatMessage, EndToken, Error, GenerationOptions, TranslationOptions};
+
+/// Default maximum number of generated tokens when `max_tokens` is not given.
+const DEFAULT_MAX_TOKENS: usize = 256;
+
+/// A string or a list of strings.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            OneOrMany::One(s) => vec![s.clone()],
+            OneOrMany::Many(v) => v.clone(),
+        }
+    }
+}
+
+/// Sampling parameters shared by the completion endpoints.
+#[derive(Deserialize, Debug, Default)]
+pub struct SamplingParams {
+    /// Maximum number of generated tokens, mapped to `max_length`.
+    pub max_tokens: Option<usize>,
+    /// Sampling temperature; 0 runs greedy search.
+    pub temperature: Option<f32>,
+    /// Nucleus sampling probability, mapped to `sampling_topp`.
+    pub top_p: Option<f32>,
+    /// Sample from the top K candidates (not part of the OpenAI API).
+    pub top_k: Option<usize>,
+    /// Number of choices, mapped to `num_hypotheses`.
+    pub n: Option<usize>,
+    /// Sequences to stop the generation on, in addition to the EOS token.
+    pub stop: Option<OneOrMany>,
+    /// Stream the generated text as server-sent events.
+    #[serde(default)]
+    pub stream: bool,
+}
+
+impl SamplingParams {
+    /// Maps the parameters to the generation options, returning them with the stop sequences
+    /// to truncate the generated texts at.
+    ///
+    /// The stop sequences encoded to a single token are also end tokens, so that the model
+    /// stops on them. Since they replace the default end token of the model, `eos_token` is
+    /// added to them.
+    pub fn generation_options(
+        &self,
+        tokenizer: &Tokenizer,
+        eos_token: Option<&str>,
+    ) -> Result<(GenerationOptions<String, String>, StopSequences), ApiError> {
+        let n = self.n.unwrap_or(1);
+        if n == 0 {
+            return Err(ApiError::invalid_request("n must be at least 1"));
+        }
+        if self.stream && n > 1 {
+            return Err(ApiError::invalid_request(
+                "n must be 1 when streaming the results",
+            ));
+        }
+
+        let mut options = GenerationOptions {
+            max_length: self.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            // The choices are sampled independently rather than taken from a beam.
+            beam_size: 1,
+            num_hypotheses: n,
+            sampling_topp: self.top_p.unwrap_or(1.),
+            include_prompt_in_result: false,
+            return_scores: true,
+            ..Default::default()
+        };
+        match self.temperature {
+            Some(t) if t < 0. => {
+                return Err(ApiError::invalid_request(
+                    "temperature must not be negative",
+                ))
+            }
+            // Greedy search returns a single hypothesis per prompt.
+            Some(0.) if n > 1 => {
+                return Err(ApiError::invalid_request(
+                    "n must be 1 when the temperature is 0",
+                ))
+            }
+            Some(0.) => options.sampling_topk = 1,
+            t => {
+                options.sampling_temperature = t.unwrap_or(1.);
+                options.sampling_topk = self.top_k.unwrap_or(0);
+            }
+        }
+
+        let stop = StopSequences(self.stop.as_ref().map(|s| s.to_vec()).unwrap_or_default());
+        if !stop.0.is_empty() {
+            let mut tokens = Vec::new();
+            for sequence in &stop.0 {
+                let encoding = tokenizer
+                    .encode(sequence.as_str(), false)
+                    .map_err(|err| ApiError::invalid_request(err.to_string()))?;
+                if let [id] = encoding.get_ids() {
+                    tokens.extend(tokenizer.id_to_token(*id));
+                }
+            }
+            tokens.extend(eos_token.map(|t| t.to_string()));
+            options.end_token = Some(EndToken::Tokens(tokens));
+        }
+        options.validate()?;
+        Ok((options, stop))
+    }
+}
+
+/// The stop sequences of a request, matched on the generated text.
+#[derive(Clone, Debug, Default)]
+pub struct StopSequences(Vec<String>);
+
+impl StopSequences {
+    /// Truncates the text before the first stop sequence, returning true if one is found.
+    pub fn truncate(&self, text: &mut String) -> bool {
+        let first = self
+            .0
+            .iter()
+            .filter_map(|sequence| text.find(sequence.as_str()))
+            .min();
+        if let Some(pos) = first {
+            text.truncate(pos);
+        }
+        first.is_some()
+    }
+
+    /// Returns the position of the end of the text which may begin a stop sequence.
+    fn partial_match(&self, text: &str) -> usize {
+        text.char_indices()
+            .map(|(i, _)| i)
+            .find(|&i| {
+                self.0
+                    .iter()
+                    .any(|sequence| sequence.starts_with(&text[i..]))
+            })
+            .unwrap_or(text.len())
+    }
+
+    /// Returns a matcher of the stop sequences on streamed text.
+    pub fn matcher(self) -> StopMatcher {
+        StopMatcher {
+            stop: self,
+            pending: String::new(),
+        }
+    }
+}
+
+/// Matches the stop sequences on the pieces of a streamed text, holding back the text which
+/// may begin a stop sequence until the next pieces tell.
+pub struct StopMatcher {
+    stop: StopSequences,
+    pending: String,
+}
+
+impl StopMatcher {
+    /// Adds a piece of the text, returning the text which can be sent and whether a stop
+    /// sequence is found, which ends the text.
+    pub fn push(&mut self, piece: &str) -> (String, bool) {
+        self.pending.push_str(piece);
+        if self.stop.truncate(&mut self.pending) {
+            return (mem::take(&mut self.pending), true);
+        }
+        let held = self
+            .pending
+            .split_off(self.stop.partial_match(&self.pending));
+        (mem::replace(&mut self.pending, held), false)
+    }
+
+    /// Returns the text held back at the end of the stream.
+    pub fn finish(self) -> String {
+        self.pending
+    }
+}
+
+// The `model` field of the requests is ignored since each endpoint serves a single model.
+
+/// Body of `POST /v1/completions`.
+#[derive(Deserialize, Debug)]
+pub struct CompletionRequest {
+    pub prompt: OneOrMany,
+    #[serde(flatten)]
+    pub params: SamplingParams,
+}
+
+/// Body of `POST /v1/chat/completions`.
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionRequest {
+    pub messages: Vec<ChatMessage>,
+    #[serde(flatten)]
+    pub params: SamplingParams,
+}
+
+/// Body of `POST /v1/translate`.
+#[derive(Deserialize, Debug)]
+pub struct TranslationRequest {
+    /// Texts to translate.
+    #[serde(alias = "text", alias = "source")]
+    pub input: OneOrMany,
+    /// Tokens forcing the beginning of the translations, e.g. the target language code.
+    #[serde(default)]
+    pub target_prefix: Vec<String>,
+    pub beam_size: Option<usize>,
+    pub max_tokens: Option<usize>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub n: Option<usize>,
+}
+
+impl TranslationRequest {
+    /// Maps the parameters to the translation options.
+    pub fn translation_options(&self) -> Result<TranslationOptions<String>, ApiError> {
+        let mut options = TranslationOptions {
+            return_scores: true,
+            ..Default::default()
+        };
+        if let Some(n) = self.n {
+            if n == 0 {
+                return Err(ApiError::invalid_request("n must be at least 1"));
+            }
+            options.num_hypotheses = n;
+            options.beam_size = options.beam_size.max(n);
+        }
+        if let Some(beam_size) = self.beam_size {
+            options.beam_size = beam_size;
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            options.max_decoding_length = max_tokens;
+        }
+        if let Some(top_p) = self.top_p {
+            options.sampling_topp = top_p;
+        }
+        match self.temperature {
+            Some(t) if t < 0. => {
+                return Err(ApiError::invalid_request(
+                    "temperature must not be negative",
+                ))
+            }
+            Some(t) if t > 0. => {
+                options.sampling_temperature = t;
+                options.sampling_topk = 0;
+            }
+            _ => {}
+        }
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+/// Token counts of a request.
+#[derive(Serialize, Debug, Default)]
+pub struct Usage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub total_tokens: usize,
+}
+
+impl Usage {
+    pub fn new(prompt_tokens: usize, completion_tokens: usize) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+}
+
+/// Body of the responses of `POST /v1/completions`, also sent as the chunks of a stream.
+#[derive(Serialize, Debug)]
+pub struct CompletionResponse {
+    pub id: String,
+    pub object: &'static str,
+    pub created: u64,
+    pub model: String,
+    pub choices: Vec<CompletionChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CompletionChoice {
+    pub index: usize,
+    pub text: String,
+    pub logprobs: Option<()>,
+    pub finish_reason: Option<&'static str>,
+}
+
+/// Body of the responses of `POST /v1/chat/completions`.
+#[derive(Serialize, Debug)]
+pub struct ChatCompletionResponse {
+    pub id: String,
+    pub object: &'static str,
+    pub created: u64,
+    pub model: String,
+    pub choices: Vec<ChatChoice>,
+    pub usage: Usage,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChatChoice {
+    pub index: usize,
+    pub message: ChatMessage,
+    pub finish_reason: &'static str,
+}
+
+/// A chunk of a streamed chat completion.
+#[derive(Serialize, Debug)]
+pub struct ChatCompletionChunk {
+    pub id: String,
+    pub object: &'static str,
+    pub created: u64,
+    pub model: String,
+    pub choices: Vec<ChatChunkChoice>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChatChunkChoice {
+    pub index: usize,
+    pub delta: Delta,
+    pub finish_reason: Option<&'static str>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Delta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+/// Body of the responses of `POST /v1/translate`.
+#[derive(Serialize, Debug)]
+pub struct TranslationResponse {
+    pub object: &'static str,
+    pub model: String,
+    pub translations: Vec<Translation>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Translation {
+    pub index: usize,
+    /// The best translation.
+    pub text: String,
+    /// All the hypotheses, best first.
+    pub hypotheses: Vec<Hypothesis>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Hypothesis {
+    pub text: String,
+    pub score: Option<f32>,
+}
+
+/// Body of the responses of `GET /v1/models`.
+#[derive(Serialize, Debug)]
+pub struct ModelList {
+    pub object: &'static str,
+    pub data: Vec<ModelCard>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ModelCard {
+    pub id: String,
+    pub object: &'static str,
+    pub owned_by: &'static str,
+}
+
+/// An error returned in the format of the OpenAI API.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    kind: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    pub fn invalid_request<T: Into<String>>(message: T) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            kind: "invalid_request_error",
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found<T: Into<String>>(message: T) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            kind: "invalid_request_error",
+            message: message.into(),
+        }
+    }
+
+    pub fn internal<T: Into<String>>(message: T) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            kind: "server_error",
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        if err.is_client_error() {
+            ApiError::invalid_request(err.to_string())
+        } else if err.is_retryable() {
+            Self {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                kind: "server_error",
+                message: err.to_string(),
+            }
+        } else {
+            ApiError::internal(err.to_string())
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    message: &'a str,
+    #[serde(rename = "type")]
+    kind: &'a str,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: ErrorDetail {
+                message: &self.message,
+                kind: self.kind,
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::whitespace::WhitespaceSplit;
+
+    use super::*;
+
+    /// A tokenizer splitting the texts on whitespace.
+    fn tokenizer() -> Tokenizer {
+        let vocab = ["<unk>", "</s>", "Hello", "world", "Human:"]
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), id as u32))
+            .collect();
+        let model = WordLevel::builder()
+            .vocab(vocab)
+            .unk_token("<unk>".to_string())
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(WhitespaceSplit);
+        tokenizer
+    }
+
+    fn sampling_params(json: &str) -> SamplingParams {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn sampling_params_are_mapped_to_the_options() {
+        let params = sampling_params(r#"{"max_tokens": 16, "temperature": 0.7, "top_p": 0.9}"#);
+        let (options, _) = params.generation_options(&tokenizer(), None).unwrap();
+        assert_eq!(options.max_length, 16);
+        assert_eq!(options.sampling_temperature, 0.7);
+        assert_eq!(options.sampling_topp, 0.9);
+        assert_eq!(options.sampling_topk, 0);
+        assert_eq!(options.beam_size, 1);
+        assert!(!options.include_prompt_in_result);
+        assert!(options.end_token.is_none());
+
+        let params = sampling_params(r#"{"n": 3}"#);
+        let (options, _) = params.generation_options(&tokenizer(), None).unwrap();
+        assert_eq!(options.max_length, DEFAULT_MAX_TOKENS);
+        assert_eq!(options.num_hypotheses, 3);
+    }
+
+    #[test]
+    fn zero_temperature_is_greedy_search() {
+        let params = sampling_params(r#"{"temperature": 0, "top_k": 40}"#);
+        let (options, _) = params.generation_options(&tokenizer(), None).unwrap();
+        assert_eq!(options.sampling_topk, 1);
+    }
+
+    #[test]
+    fn invalid_sampling_params_are_rejected() {
+        for json in [
+            r#"{"temperature": -0.5}"#,
+            r#"{"n": 0}"#,
+            r#"{"n": 2, "temperature": 0}"#,
+            r#"{"n": 2, "stream": true}"#,
+            r#"{"top_p": 1.5}"#,
+        ] {
+            let err = sampling_params(json)
+                .generation_options(&tokenizer(), None)
+                .unwrap_err();
+            assert_eq!(err.status, StatusCode::BAD_REQUEST, "{json}");
+        }
+    }
+
+    #[test]
+    fn eos_token_is_added_to_the_stop_tokens() {
+        let params = sampling_params(r#"{"stop": "Human:"}"#);
+        let (options, _) = params
+            .generation_options(&tokenizer(), Some("</s>"))
+            .unwrap();
+        assert!(matches!(
+            options.end_token,
+            Some(EndToken::Tokens(tokens)) if tokens == ["Human:", "</s>"]
+        ));
+    }
+
+    #[test]
+    fn single_token_stop_sequences_are_end_tokens() {
+        let params = SamplingParams {
+            stop: Some(OneOrMany::Many(vec![
+                "Human:".to_string(),
+                "Hello world".to_string(),
+            ])),
+            ..Default::default()
+        };
+        let (options, stop) = params
+            .generation_options(&tokenizer(), Some("</s>"))
+            .unwrap();
+        // The sequence of two tokens is only matched on the text.
+        assert!(matches!(
+            options.end_token,
+            Some(EndToken::Tokens(tokens)) if tokens == ["Human:", "</s>"]
+        ));
+        let mut text = "Hi! Hello world".to_string();
+        assert!(stop.truncate(&mut text));
+        assert_eq!(text, "Hi! ");
+    }
+
+    fn translation_request(json: &str) -> TranslationRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn translation_params_are_mapped_to_the_options() {
+        let req = translation_request(r#"{"input": "Hello", "n": 3, "max_tokens": 32}"#);
+        let options = req.translation_options().unwrap();
+        assert_eq!(options.num_hypotheses, 3);
+        assert_eq!(options.beam_size, 3);
+        assert_eq!(options.max_decoding_length, 32);
+        assert!(options.return_scores);
+
+        let req = translation_request(r#"{"text": "Hello", "temperature": 0.8, "beam_size": 1}"#);
+        let options = req.translation_options().unwrap();
+        assert_eq!(options.beam_size, 1);
+        assert_eq!(options.sampling_temperature, 0.8);
+        assert_eq!(options.sampling_topk, 0);
+    }
+
+    #[test]
+    fn invalid_translation_params_are_rejected() {
+        for json in [
+            r#"{"input": "Hello", "temperature": -1}"#,
+            r#"{"input": "Hello", "n": 0}"#,
+            r#"{"input": "Hello", "beam_size": 0}"#,
+        ] {
+            let err = translation_request(json).translation_options().unwrap_err();
+            assert_eq!(err.status, StatusCode::BAD_REQUEST, "{json}");
+        }
+    }
+
+    fn stop(sequences: &[&str]) -> StopSequences {
+        StopSequences(sequences.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn text_is_truncated_at_the_first_stop_sequence() {
+        let stop = stop(&["\n", "Human:"]);
+        let mut text = "Hello!\nHuman: hi".to_string();
+        assert!(stop.truncate(&mut text));
+        assert_eq!(text, "Hello!");
+
+        let mut text = "Hello! Human: hi\n".to_string();
+        assert!(stop.truncate(&mut text));
+        assert_eq!(text, "Hello! ");
+
+        let mut text = "Hello!".to_string();
+        assert!(!stop.truncate(&mut text));
+        assert_eq!(text, "Hello!");
+    }
+
+    #[test]
+    fn streamed_text_is_held_back_while_it_may_begin_a_stop_sequence() {
+        let mut matcher = stop(&["Human:"]).matcher();
+        assert_eq!(matcher.push("Hello! Hu"), ("Hello! ".to_string(), false));
+        assert_eq!(matcher.push("go"), ("Hugo".to_string(), false));
+        assert_eq!(matcher.push(" Hum"), (" ".to_string(), false));
+        assert_eq!(matcher.push("an: hi"), (String::new(), true));
+
+        let mut matcher = stop(&["Human:"]).matcher();
+        assert_eq!(matcher.push("Hi Hu"), ("Hi ".to_string(), false));
+        assert_eq!(matcher.finish(), "Hu");
+    }
+}
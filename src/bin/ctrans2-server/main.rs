@@ -0,0 +1,527 @@
+// main.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! HTTP server exposing a generator and a translator through an OpenAI-compatible API.
+//!
+//! ```sh
+//! ctrans2-server --generator <model_dir> --translator <model_dir> --port 8000
+//! curl http://127.0.0.1:8000/v1/chat/completions -H 'Content-Type: application/json' \
+//!     -d '{"messages": [{"role": "user", "content": "Hello!"}], "stream": true}'
+//! ```
+
+use std::convert::Infallible;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use clap::Parser;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio_stream::wrappers::ReceiverStream;
+
+use ctrans2::metrics;
+use ctrans2::{
+    ChatMessage, ComputeType, Config, Device, GeneratedText, Generator, ModelDir, Translator,
+};
+
+use self::api::{
+    ApiError, ChatChoice, ChatChunkChoice, ChatCompletionChunk, ChatCompletionRequest,
+    ChatCompletionResponse, CompletionChoice, CompletionRequest, CompletionResponse, Delta,
+    Hypothesis, ModelCard, ModelList, StopSequences, Translation, TranslationRequest,
+    TranslationResponse, Usage,
+};
+use self::worker::StreamInput;
+
+mod api;
+mod worker;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "ctrans2-server",
+    version,
+    about = "Serves CTranslate2 models through an OpenAI-compatible API"
+)]
+struct Args {
+    /// Directory of the decoder-only model serving the completion endpoints.
+    #[arg(long, required_unless_present = "translator")]
+    generator: Option<PathBuf>,
+    /// Directory of the sequence-to-sequence model serving `/v1/translate`.
+    #[arg(long)]
+    translator: Option<PathBuf>,
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    /// Port to listen on.
+    #[arg(long, default_value_t = 8000)]
+    port: u16,
+    /// Device to use (cpu, cuda, or auto).
+    #[arg(long, default_value = "cpu")]
+    device: Device,
+    /// Computation type, such as int8 or float16.
+    #[arg(long, default_value = "default")]
+    compute_type: ComputeType,
+    /// Number of threads per replica (0 uses the default value).
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+}
+
+impl Args {
+    fn config(&self) -> Config {
+        Config {
+            compute_type: self.compute_type,
+            num_threads_per_replica: self.threads,
+            ..Default::default()
+        }
+    }
+}
+
+/// A loaded model, shared by the handlers.
+struct Model<M> {
+    /// Name of the model returned in the responses, i.e. the name of its directory.
+    name: String,
+    model: Arc<M>,
+}
+
+struct AppState {
+    generator: Option<Model<Generator>>,
+    /// EOS token of the generator, which still ends the generation when `stop` is given.
+    eos_token: Option<String>,
+    translator: Option<Model<Translator>>,
+    next_id: AtomicU64,
+}
+
+type SharedState = Arc<AppState>;
+
+impl AppState {
+    fn generator(&self) -> Result<&Model<Generator>, ApiError> {
+        self.generator
+            .as_ref()
+            .ok_or_else(|| ApiError::not_found("no generator model is loaded"))
+    }
+
+    fn translator(&self) -> Result<&Model<Translator>, ApiError> {
+        self.translator
+            .as_ref()
+            .ok_or_else(|| ApiError::not_found("no translator model is loaded"))
+    }
+
+    fn new_id(&self, prefix: &str) -> String {
+        let n = self.next_id.fetch_add(1, Ordering::Relaxed);
+        format!("{prefix}-{:x}{n:06x}", now())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn model_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Truncates the text at the stop sequences, returning the reason the generation finished:
+/// `length` if it stopped because of `max_length`.
+fn finish(
+    text: &mut String,
+    ids: &[usize],
+    stop: &StopSequences,
+    max_length: usize,
+) -> &'static str {
+    if !stop.truncate(text) && ids.len() >= max_length {
+        "length"
+    } else {
+        "stop"
+    }
+}
+
+fn completion_tokens(results: &[GeneratedText]) -> usize {
+    results
+        .iter()
+        .flat_map(|r| r.ids.iter())
+        .map(|ids| ids.len())
+        .sum()
+}
+
+/// Turns the pieces of a streamed generation into server-sent events, given the function
+/// building the chunk of a piece (`None` for the last chunk).
+fn sse<F, T>(
+    pieces: mpsc::Receiver<ctrans2::Result<String>>,
+    first: Option<T>,
+    chunk: F,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>>
+where
+    F: Fn(Option<String>) -> T + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    let json = |value: &T| Event::default().json_data(value).unwrap_or_default();
+    let first = stream::iter(first.map(|c| json(&c)));
+    let last = json(&chunk(None));
+    let pieces = ReceiverStream::new(pieces)
+        .map(move |piece| match piece {
+            Ok(piece) => json(&chunk(Some(piece))),
+            Err(err) => Event::default()
+                .event("error")
+                .json_data(serde_json::json!({ "error": { "message": err.to_string() } }))
+                .unwrap_or_default(),
+        })
+        .chain(stream::iter([last, Event::default().data("[DONE]")]));
+    Sse::new(first.chain(pieces).map(Ok)).keep_alive(KeepAlive::default())
+}
+
+async fn completions(
+    State(state): State<SharedState>,
+    Json(req): Json<CompletionRequest>,
+) -> Result<Response, ApiError> {
+    let generator = state.generator()?;
+    let (options, stop) = req
+        .params
+        .generation_options(generator.model.tokenizer(), state.eos_token.as_deref())?;
+    let id = state.new_id("cmpl");
+    let created = now();
+    let model = generator.name.clone();
+
+    if req.params.stream {
+        let [prompt] = <[String; 1]>::try_from(req.prompt.to_vec())
+            .map_err(|_| ApiError::invalid_request("only one prompt can be streamed"))?;
+        let (pieces, receiver) = mpsc::channel(32);
+        let generator = generator.model.clone();
+        task::spawn_blocking(move || {
+            worker::stream(
+                &generator,
+                StreamInput::Prompt(prompt),
+                options,
+                stop,
+                pieces,
+            )
+        });
+        let chunk = move |text: Option<String>| CompletionResponse {
+            id: id.clone(),
+            object: "text_completion",
+            created,
+            model: model.clone(),
+            choices: vec![CompletionChoice {
+                index: 0,
+                finish_reason: if text.is_none() { Some("stop") } else { None },
+                text: text.unwrap_or_default(),
+                logprobs: None,
+            }],
+            usage: None,
+        };
+        return Ok(sse(receiver, None, chunk).into_response());
+    }
+
+    let completion = worker::complete(&generator.model, req.prompt.to_vec(), &options).await?;
+
+    let usage = Usage::new(
+        completion.prompt_tokens,
+        completion_tokens(&completion.results),
+    );
+    let choices = completion
+        .results
+        .into_iter()
+        .flat_map(|r| r.texts.into_iter().zip(r.ids))
+        .enumerate()
+        .map(|(index, (mut text, ids))| CompletionChoice {
+            index,
+            finish_reason: Some(finish(&mut text, &ids, &stop, options.max_length)),
+            text,
+            logprobs: None,
+        })
+        .collect();
+    Ok(Json(CompletionResponse {
+        id,
+        object: "text_completion",
+        created,
+        model,
+        choices,
+        usage: Some(usage),
+    })
+    .into_response())
+}
+
+async fn chat_completions(
+    State(state): State<SharedState>,
+    Json(req): Json<ChatCompletionRequest>,
+) -> Result<Response, ApiError> {
+    let generator = state.generator()?;
+    let (options, stop) = req
+        .params
+        .generation_options(generator.model.tokenizer(), state.eos_token.as_deref())?;
+    let id = state.new_id("chatcmpl");
+    let created = now();
+    let model = generator.name.clone();
+
+    if req.params.stream {
+        let (pieces, receiver) = mpsc::channel(32);
+        let generator = generator.model.clone();
+        task::spawn_blocking(move || {
+            worker::stream(
+                &generator,
+                StreamInput::Chat(req.messages),
+                options,
+                stop,
+                pieces,
+            )
+        });
+        let chunk = move |delta: Delta, finish_reason| ChatCompletionChunk {
+            id: id.clone(),
+            object: "chat.completion.chunk",
+            created,
+            model: model.clone(),
+            choices: vec![ChatChunkChoice {
+                index: 0,
+                delta,
+                finish_reason,
+            }],
+        };
+        let first = chunk(
+            Delta {
+                role: Some("assistant"),
+                content: None,
+            },
+            None,
+        );
+        let next = move |content: Option<String>| match content {
+            Some(content) => chunk(
+                Delta {
+                    role: None,
+                    content: Some(content),
+                },
+                None,
+            ),
+            None => chunk(Delta::default(), Some("stop")),
+        };
+        return Ok(sse(receiver, Some(first), next).into_response());
+    }
+
+    let completion = worker::chat(&generator.model, req.messages, options.clone()).await?;
+
+    let usage = Usage::new(
+        completion.prompt_tokens,
+        completion_tokens(&completion.results),
+    );
+    let choices = completion
+        .results
+        .into_iter()
+        .flat_map(|r| r.texts.into_iter().zip(r.ids))
+        .enumerate()
+        .map(|(index, (mut text, ids))| ChatChoice {
+            index,
+            finish_reason: finish(&mut text, &ids, &stop, options.max_length),
+            message: ChatMessage::assistant(text),
+        })
+        .collect();
+    Ok(Json(ChatCompletionResponse {
+        id,
+        object: "chat.completion",
+        created,
+        model,
+        choices,
+        usage,
+    })
+    .into_response())
+}
+
+async fn translate(
+    State(state): State<SharedState>,
+    Json(req): Json<TranslationRequest>,
+) -> Result<Json<TranslationResponse>, ApiError> {
+    let translator = state.translator()?;
+    let options = req.translation_options()?;
+
+    let sources = req.input.to_vec();
+    let target_prefixes = vec![req.target_prefix; sources.len()];
+    let results = translator
+        .model
+        .translate_batch_async(sources, target_prefixes, &options)
+        .await?;
+
+    let translations = results
+        .into_iter()
+        .enumerate()
+        .map(|(index, r)| Translation {
+            index,
+            text: r.text().to_string(),
+            hypotheses: r
+                .texts
+                .into_iter()
+                .enumerate()
+                .map(|(i, text)| Hypothesis {
+                    text,
+                    score: r.scores.get(i).copied(),
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(Json(TranslationResponse {
+        object: "translation",
+        model: translator.name.clone(),
+        translations,
+    }))
+}
+
+async fn models(State(state): State<SharedState>) -> Json<ModelList> {
+    let names = [
+        state.generator.as_ref().map(|m| &m.name),
+        state.translator.as_ref().map(|m| &m.name),
+    ];
+    Json(ModelList {
+        object: "list",
+        data: names
+            .into_iter()
+            .flatten()
+            .map(|name| ModelCard {
+                id: name.clone(),
+                object: "model",
+                owned_by: "ctrans2",
+            })
+            .collect(),
+    })
+}
+
+/// Serves the statistics of the models in the Prometheus text format.
+async fn metrics(State(state): State<SharedState>) -> impl IntoResponse {
+    let mut stats = Vec::new();
+    if let Some(generator) = &state.generator {
+        stats.push((generator.name.as_str(), generator.model.stats()));
+    }
+    if let Some(translator) = &state.translator {
+        stats.push((translator.name.as_str(), translator.model.stats()));
+    }
+    let labels = stats
+        .iter()
+        .map(|(name, _)| [("model", *name)])
+        .collect::<Vec<_>>();
+    let models = labels
+        .iter()
+        .zip(&stats)
+        .map(|(labels, (_, stats))| (&labels[..], stats))
+        .collect::<Vec<_>>();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::encode_prometheus(&models),
+    )
+}
+
+fn router(state: SharedState) -> Router {
+    Router::new()
+        .route("/v1/models", get(models))
+        .route("/v1/completions", post(completions))
+        .route("/v1/chat/completions", post(chat_completions))
+        .route("/v1/translate", post(translate))
+        .route("/metrics", get(metrics))
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    let mut eos_token = None;
+    let generator = match &args.generator {
+        Some(path) => {
+            let dir = ModelDir::open(path)?;
+            eos_token = dir.info().eos_token().map(|t| t.to_string());
+            Some(Model {
+                name: model_name(path),
+                model: Arc::new(Generator::new(dir.path(), args.device, args.config())?),
+            })
+        }
+        None => None,
+    };
+    let translator = match &args.translator {
+        Some(path) => Some(Model {
+            name: model_name(path),
+            model: Arc::new(Translator::new(path, args.device, args.config())?),
+        }),
+        None => None,
+    };
+
+    let app = router(Arc::new(AppState {
+        generator,
+        eos_token,
+        translator,
+        next_id: AtomicU64::new(0),
+    }));
+
+    let listener = tokio::net::TcpListener::bind((args.host.as_str(), args.port)).await?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{self, Body};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// Returns a router serving no model.
+    fn empty_router() -> Router {
+        router(Arc::new(AppState {
+            generator: None,
+            eos_token: None,
+            translator: None,
+            next_id: AtomicU64::new(0),
+        }))
+    }
+
+    async fn json_body(res: Response) -> serde_json::Value {
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn models_are_listed() {
+        let req = Request::get("/v1/models").body(Body::empty()).unwrap();
+        let res = empty_router().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            json_body(res).await,
+            serde_json::json!({ "object": "list", "data": [] })
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_model_is_not_found() {
+        for (uri, body) in [
+            ("/v1/completions", r#"{"prompt": "Hello"}"#),
+            (
+                "/v1/chat/completions",
+                r#"{"messages": [{"role": "user", "content": "Hello"}]}"#,
+            ),
+            ("/v1/translate", r#"{"input": "Hello"}"#),
+        ] {
+            let req = Request::post(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            let res = empty_router().oneshot(req).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{uri}");
+            let body = json_body(res).await;
+            assert_eq!(body["error"]["type"], "invalid_request_error", "{uri}");
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// worker.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Calls to the models. The batches are awaited without blocking the runtime, and the
+//! streamed tokens are read on its blocking threads.
+
+use tokio::sync::mpsc;
+
+use ctrans2::{ChatMessage, EndToken, Error, GeneratedText, GenerationOptions, Generator, Result};
+
+use crate::api::StopSequences;
+
+/// Generation results with the number of tokens of the prompts.
+pub struct Completion {
+    pub results: Vec<GeneratedText>,
+    pub prompt_tokens: usize,
+}
+
+/// Input of a streamed generation.
+pub enum StreamInput {
+    Prompt(String),
+    Chat(Vec<ChatMessage>),
+}
+
+fn count_tokens(generator: &Generator, text: &str) -> Result<usize> {
+    generator
+        .tokenizer()
+        .encode(text, false)
+        .map(|e| e.len())
+        .map_err(|err| Error::Encode(err.to_string()))
+}
+
+/// Generates the completions of each prompt.
+pub async fn complete(
+    generator: &Generator,
+    prompts: Vec<String>,
+    options: &GenerationOptions<String, String>,
+) -> Result<Completion> {
+    let prompt_tokens = prompts
+        .iter()
+        .map(|p| count_tokens(generator, p))
+        .sum::<Result<usize>>()?;
+    let results = generator.generate_batch_async(prompts, options).await?;
+    Ok(Completion {
+        results,
+        prompt_tokens,
+    })
+}
+
+fn render(generator: &Generator, messages: &[ChatMessage]) -> Result<String> {
+    match generator.chat_template() {
+        Some(template) => template.render(messages),
+        None => Err(Error::InvalidOptions(
+            "the model has no chat template".to_string(),
+        )),
+    }
+}
+
+/// Adds the EOS token of the chat template to the end tokens, since chat models end their
+/// replies with it.
+fn add_chat_eos(generator: &Generator, options: &mut GenerationOptions<String, String>) {
+    let Some(eos) = generator.chat_template().and_then(|t| t.eos_token.clone()) else {
+        return;
+    };
+    match &mut options.end_token {
+        None => options.end_token = Some(EndToken::Token(eos)),
+        Some(EndToken::Tokens(tokens)) if !tokens.contains(&eos) => tokens.push(eos),
+        _ => {}
+    }
+}
+
+/// Generates the replies to the conversation.
+pub async fn chat(
+    generator: &Generator,
+    messages: Vec<ChatMessage>,
+    mut options: GenerationOptions<String, String>,
+) -> Result<Completion> {
+    let prompt_tokens = count_tokens(generator, &render(generator, &messages)?)?;
+    add_chat_eos(generator, &mut options);
+    let results = generator.generate_chat_async(&[messages], &options).await?;
+    Ok(Completion {
+        results,
+        prompt_tokens,
+    })
+}
+
+/// Sends the pieces of the generated text as they are decoded, until a stop sequence. The
+/// generation stops when the receiver is dropped.
+pub fn stream(
+    generator: &Generator,
+    input: StreamInput,
+    mut options: GenerationOptions<String, String>,
+    stop: StopSequences,
+    pieces: mpsc::Sender<Result<String>>,
+) {
+    let prompt = match input {
+        StreamInput::Prompt(prompt) => Ok(prompt),
+        StreamInput::Chat(messages) => {
+            add_chat_eos(generator, &mut options);
+            render(generator, &messages)
+        }
+    };
+    let tokens = match prompt.and_then(|p| generator.generate_tokens(p.as_str(), &options)) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            pieces.blocking_send(Err(err)).ok();
+            return;
+        }
+    };
+    let mut stop = stop.matcher();
+    for piece in tokens {
+        let (piece, stopped) = match piece {
+            Ok(piece) => stop.push(&piece),
+            Err(err) => {
+                pieces.blocking_send(Err(err)).ok();
+                return;
+            }
+        };
+        if !piece.is_empty() && pieces.blocking_send(Ok(piece)).is_err() {
+            // The client disconnected; dropping the iterator stops the generation.
+            return;
+        }
+        if stopped {
+            return;
+        }
+    }
+    let rest = stop.finish();
+    if !rest.is_empty() {
+        pieces.blocking_send(Ok(rest)).ok();
+    }
+}
@@ -0,0 +1,495 @@
+// ctrans2.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Command-line tool running batch generation and translation.
+//!
+//! Inputs are read from a file or the standard input, one per line, either as plain text or
+//! as JSON objects (`{"prompt": "..."}`, or `{"source": "...", "target_prefix": ["..."]}`).
+//! Outputs are written as JSON lines with the text and score of each hypothesis:
+//!
+//! ```sh
+//! ctrans2 generate <model_dir> --input prompts.txt --max-length 64 > outputs.jsonl
+//! ctrans2 translate <model_dir> --input sources.jsonl --input-format jsonl --beam-size 4
+//! ```
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use ctrans2::{
+    BatchType, ComputeType, Config, Device, EndToken, GenerationOptions, Generator,
+    TranslationOptions, Translator,
+};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "ctrans2",
+    version,
+    about = "Runs CTranslate2 models on batches of inputs"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generates texts from prompts with a decoder-only model.
+    Generate(GenerateArgs),
+    /// Translates sources with a sequence-to-sequence model.
+    Translate(TranslateArgs),
+}
+
+#[derive(Args, Debug)]
+struct ModelArgs {
+    /// Path to the model directory.
+    model: PathBuf,
+    /// Device to use (cpu, cuda, or auto).
+    #[arg(long, default_value = "cpu")]
+    device: Device,
+    /// Computation type, such as int8 or float16.
+    #[arg(long, default_value = "default")]
+    compute_type: ComputeType,
+    /// Number of threads per replica (0 uses the default value).
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    /// Device IDs where to place the model, separated by commas.
+    #[arg(long, value_delimiter = ',', default_value = "0")]
+    device_indices: Vec<i32>,
+}
+
+impl ModelArgs {
+    fn config(&self) -> Config {
+        Config {
+            compute_type: self.compute_type,
+            device_indices: self.device_indices.clone(),
+            num_threads_per_replica: self.threads,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum InputFormat {
+    /// One input per line.
+    Text,
+    /// One JSON object per line.
+    Jsonl,
+}
+
+#[derive(Args, Debug)]
+struct IoArgs {
+    /// File to read the inputs from (defaults to the standard input).
+    #[arg(long, short)]
+    input: Option<PathBuf>,
+    /// Format of the inputs.
+    #[arg(long, value_enum, default_value = "text")]
+    input_format: InputFormat,
+    /// File to write the outputs to (defaults to the standard output).
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Number of inputs passed to the model at once.
+    #[arg(long, default_value_t = 32)]
+    batch_size: usize,
+}
+
+/// Flags of the options shared by generation and translation.
+///
+/// Options which are not given keep the default values of the library.
+#[derive(Args, Debug)]
+struct DecodingArgs {
+    /// Beam size to use for beam search (set 1 to run greedy search).
+    #[arg(long)]
+    beam_size: Option<usize>,
+    /// Beam search patience factor.
+    #[arg(long)]
+    patience: Option<f32>,
+    /// Exponential penalty applied to the length during beam search.
+    #[arg(long)]
+    length_penalty: Option<f32>,
+    /// Penalty applied to the score of previously generated tokens (set > 1 to penalize).
+    #[arg(long)]
+    repetition_penalty: Option<f32>,
+    /// Prevent repetitions of ngrams with this size (set 0 to disable).
+    #[arg(long)]
+    no_repeat_ngram_size: Option<usize>,
+    /// Disable the generation of the unknown token.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    disable_unk: Option<bool>,
+    /// Disable the generation of a sequence of tokens, separated by spaces (repeatable).
+    #[arg(long = "suppress-sequence")]
+    suppress_sequences: Vec<String>,
+    /// Stop the decoding on this token (repeatable; defaults to the model EOS token).
+    #[arg(long = "end-token")]
+    end_tokens: Vec<String>,
+    /// Include the end token in the result.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    return_end_token: Option<bool>,
+    /// Randomly sample from the top K candidates (set 0 to sample from the full distribution).
+    #[arg(long)]
+    sampling_topk: Option<usize>,
+    /// Keep the most probable tokens whose cumulative probability exceeds this value.
+    #[arg(long)]
+    sampling_topp: Option<f32>,
+    /// High temperature increase randomness.
+    #[arg(long)]
+    sampling_temperature: Option<f32>,
+    /// Number of hypotheses to include in the result.
+    #[arg(long)]
+    num_hypotheses: Option<usize>,
+    /// Include the score of each hypothesis in the outputs (pass `false` to omit it).
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    return_scores: bool,
+    /// Return alternatives at the first unconstrained decoding position.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    return_alternatives: Option<bool>,
+    /// Minimum probability to expand an alternative.
+    #[arg(long)]
+    min_alternative_expansion_prob: Option<f32>,
+    /// The maximum batch size used by CTranslate2 (set 0 to disable).
+    #[arg(long)]
+    max_batch_size: Option<usize>,
+    /// Whether `--max-batch-size` is the number of examples or tokens.
+    #[arg(long)]
+    batch_type: Option<BatchType>,
+}
+
+impl DecodingArgs {
+    fn suppress_sequences(&self) -> Vec<Vec<String>> {
+        self.suppress_sequences
+            .iter()
+            .map(|s| split_tokens(s))
+            .collect()
+    }
+
+    fn end_token(&self) -> Option<EndToken> {
+        match &self.end_tokens[..] {
+            [] => None,
+            [token] => Some(EndToken::Token(token.clone())),
+            tokens => Some(EndToken::Tokens(tokens.to_vec())),
+        }
+    }
+}
+
+/// Overwrites the given fields of the options with the flags which were given.
+macro_rules! set_options {
+    ($options:expr, $args:expr, $($field:ident),* $(,)?) => {
+        $(
+            if let Some(v) = $args.$field {
+                $options.$field = v;
+            }
+        )*
+    };
+}
+
+#[derive(Args, Debug)]
+struct GenerateArgs {
+    #[command(flatten)]
+    model: ModelArgs,
+    #[command(flatten)]
+    io: IoArgs,
+    #[command(flatten, next_help_heading = "Decoding options")]
+    decoding: DecodingArgs,
+    /// Maximum generation length.
+    #[arg(long, help_heading = "Generation options")]
+    max_length: Option<usize>,
+    /// Minimum generation length.
+    #[arg(long, help_heading = "Generation options")]
+    min_length: Option<usize>,
+    /// Compute the log probability of each generated token.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Generation options")]
+    return_log_prob: Option<bool>,
+    /// Include the log probabilities over the vocabulary at each step in the outputs.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Generation options")]
+    return_logits_vocab: Option<bool>,
+    /// Tokens of a static prompt prefixing all inputs, separated by spaces.
+    #[arg(long, help_heading = "Generation options")]
+    static_prompt: Option<String>,
+    /// Cache the model state after the static prompt.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Generation options")]
+    cache_static_prompt: Option<bool>,
+    /// Include the prompts in the outputs.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Generation options")]
+    include_prompt_in_result: Option<bool>,
+}
+
+impl GenerateArgs {
+    fn options(&self) -> GenerationOptions<String, String> {
+        let mut options = GenerationOptions {
+            suppress_sequences: self.decoding.suppress_sequences(),
+            end_token: self.decoding.end_token(),
+            static_prompt: self
+                .static_prompt
+                .as_deref()
+                .map(split_tokens)
+                .unwrap_or_default(),
+            return_scores: self.decoding.return_scores,
+            ..Default::default()
+        };
+        set_options!(
+            options,
+            self.decoding,
+            beam_size,
+            patience,
+            length_penalty,
+            repetition_penalty,
+            no_repeat_ngram_size,
+            disable_unk,
+            return_end_token,
+            sampling_topk,
+            sampling_topp,
+            sampling_temperature,
+            num_hypotheses,
+            return_alternatives,
+            min_alternative_expansion_prob,
+            max_batch_size,
+            batch_type,
+        );
+        set_options!(
+            options,
+            self,
+            max_length,
+            min_length,
+            return_log_prob,
+            return_logits_vocab,
+            cache_static_prompt,
+            include_prompt_in_result,
+        );
+        options
+    }
+}
+
+#[derive(Args, Debug)]
+struct TranslateArgs {
+    #[command(flatten)]
+    model: ModelArgs,
+    #[command(flatten)]
+    io: IoArgs,
+    #[command(flatten, next_help_heading = "Decoding options")]
+    decoding: DecodingArgs,
+    /// Tokens forcing the beginning of the translations, separated by spaces (used for the
+    /// inputs without a `target_prefix`).
+    #[arg(long, help_heading = "Translation options")]
+    target_prefix: Option<String>,
+    /// Coverage penalty weight applied during beam search.
+    #[arg(long, help_heading = "Translation options")]
+    coverage_penalty: Option<f32>,
+    /// Bias the decoding towards the target prefix with this weight, in (0, 1).
+    #[arg(long, help_heading = "Translation options")]
+    prefix_bias_beta: Option<f32>,
+    /// Truncate the inputs after this many tokens (set 0 to disable truncation).
+    #[arg(long, help_heading = "Translation options")]
+    max_input_length: Option<usize>,
+    /// Maximum decoding length.
+    #[arg(long, help_heading = "Translation options")]
+    max_decoding_length: Option<usize>,
+    /// Minimum decoding length.
+    #[arg(long, help_heading = "Translation options")]
+    min_decoding_length: Option<usize>,
+    /// Use the vocabulary map included in the model directory, if it exists.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Translation options")]
+    use_vmap: Option<bool>,
+    /// Include the attention vectors in the outputs.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Translation options")]
+    return_attention: Option<bool>,
+    /// Replace unknown target tokens by the source token with the highest attention.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true", help_heading = "Translation options")]
+    replace_unknowns: Option<bool>,
+}
+
+impl TranslateArgs {
+    fn options(&self) -> TranslationOptions<String> {
+        let mut options = TranslationOptions {
+            suppress_sequences: self.decoding.suppress_sequences(),
+            end_token: self.decoding.end_token(),
+            return_scores: self.decoding.return_scores,
+            ..Default::default()
+        };
+        set_options!(
+            options,
+            self.decoding,
+            beam_size,
+            patience,
+            length_penalty,
+            repetition_penalty,
+            no_repeat_ngram_size,
+            disable_unk,
+            return_end_token,
+            sampling_topk,
+            sampling_topp,
+            sampling_temperature,
+            num_hypotheses,
+            return_alternatives,
+            min_alternative_expansion_prob,
+            max_batch_size,
+            batch_type,
+        );
+        set_options!(
+            options,
+            self,
+            coverage_penalty,
+            prefix_bias_beta,
+            max_input_length,
+            max_decoding_length,
+            min_decoding_length,
+            use_vmap,
+            return_attention,
+            replace_unknowns,
+        );
+        options
+    }
+}
+
+/// An input read from a JSON line.
+#[derive(Deserialize, Debug)]
+struct Input {
+    #[serde(alias = "prompt", alias = "source")]
+    text: String,
+    #[serde(default)]
+    target_prefix: Option<Vec<String>>,
+}
+
+/// The outputs of an input, written as a JSON line.
+#[derive(Serialize, Debug)]
+struct Output<'a> {
+    /// Position of the input.
+    index: usize,
+    hypotheses: Vec<Hypothesis<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct Hypothesis<'a> {
+    text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f32>,
+    tokens: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attention: Option<&'a [Vec<f32>]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logits: Option<Vec<&'a [f32]>>,
+}
+
+fn split_tokens(s: &str) -> Vec<String> {
+    s.split_whitespace().map(|t| t.to_string()).collect()
+}
+
+fn read_inputs(args: &IoArgs) -> Result<Vec<Input>, Box<dyn Error>> {
+    let reader: Box<dyn BufRead> = match &args.input {
+        Some(path) if path.as_os_str() != "-" => Box::new(BufReader::new(File::open(path)?)),
+        _ => Box::new(io::stdin().lock()),
+    };
+
+    let mut inputs = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        match args.input_format {
+            InputFormat::Text => inputs.push(Input {
+                text: line,
+                target_prefix: None,
+            }),
+            InputFormat::Jsonl if line.trim().is_empty() => continue,
+            InputFormat::Jsonl => inputs
+                .push(serde_json::from_str(&line).map_err(|err| format!("line {}: {err}", i + 1))?),
+        }
+    }
+    Ok(inputs)
+}
+
+fn open_output(args: &IoArgs) -> Result<Box<dyn Write>, Box<dyn Error>> {
+    Ok(match &args.output {
+        Some(path) if path.as_os_str() != "-" => Box::new(BufWriter::new(File::create(path)?)),
+        _ => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn write_output<W: Write>(w: &mut W, output: &Output) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer(&mut *w, output)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+fn generate(args: GenerateArgs) -> Result<(), Box<dyn Error>> {
+    let inputs = read_inputs(&args.io)?;
+    let options = args.options();
+    let generator = Generator::new(&args.model.model, args.model.device, args.model.config())?;
+
+    let mut w = open_output(&args.io)?;
+    for (n, batch) in inputs.chunks(args.io.batch_size.max(1)).enumerate() {
+        let prompts = batch.iter().map(|i| i.text.as_str()).collect::<Vec<_>>();
+        let results = generator.generate_batch(prompts, &options)?;
+        for (i, res) in results.iter().enumerate() {
+            let hypotheses = (0..res.num_hypotheses())
+                .map(|h| Hypothesis {
+                    text: &res.texts[h],
+                    score: res.scores.get(h).copied(),
+                    tokens: &res.tokens[h],
+                    attention: None,
+                    logits: res
+                        .logits
+                        .get(h)
+                        .map(|l| (0..l.num_steps()).filter_map(|s| l.step(s)).collect()),
+                })
+                .collect();
+            let index = n * args.io.batch_size.max(1) + i;
+            write_output(&mut w, &Output { index, hypotheses })?;
+        }
+        w.flush()?;
+    }
+    Ok(())
+}
+
+fn translate(args: TranslateArgs) -> Result<(), Box<dyn Error>> {
+    let inputs = read_inputs(&args.io)?;
+    let options = args.options();
+    let default_prefix = args
+        .target_prefix
+        .as_deref()
+        .map(split_tokens)
+        .unwrap_or_default();
+    let translator = Translator::new(&args.model.model, args.model.device, args.model.config())?;
+
+    let mut w = open_output(&args.io)?;
+    for (n, batch) in inputs.chunks(args.io.batch_size.max(1)).enumerate() {
+        let sources = batch.iter().map(|i| i.text.as_str()).collect::<Vec<_>>();
+        let target_prefixes = batch
+            .iter()
+            .map(|i| {
+                i.target_prefix
+                    .clone()
+                    .unwrap_or_else(|| default_prefix.clone())
+            })
+            .collect::<Vec<_>>();
+        let results = translator.translate_batch(sources, target_prefixes, &options)?;
+        for (i, res) in results.iter().enumerate() {
+            let hypotheses = (0..res.num_hypotheses())
+                .map(|h| Hypothesis {
+                    text: &res.texts[h],
+                    score: res.scores.get(h).copied(),
+                    tokens: &res.tokens[h],
+                    attention: res.attention.get(h).map(|a| a.as_slice()),
+                    logits: None,
+                })
+                .collect();
+            let index = n * args.io.batch_size.max(1) + i;
+            write_output(&mut w, &Output { index, hypotheses })?;
+        }
+        w.flush()?;
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    match Cli::parse().command {
+        Command::Generate(args) => generate(args),
+        Command::Translate(args) => translate(args),
+    }
+}
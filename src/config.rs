@@ -8,15 +8,59 @@
 
 //! Configs and associated enums.
 
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::error::{self, Error};
+
 /// Device to use.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum Device {
     CPU,
     CUDA,
+    /// CUDA if a CUDA device is available, otherwise CPU.
+    Auto,
+}
+
+impl Device {
+    /// Resolves [`Device::Auto`] to the device the models are placed on.
+    pub fn resolve(self) -> Device {
+        match self {
+            Device::Auto if crate::device::get_cuda_device_count() > 0 => Device::CUDA,
+            Device::Auto => Device::CPU,
+            device => device,
+        }
+    }
+}
+
+impl FromStr for Device {
+    type Err = String;
+
+    /// Parses `cpu`, `cuda`, or `auto`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cpu" => Ok(Device::CPU),
+            "cuda" => Ok(Device::CUDA),
+            "auto" => Ok(Device::Auto),
+            _ => Err(format!("unknown device: {s}")),
+        }
+    }
 }
 
-/// Model computation type or a dictionary mapping a device name to the computation type.
-#[derive(Debug, Default)]
+/// Model computation type.
+///
+/// See [`Config::device_compute_types`] to use a different computation type on each device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum ComputeType {
     #[default]
     Default,
@@ -26,14 +70,54 @@ pub enum ComputeType {
     Int8Float16,
     Int16,
     Float16,
+    #[cfg_attr(feature = "serde", serde(rename = "bfloat16"))]
+    BFloat16,
+    #[cfg_attr(feature = "serde", serde(rename = "int8_bfloat16"))]
+    Int8BFloat16,
+    Int8Float32,
+}
+
+impl FromStr for ComputeType {
+    type Err = String;
+
+    /// Parses the compute type names used by CTranslate2, such as `int8_float16`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "default" => Ok(ComputeType::Default),
+            "auto" => Ok(ComputeType::Auto),
+            "float32" => Ok(ComputeType::Float32),
+            "int8" => Ok(ComputeType::Int8),
+            "int8_float16" => Ok(ComputeType::Int8Float16),
+            "int16" => Ok(ComputeType::Int16),
+            "float16" => Ok(ComputeType::Float16),
+            "bfloat16" => Ok(ComputeType::BFloat16),
+            "int8_bfloat16" => Ok(ComputeType::Int8BFloat16),
+            "int8_float32" => Ok(ComputeType::Int8Float32),
+            _ => Err(format!("unknown compute type: {s}")),
+        }
+    }
 }
 
 /// Config of Translator.
-#[derive(Debug)]
+///
+/// [`Config::builder`] checks the values when the config is built.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct Config {
-    /// Model computation type or a dictionary mapping a device name to the computation type.
+    /// Model computation type, used on the devices not in `device_compute_types`.
     pub compute_type: ComputeType,
+    /// Model computation type for each device, e.g. int8 on CPU and int8_float16 on CUDA, so
+    /// that the same config suits [`Device::Auto`].
+    pub device_compute_types: HashMap<Device, ComputeType>,
     /// Device IDs where to place this generator on.
+    ///
+    /// The indices must be less than [`get_cuda_device_count`](crate::device::get_cuda_device_count)
+    /// on CUDA, and an explicit compute type must be supported by each device. Use
+    /// [`ComputeType::Auto`] to let CTranslate2 choose the fastest type the devices support.
     pub device_indices: Vec<i32>,
     pub num_threads_per_replica: usize,
     pub max_queued_batches: i64,
@@ -44,6 +128,7 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             compute_type: Default::default(),
+            device_compute_types: HashMap::new(),
             device_indices: vec![0],
             num_threads_per_replica: 0,
             max_queued_batches: 0,
@@ -52,10 +137,297 @@ impl Default for Config {
     }
 }
 
+impl Config {
+    /// Returns a builder of a config, starting from the default values.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Checks the values which are invalid on any device.
+    ///
+    /// The device indices and the computation types are checked against the device when the
+    /// model is constructed.
+    pub fn validate(&self) -> error::Result<()> {
+        if self.device_indices.is_empty() {
+            return Err(Error::InvalidOptions(
+                "device_indices must not be empty".to_string(),
+            ));
+        }
+        if let Some(index) = self.device_indices.iter().find(|i| **i < 0) {
+            return Err(Error::InvalidOptions(format!(
+                "device_indices must not be negative, got {index}"
+            )));
+        }
+        if self.max_queued_batches < -1 {
+            return Err(Error::InvalidOptions(format!(
+                "max_queued_batches must be -1 or more, got {}",
+                self.max_queued_batches
+            )));
+        }
+        if self.cpu_core_offset < -1 {
+            return Err(Error::InvalidOptions(format!(
+                "cpu_core_offset must be -1 or more, got {}",
+                self.cpu_core_offset
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the computation type used on the given device.
+    pub fn compute_type_for(&self, device: Device) -> ComputeType {
+        let device = device.resolve();
+        self.device_compute_types
+            .get(&device)
+            .copied()
+            .unwrap_or(self.compute_type)
+    }
+}
+
+/// Defines the setters of a builder, each overwriting a field of the value being built.
+macro_rules! builder_setters {
+    ($value:ident: $ty:ident { $($field:ident: $field_ty:ty),* $(,)? }) => {
+        $(
+            #[doc = concat!("Sets [`", stringify!($ty), "::", stringify!($field), "`].")]
+            pub fn $field(mut self, $field: $field_ty) -> Self {
+                self.$value.$field = $field;
+                self
+            }
+        )*
+    };
+}
+pub(crate) use builder_setters;
+
+/// Builds a [`Config`], checking its values.
+///
+/// ```
+/// use ctrans2::{ComputeType, Config, Device};
+///
+/// let config = Config::builder()
+///     .compute_type(ComputeType::Int8)
+///     .device_compute_type(Device::CUDA, ComputeType::Int8Float16)
+///     .num_threads_per_replica(4)
+///     .build()
+///     .unwrap();
+/// assert_eq!(config.compute_type_for(Device::CUDA), ComputeType::Int8Float16);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    builder_setters!(config: Config {
+        compute_type: ComputeType,
+        device_indices: Vec<i32>,
+        num_threads_per_replica: usize,
+        max_queued_batches: i64,
+        cpu_core_offset: i32,
+    });
+
+    /// Sets the computation type used on the given device.
+    pub fn device_compute_type(mut self, device: Device, compute_type: ComputeType) -> Self {
+        self.config
+            .device_compute_types
+            .insert(device, compute_type);
+        self
+    }
+
+    /// Returns the config, or [`Error::InvalidOptions`] if a value is invalid.
+    pub fn build(self) -> error::Result<Config> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+impl From<Config> for ConfigBuilder {
+    fn from(config: Config) -> Self {
+        Self { config }
+    }
+}
+
+/// Checks the decoding options shared by the generation and translation options.
+pub(crate) fn validate_decoding(
+    beam_size: usize,
+    num_hypotheses: usize,
+    sampling_topp: f32,
+    min_length: usize,
+    max_length: usize,
+) -> error::Result<()> {
+    if beam_size == 0 {
+        return Err(Error::InvalidOptions(
+            "beam_size must be at least 1".to_string(),
+        ));
+    }
+    if num_hypotheses == 0 {
+        return Err(Error::InvalidOptions(
+            "num_hypotheses must be at least 1".to_string(),
+        ));
+    }
+    if !(sampling_topp > 0. && sampling_topp <= 1.) {
+        return Err(Error::InvalidOptions(format!(
+            "sampling_topp must be in (0, 1], got {sampling_topp}"
+        )));
+    }
+    if min_length > max_length {
+        return Err(Error::InvalidOptions(format!(
+            "the minimum length {min_length} exceeds the maximum length {max_length}"
+        )));
+    }
+    Ok(())
+}
+
 /// Whether max_batch_size is the number of “examples” or “tokens”.
-#[derive(Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum BatchType {
     #[default]
     Examples,
     Tokens,
-}
\ No newline at end of file
+}
+
+impl FromStr for BatchType {
+    type Err = String;
+
+    /// Parses `examples` or `tokens`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "examples" => Ok(BatchType::Examples),
+            "tokens" => Ok(BatchType::Tokens),
+            _ => Err(format!("unknown batch type: {s}")),
+        }
+    }
+}
+
+/// Tokens to stop the decoding on.
+///
+/// With the `serde` feature, it is written as a token, a list of tokens, or a list of IDs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(untagged)
+)]
+pub enum EndToken {
+    /// A single token.
+    Token(String),
+    /// Any of the given tokens.
+    Tokens(Vec<String>),
+    /// Any of the given token IDs.
+    Ids(Vec<usize>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_rejects_invalid_configs() {
+        assert!(Config::builder().build().is_ok());
+        for builder in [
+            Config::builder().device_indices(vec![]),
+            Config::builder().device_indices(vec![0, -1]),
+            Config::builder().max_queued_batches(-2),
+            Config::builder().cpu_core_offset(-2),
+        ] {
+            assert!(matches!(builder.build(), Err(Error::InvalidOptions(_))));
+        }
+    }
+
+    #[test]
+    fn decoding_options_are_checked() {
+        assert!(validate_decoding(1, 1, 1., 0, 256).is_ok());
+        assert!(validate_decoding(4, 2, 0.5, 256, 256).is_ok());
+        for (beam_size, num_hypotheses, sampling_topp, min_length, max_length) in [
+            (0, 1, 1., 0, 256),
+            (1, 0, 1., 0, 256),
+            (1, 1, 0., 0, 256),
+            (1, 1, 1.5, 0, 256),
+            (1, 1, f32::NAN, 0, 256),
+            (1, 1, 1., 257, 256),
+        ] {
+            assert!(matches!(
+                validate_decoding(
+                    beam_size,
+                    num_hypotheses,
+                    sampling_topp,
+                    min_length,
+                    max_length
+                ),
+                Err(Error::InvalidOptions(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn end_token_is_untagged() {
+        for (end_token, json) in [
+            (EndToken::Token("</s>".to_string()), r#""</s>""#),
+            (
+                EndToken::Tokens(vec!["</s>".to_string(), "<eot>".to_string()]),
+                r#"["</s>","<eot>"]"#,
+            ),
+            (EndToken::Ids(vec![2, 32000]), "[2,32000]"),
+        ] {
+            assert_eq!(serde_json::to_string(&end_token).unwrap(), json);
+            assert_eq!(serde_json::from_str::<EndToken>(json).unwrap(), end_token);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn compute_types_use_the_ctranslate2_names() {
+        for name in [
+            "default",
+            "auto",
+            "float32",
+            "int8",
+            "int8_float16",
+            "int16",
+            "float16",
+            "bfloat16",
+            "int8_bfloat16",
+            "int8_float32",
+        ] {
+            let compute_type = name.parse::<ComputeType>().unwrap();
+            let json = format!("\"{name}\"");
+            assert_eq!(serde_json::to_string(&compute_type).unwrap(), json);
+            assert_eq!(
+                serde_json::from_str::<ComputeType>(&json).unwrap(),
+                compute_type
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_round_trips() {
+        let config = Config::builder()
+            .compute_type(ComputeType::Int8)
+            .device_compute_type(Device::CUDA, ComputeType::Int8Float16)
+            .device_indices(vec![0, 1])
+            .build()
+            .unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        let decoded = serde_json::from_str::<Config>(&json).unwrap();
+        assert_eq!(decoded.compute_type, ComputeType::Int8);
+        assert_eq!(decoded.device_compute_types, config.device_compute_types);
+        assert_eq!(decoded.device_indices, vec![0, 1]);
+        assert_eq!(decoded.cpu_core_offset, -1);
+
+        // The missing fields take their default values.
+        let partial =
+            serde_json::from_str::<Config>(r#"{"device_compute_types": {"cuda": "int8_float16"}}"#)
+                .unwrap();
+        assert_eq!(partial.compute_type, ComputeType::Default);
+        assert_eq!(partial.device_indices, vec![0]);
+        assert_eq!(
+            partial.device_compute_types,
+            HashMap::from([(Device::CUDA, ComputeType::Int8Float16)])
+        );
+    }
+}
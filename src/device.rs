@@ -0,0 +1,147 @@
+// device.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Queries of the devices available at runtime.
+//!
+//! ```no_run
+//! use ctrans2::{ComputeType, Device};
+//! use ctrans2::device;
+//!
+//! println!("{} CUDA devices", device::get_cuda_device_count());
+//! let types = device::get_supported_compute_types(Device::Auto, 0).unwrap();
+//! assert!(types.contains(&ComputeType::Float32));
+//! ```
+
+use crate::config::{ComputeType, Config, Device};
+use crate::error::{Error, Result};
+
+#[cxx::bridge]
+mod ffi {
+    unsafe extern "C++" {
+        include!("../include/device.h");
+
+        fn get_cuda_device_count() -> i32;
+
+        fn get_supported_compute_types(cuda: bool, device_index: i32) -> Result<Vec<String>>;
+    }
+}
+
+/// Returns the number of CUDA devices, which is 0 if CTranslate2 is built without CUDA
+/// support or no device is visible.
+pub fn get_cuda_device_count() -> usize {
+    ffi::get_cuda_device_count().max(0) as usize
+}
+
+/// Returns the compute types the given device can run efficiently.
+///
+/// [`Device::Auto`] is resolved first, so on a machine without a GPU the compute types of
+/// the CPU are returned. [`ComputeType::Default`] and [`ComputeType::Auto`] are not listed
+/// since they are accepted by any device.
+pub fn get_supported_compute_types(device: Device, device_index: i32) -> Result<Vec<ComputeType>> {
+    let device = device.resolve();
+    check_device_index(device, device_index)?;
+    let names = ffi::get_supported_compute_types(device == Device::CUDA, device_index)
+        .map_err(Error::from)?;
+    Ok(names
+        .iter()
+        .filter_map(|name| name.parse::<ComputeType>().ok())
+        .collect())
+}
+
+/// Checks that the devices of the config can run the model, returning the device the model
+/// is placed on, i.e. [`Device::Auto`] resolved, and the compute type it runs with.
+///
+/// An explicit compute type must be supported by every device. [`ComputeType::Default`] and
+/// [`ComputeType::Auto`] are accepted by any device, since CTranslate2 chooses a supported
+/// type for them.
+pub(crate) fn check_config(device: Device, config: &Config) -> Result<(Device, ComputeType)> {
+    config.validate()?;
+    let device = device.resolve();
+    let compute_type = config.compute_type_for(device);
+    for &index in &config.device_indices {
+        check_device_index(device, index)?;
+        // The default and automatic compute types are chosen by CTranslate2 for the device.
+        if matches!(compute_type, ComputeType::Default | ComputeType::Auto) {
+            continue;
+        }
+        let supported = get_supported_compute_types(device, index)?;
+        check_compute_type(device, index, compute_type, &supported)?;
+    }
+    Ok((device, compute_type))
+}
+
+/// Returns an error if the compute type is not in the types `supported` by the device.
+fn check_compute_type(
+    device: Device,
+    index: i32,
+    compute_type: ComputeType,
+    supported: &[ComputeType],
+) -> Result<()> {
+    if !supported.contains(&compute_type) {
+        return Err(Error::DeviceUnavailable(format!(
+            "{compute_type:?} is not supported by {device:?} {index}, which supports \
+             {supported:?}; use ComputeType::Auto to let CTranslate2 choose a supported type"
+        )));
+    }
+    Ok(())
+}
+
+fn check_device_index(device: Device, index: i32) -> Result<()> {
+    let count = match device {
+        Device::CUDA => get_cuda_device_count(),
+        // Every index designates the same CPU.
+        _ => return Ok(()),
+    };
+    if index < 0 || index as usize >= count {
+        return Err(Error::DeviceUnavailable(format!(
+            "CUDA device {index} is not available; {count} devices found"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ComputeType::*;
+
+    const CPU: [ComputeType; 4] = [Float32, Int16, Int8, Int8Float32];
+    const GPU: [ComputeType; 7] = [
+        Float32,
+        Float16,
+        BFloat16,
+        Int8,
+        Int8Float32,
+        Int8Float16,
+        Int8BFloat16,
+    ];
+
+    #[test]
+    fn supported_compute_type_is_accepted() {
+        for compute_type in CPU {
+            assert!(check_compute_type(Device::CPU, 0, compute_type, &CPU).is_ok());
+        }
+        for compute_type in GPU {
+            assert!(check_compute_type(Device::CUDA, 0, compute_type, &GPU).is_ok());
+        }
+    }
+
+    #[test]
+    fn unsupported_compute_type_is_rejected() {
+        for compute_type in [Int8Float16, Int8BFloat16, Float16, BFloat16] {
+            assert!(matches!(
+                check_compute_type(Device::CPU, 0, compute_type, &CPU),
+                Err(Error::DeviceUnavailable(_))
+            ));
+        }
+        assert!(matches!(
+            check_compute_type(Device::CUDA, 1, Int16, &GPU),
+            Err(Error::DeviceUnavailable(msg)) if msg.contains("CUDA 1")
+        ));
+    }
+}
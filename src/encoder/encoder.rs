@@ -0,0 +1,184 @@
+use cxx::UniquePtr;
+
+use crate::config::{ComputeType, Config, Device};
+use crate::device::check_config;
+use crate::error::{Error, Result};
+
+#[cxx::bridge]
+mod ffi {
+    struct EncVecStr<'a> {
+        v: Vec<&'a str>,
+    }
+
+    struct EncVecUSize {
+        v: Vec<usize>,
+    }
+
+    enum EncComputeType {
+        Default,
+        Auto,
+        Float32,
+        Int8,
+        Int8Float16,
+        Int16,
+        Float16,
+        BFloat16,
+        Int8BFloat16,
+        Int8Float32,
+    }
+
+    struct EncoderConfig {
+        compute_type: EncComputeType,
+        device_indices: Vec<i32>,
+        num_threads_per_replica: usize,
+        max_queued_batches: i64,
+        cpu_core_offset: i32,
+    }
+
+    struct EncoderForwardOutput {
+        last_hidden_state: Vec<f32>,
+        last_hidden_state_shape: Vec<usize>,
+        pooler_output: Vec<f32>,
+        pooler_output_shape: Vec<usize>,
+        has_pooler_output: bool,
+    }
+
+    unsafe extern "C++" {
+        include!("../../include/encoder.h");
+
+        type Encoder;
+
+        fn new_encoder(
+            model_path: &str,
+            cuda: bool,
+            config: EncoderConfig,
+        ) -> Result<UniquePtr<Encoder>>;
+
+        fn forward_batch(
+            &self,
+            tokens: Vec<EncVecStr>,
+            token_type_ids: Vec<EncVecUSize>,
+        ) -> Result<EncoderForwardOutput>;
+
+        fn forward_batch_ids(
+            &self,
+            ids: Vec<EncVecUSize>,
+            token_type_ids: Vec<EncVecUSize>,
+        ) -> Result<EncoderForwardOutput>;
+    }
+}
+
+/// An encoder model, such as BERT.
+pub struct Encoder {
+    ptr: UniquePtr<ffi::Encoder>,
+}
+
+impl Encoder {
+    pub fn new<T: AsRef<str>>(
+        model_path: T,
+        device: Device,
+        config: Config,
+    ) -> Result<Encoder> {
+        let (device, compute_type) = check_config(device, &config)?;
+        Ok(Encoder {
+            ptr: ffi::new_encoder(
+                model_path.as_ref(),
+                device == Device::CUDA,
+                ffi::EncoderConfig {
+                    compute_type: match compute_type {
+                        ComputeType::Default => ffi::EncComputeType::Default,
+                        ComputeType::Auto => ffi::EncComputeType::Auto,
+                        ComputeType::Float32 => ffi::EncComputeType::Float32,
+                        ComputeType::Int8 => ffi::EncComputeType::Int8,
+                        ComputeType::Int8Float16 => ffi::EncComputeType::Int8Float16,
+                        ComputeType::Int16 => ffi::EncComputeType::Int16,
+                        ComputeType::Float16 => ffi::EncComputeType::Float16,
+                        ComputeType::BFloat16 => ffi::EncComputeType::BFloat16,
+                        ComputeType::Int8BFloat16 => ffi::EncComputeType::Int8BFloat16,
+                        ComputeType::Int8Float32 => ffi::EncComputeType::Int8Float32,
+                    },
+                    device_indices: config.device_indices,
+                    num_threads_per_replica: config.num_threads_per_replica,
+                    max_queued_batches: config.max_queued_batches,
+                    cpu_core_offset: config.cpu_core_offset,
+                },
+            )
+            .map_err(Error::model_load)?,
+        })
+    }
+
+    /// Computes the forward pass of a batch of tokens.
+    ///
+    /// `token_type_ids` can be empty if the model does not use them.
+    pub fn forward_batch<T: AsRef<str>>(
+        &self,
+        tokens: &[Vec<T>],
+        token_type_ids: &[Vec<usize>],
+    ) -> Result<EncoderForwardOutput> {
+        Ok(self
+            .ptr
+            .forward_batch(
+                tokens
+                    .iter()
+                    .map(|v| ffi::EncVecStr {
+                        v: v.iter().map(|s| s.as_ref()).collect(),
+                    })
+                    .collect(),
+                vec_ffi_vecusize(token_type_ids),
+            )?
+            .into())
+    }
+
+    /// Computes the forward pass of a batch of token IDs.
+    ///
+    /// `token_type_ids` can be empty if the model does not use them.
+    pub fn forward_batch_ids(
+        &self,
+        ids: &[Vec<usize>],
+        token_type_ids: &[Vec<usize>],
+    ) -> Result<EncoderForwardOutput> {
+        Ok(self
+            .ptr
+            .forward_batch_ids(vec_ffi_vecusize(ids), vec_ffi_vecusize(token_type_ids))?
+            .into())
+    }
+}
+
+/// The outputs of an encoder, copied to the host memory as 32-bit floats.
+///
+/// The tensors are flattened in row-major order.
+#[derive(Debug)]
+pub struct EncoderForwardOutput {
+    /// Output of the last layer, of the shape `[batch_size, max_length, hidden_size]`.
+    pub last_hidden_state: Vec<f32>,
+    /// Shape of `last_hidden_state`.
+    pub last_hidden_state_shape: Vec<usize>,
+    /// Output of the pooling layer, of the shape `[batch_size, hidden_size]` (`None` if the model
+    /// has no pooling layer).
+    pub pooler_output: Option<Vec<f32>>,
+    /// Shape of `pooler_output`.
+    pub pooler_output_shape: Option<Vec<usize>>,
+}
+
+impl From<ffi::EncoderForwardOutput> for EncoderForwardOutput {
+    fn from(res: ffi::EncoderForwardOutput) -> Self {
+        let (pooler_output, pooler_output_shape) = if res.has_pooler_output {
+            (Some(res.pooler_output), Some(res.pooler_output_shape))
+        } else {
+            (None, None)
+        };
+        Self {
+            last_hidden_state: res.last_hidden_state,
+            last_hidden_state_shape: res.last_hidden_state_shape,
+            pooler_output,
+            pooler_output_shape,
+        }
+    }
+}
+
+#[inline]
+fn vec_ffi_vecusize(src: &[Vec<usize>]) -> Vec<ffi::EncVecUSize> {
+    src.iter()
+        .map(|v| ffi::EncVecUSize { v: v.clone() })
+        .collect()
+}
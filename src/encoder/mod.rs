@@ -0,0 +1,254 @@
+use std::path::Path;
+
+use tokenizers::{EncodeInput, Encoding, Tokenizer};
+
+use crate::config::{Config, Device};
+use crate::error::{Error, Result};
+use crate::model::{Architecture, ModelDir};
+pub use self::encoder::EncoderForwardOutput;
+
+/// The bridge to the CTranslate2 encoder, taking tokens or token IDs without a tokenizer.
+pub mod encoder;
+
+const TOKENIZER_FILENAME: &str = "tokenizer.json";
+
+/// How to pool the hidden states of the tokens into a single embedding.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Pooling {
+    /// Average of the hidden states of the tokens, ignoring the padding.
+    #[default]
+    Mean,
+    /// Hidden state of the first token, e.g. `[CLS]`.
+    Cls,
+}
+
+/// An encoder model with a tokenizer.
+pub struct Encoder {
+    encoder: self::encoder::Encoder,
+    tokenizer: Tokenizer,
+}
+
+impl Encoder {
+    /// Initializes the encoder and tokenizer.
+    pub fn new<T: AsRef<Path>>(path: T, device: Device, config: Config) -> Result<Encoder> {
+        let dir = ModelDir::open_as(path, Architecture::Encoder)?;
+        Encoder::with_tokenizer(
+            dir.path(),
+            device,
+            config,
+            Tokenizer::from_file(dir.join(TOKENIZER_FILENAME))
+                .map_err(|err| Error::Tokenizer(err.to_string()))?,
+        )
+    }
+
+    /// Initializes the encoder with the given tokenizer.
+    pub fn with_tokenizer<T: AsRef<Path>>(
+        path: T,
+        device: Device,
+        config: Config,
+        tokenizer: Tokenizer,
+    ) -> Result<Encoder> {
+        let dir = ModelDir::open_as(path, Architecture::Encoder)?;
+        Ok(Encoder {
+            encoder: self::encoder::Encoder::new(dir.path_str(), device, config)?,
+            tokenizer,
+        })
+    }
+
+    /// Computes the forward pass of the given texts.
+    pub fn forward_batch<'a, T>(&self, texts: Vec<T>) -> Result<EncoderForwardOutput>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        let encodings = self.encode(texts)?;
+        self.forward(&encodings)
+    }
+
+    /// Computes an embedding of each of the given texts.
+    ///
+    /// The embeddings are L2-normalized if `normalize` is true.
+    pub fn embed<'a, T>(
+        &self,
+        texts: Vec<T>,
+        pooling: Pooling,
+        normalize: bool,
+    ) -> Result<Vec<Vec<f32>>>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        let encodings = self.encode(texts)?;
+        let (ids, token_type_ids) = unpad(&encodings);
+        let lengths = ids.iter().map(Vec::len).collect::<Vec<_>>();
+        let output = self.encoder.forward_batch_ids(&ids, &token_type_ids)?;
+        pool(&output, &lengths, pooling, normalize)
+    }
+
+    fn encode<'a, T>(&self, texts: Vec<T>) -> Result<Vec<Encoding>>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        texts
+            .into_iter()
+            .map(|s| {
+                self.tokenizer
+                    .encode(s, true)
+                    .map_err(|err| Error::Encode(err.to_string()))
+            })
+            .collect()
+    }
+
+    fn forward(&self, encodings: &[Encoding]) -> Result<EncoderForwardOutput> {
+        let (ids, token_type_ids) = unpad(encodings);
+        self.encoder.forward_batch_ids(&ids, &token_type_ids)
+    }
+}
+
+/// Returns the IDs and type IDs of the tokens of each encoding, without the padding added by
+/// the tokenizer on either side. CTranslate2 pads the batch on the right, so the tokens of
+/// each example start at the first position of the hidden states.
+fn unpad(encodings: &[Encoding]) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    encodings
+        .iter()
+        .map(|e| {
+            e.get_ids()
+                .iter()
+                .zip(e.get_type_ids())
+                .zip(e.get_attention_mask())
+                .filter(|(_, mask)| **mask != 0)
+                .map(|((id, type_id), _)| (*id as usize, *type_id as usize))
+                .unzip()
+        })
+        .unzip()
+}
+
+/// Pools the last hidden state into one embedding per example, given the number of tokens of
+/// each example, which are followed by the padding.
+///
+/// The embeddings are L2-normalized if `normalize` is true.
+fn pool(
+    output: &EncoderForwardOutput,
+    lengths: &[usize],
+    pooling: Pooling,
+    normalize: bool,
+) -> Result<Vec<Vec<f32>>> {
+    let shape = &output.last_hidden_state_shape;
+    let [batch_size, max_length, hidden_size] = shape[..] else {
+        return Err(Error::Ffi(format!(
+            "unexpected shape of the last hidden state: {shape:?}"
+        )));
+    };
+    if batch_size != lengths.len()
+        || output.last_hidden_state.len() != batch_size * max_length * hidden_size
+    {
+        return Err(Error::Ffi(format!(
+            "the last hidden state of the shape {shape:?} does not match {} examples",
+            lengths.len()
+        )));
+    }
+
+    let mut res = Vec::with_capacity(batch_size);
+    for (states, &len) in output
+        .last_hidden_state
+        .chunks(max_length * hidden_size)
+        .zip(lengths)
+    {
+        let mut embedding = match pooling {
+            Pooling::Cls => states[..hidden_size].to_vec(),
+            Pooling::Mean => {
+                let len = len.clamp(1, max_length);
+                let mut sum = vec![0f32; hidden_size];
+                for state in states.chunks(hidden_size).take(len) {
+                    for (s, v) in sum.iter_mut().zip(state) {
+                        *s += v;
+                    }
+                }
+                sum.iter().map(|s| s / len as f32).collect()
+            }
+        };
+        if normalize {
+            let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if norm > 0. {
+                embedding.iter_mut().for_each(|v| *v /= norm);
+            }
+        }
+        res.push(embedding);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::PaddingDirection;
+
+    use super::*;
+
+    /// Hidden states of 2 examples of at most 3 tokens with 2 dimensions, the second example
+    /// having 2 tokens followed by the padding.
+    fn output() -> EncoderForwardOutput {
+        EncoderForwardOutput {
+            last_hidden_state: vec![
+                1., 2., 3., 4., 5., 6., //
+                3., 4., 1., 0., 100., 100.,
+            ],
+            last_hidden_state_shape: vec![2, 3, 2],
+            pooler_output: None,
+            pooler_output_shape: None,
+        }
+    }
+
+    #[test]
+    fn cls_pooling_takes_the_first_token() {
+        let res = pool(&output(), &[3, 2], Pooling::Cls, false).unwrap();
+        assert_eq!(res, vec![vec![1., 2.], vec![3., 4.]]);
+    }
+
+    #[test]
+    fn mean_pooling_ignores_the_padding() {
+        let res = pool(&output(), &[3, 2], Pooling::Mean, false).unwrap();
+        assert_eq!(res, vec![vec![3., 4.], vec![2., 2.]]);
+    }
+
+    #[test]
+    fn embeddings_are_l2_normalized() {
+        let res = pool(&output(), &[3, 2], Pooling::Cls, true).unwrap();
+        assert_eq!(res[1], vec![0.6, 0.8]);
+        for embedding in res {
+            let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+            assert!((norm - 1.).abs() < 1e-6);
+        }
+
+        // A zero embedding is kept as is.
+        let mut zero = output();
+        zero.last_hidden_state.iter_mut().for_each(|v| *v = 0.);
+        let res = pool(&zero, &[3, 2], Pooling::Mean, true).unwrap();
+        assert_eq!(res[0], vec![0., 0.]);
+    }
+
+    #[test]
+    fn mismatched_shape_is_rejected() {
+        assert!(pool(&output(), &[3], Pooling::Mean, false).is_err());
+        let mut output = output();
+        output.last_hidden_state_shape = vec![2, 6];
+        assert!(pool(&output, &[3, 2], Pooling::Mean, false).is_err());
+    }
+
+    #[test]
+    fn padding_of_the_tokenizer_is_removed() {
+        let vocab = [("[PAD]", 0), ("hello", 1), ("world", 2)]
+            .into_iter()
+            .map(|(token, id)| (token.to_string(), id))
+            .collect();
+        let model = WordLevel::builder().vocab(vocab).build().unwrap();
+        let tokenizer = Tokenizer::new(model);
+        let mut left = tokenizer.encode("hello", false).unwrap();
+        left.pad(3, 0, 0, "[PAD]", PaddingDirection::Left);
+        let mut right = tokenizer.encode("world", false).unwrap();
+        right.pad(3, 0, 0, "[PAD]", PaddingDirection::Right);
+        assert_eq!(left.get_ids(), &[0, 0, 1]);
+
+        let (ids, token_type_ids) = unpad(&[left, right]);
+        assert_eq!(ids, vec![vec![1], vec![2]]);
+        assert_eq!(token_type_ids, vec![vec![0], vec![0]]);
+    }
+}
@@ -0,0 +1,177 @@
+// error.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Errors returned by this crate.
+
+use thiserror::Error;
+
+use crate::model::ModelDirError;
+
+/// Prefix added to the messages of `std::invalid_argument` and `std::out_of_range` by
+/// `include/exception.h`.
+const INVALID_ARGUMENT_PREFIX: &str = "invalid_argument: ";
+/// Prefix added to the messages of `std::bad_alloc` by `include/exception.h`.
+const BAD_ALLOC_PREFIX: &str = "bad_alloc: ";
+/// Prefix added to the messages of `ModelUnloadedError` by `include/exception.h`.
+const MODEL_UNLOADED_PREFIX: &str = "model_unloaded: ";
+
+/// Messages of CTranslate2 exceptions thrown when the requested device cannot be used.
+const DEVICE_UNAVAILABLE_MESSAGES: [&str; 5] = [
+    "not compiled with cuda support",
+    "no cuda-capable device",
+    "cuda driver version is insufficient",
+    "invalid device ordinal",
+    "unsupported device",
+];
+
+/// An error returned by this crate.
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    /// The model cannot be loaded, e.g. the directory is invalid or the model is corrupted.
+    #[error("failed to load the model: {0}")]
+    ModelLoad(String),
+    /// The tokenizer or its config cannot be loaded.
+    #[error("failed to load the tokenizer: {0}")]
+    Tokenizer(String),
+    /// The inputs cannot be encoded.
+    #[error("failed to encode the input: {0}")]
+    Encode(String),
+    /// The outputs cannot be decoded.
+    #[error("failed to decode the output: {0}")]
+    Decode(String),
+    /// The device ran out of memory; the request may succeed with smaller batches or later.
+    #[error("out of memory: {0}")]
+    OutOfMemory(String),
+    /// The options or inputs of the request are invalid.
+    #[error("invalid options: {0}")]
+    InvalidOptions(String),
+    /// The requested device or compute type is not available.
+    #[error("device unavailable: {0}")]
+    DeviceUnavailable(String),
+    /// The model was unloaded and must be loaded again before it is used.
+    #[error("model unloaded: {0}")]
+    ModelUnloaded(String),
+    /// Any other error raised by CTranslate2.
+    #[error("CTranslate2 error: {0}")]
+    Ffi(String),
+}
+
+/// A result with [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Maps an exception thrown while loading a model.
+    pub(crate) fn model_load(err: cxx::Exception) -> Error {
+        Error::from_exception(err.what(), Error::ModelLoad)
+    }
+
+    /// Returns true if the error is caused by the request, so that retrying it as is will
+    /// fail again.
+    pub fn is_client_error(&self) -> bool {
+        matches!(self, Error::Encode(_) | Error::InvalidOptions(_))
+    }
+
+    /// Returns true if the request may succeed when retried later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::OutOfMemory(_))
+    }
+
+    /// Maps the message of a C++ exception to a variant, falling back to `default`.
+    fn from_exception(what: &str, default: fn(String) -> Error) -> Error {
+        if let Some(msg) = what.strip_prefix(INVALID_ARGUMENT_PREFIX) {
+            return Error::InvalidOptions(msg.to_string());
+        }
+        if let Some(msg) = what.strip_prefix(BAD_ALLOC_PREFIX) {
+            return Error::OutOfMemory(msg.to_string());
+        }
+        if let Some(msg) = what.strip_prefix(MODEL_UNLOADED_PREFIX) {
+            return Error::ModelUnloaded(msg.to_string());
+        }
+
+        let lower = what.to_lowercase();
+        if lower.contains("out of memory") {
+            Error::OutOfMemory(what.to_string())
+        } else if DEVICE_UNAVAILABLE_MESSAGES
+            .iter()
+            .any(|m| lower.contains(m))
+        {
+            Error::DeviceUnavailable(what.to_string())
+        } else {
+            default(what.to_string())
+        }
+    }
+}
+
+impl From<cxx::Exception> for Error {
+    fn from(err: cxx::Exception) -> Self {
+        Error::from_exception(err.what(), Error::Ffi)
+    }
+}
+
+impl From<ModelDirError> for Error {
+    fn from(err: ModelDirError) -> Self {
+        Error::ModelLoad(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn prefixes_of_exception_h_are_mapped() {
+        let err = Error::from_exception("invalid_argument: beam_size must be > 0", Error::Ffi);
+        assert!(matches!(err, Error::InvalidOptions(msg) if msg == "beam_size must be > 0"));
+
+        let err = Error::from_exception("bad_alloc: std::bad_alloc", Error::Ffi);
+        assert!(matches!(err, Error::OutOfMemory(msg) if msg == "std::bad_alloc"));
+
+        let err = Error::from_exception("model_unloaded: the model is unloaded", Error::Ffi);
+        assert!(matches!(err, Error::ModelUnloaded(msg) if msg == "the model is unloaded"));
+    }
+
+    #[test]
+    fn messages_are_matched_case_insensitively() {
+        let err = Error::from_exception("CUDA failed with error out of memory", Error::Ffi);
+        assert!(matches!(&err, Error::OutOfMemory(_)));
+        assert!(err.is_retryable());
+
+        let err =
+            Error::from_exception("CUDA failed with error invalid device ordinal", Error::Ffi);
+        assert!(matches!(err, Error::DeviceUnavailable(_)));
+
+        let err = Error::from_exception(
+            "This CTranslate2 package was not compiled with CUDA support",
+            Error::ModelLoad,
+        );
+        assert!(matches!(err, Error::DeviceUnavailable(_)));
+    }
+
+    #[test]
+    fn other_messages_fall_back_to_the_default() {
+        let err = Error::from_exception("unsupported model spec", Error::Ffi);
+        assert!(matches!(err, Error::Ffi(msg) if msg == "unsupported model spec"));
+
+        let err = Error::from_exception("unable to open file 'model.bin'", Error::ModelLoad);
+        assert!(matches!(&err, Error::ModelLoad(msg) if msg == "unable to open file 'model.bin'"));
+        assert!(!err.is_client_error());
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn model_dir_error_is_a_model_load_error() {
+        let err = Error::from(ModelDirError::MissingModel(PathBuf::from(
+            "/models/gpt2/model.bin",
+        )));
+        assert!(
+            matches!(err, Error::ModelLoad(msg) if msg == "/models/gpt2/model.bin is not found")
+        );
+    }
+}
@@ -0,0 +1,322 @@
+// future.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Futures of asynchronous batches.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// Time after which an idle completion thread exits.
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+/// A future resolved when a batch submitted to CTranslate2 finishes.
+///
+/// A thread of a shared pool blocks on the C++ futures of the batch and wakes the task
+/// awaiting this future, so that the calling thread is never blocked. Synchronous callers can
+/// block on [`BatchFuture::wait`] instead.
+pub struct BatchFuture<T> {
+    shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    ready: Condvar,
+}
+
+struct State<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+    /// Called with the result instead of storing it, set by [`BatchFuture::on_complete`].
+    callback: Option<Box<dyn FnOnce(Result<T>) + Send>>,
+}
+
+/// The sending half of a [`BatchFuture`], created by [`channel`].
+///
+/// The future resolves to an error if this is dropped without sending a result.
+pub(crate) struct Completer<T> {
+    shared: Option<Arc<Shared<T>>>,
+}
+
+/// Creates a future and the completer resolving it, which can be sent to another thread.
+pub(crate) fn channel<T>() -> (Completer<T>, BatchFuture<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            result: None,
+            waker: None,
+            callback: None,
+        }),
+        ready: Condvar::new(),
+    });
+    (
+        Completer {
+            shared: Some(shared.clone()),
+        },
+        BatchFuture { shared },
+    )
+}
+
+impl<T> Completer<T> {
+    /// Resolves the future with the given result.
+    pub(crate) fn complete(mut self, result: Result<T>) {
+        self.set(result);
+    }
+
+    fn set(&mut self, result: Result<T>) {
+        let Some(shared) = self.shared.take() else {
+            return;
+        };
+        let mut state = shared.state.lock().unwrap();
+        if let Some(callback) = state.callback.take() {
+            drop(state);
+            callback(result);
+            return;
+        }
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        shared.ready.notify_all();
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        self.set(Err(Error::Ffi(
+            "the batch was dropped before it finished".to_string(),
+        )));
+    }
+}
+
+impl<T: Send + 'static> BatchFuture<T> {
+    /// Runs `wait`, which blocks until the batch finishes, on a completion thread.
+    pub(crate) fn spawn<F>(wait: F) -> BatchFuture<T>
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        let (completer, future) = channel();
+        CompletionPool::global().execute(Box::new(move || completer.complete(wait())));
+        future
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The threads blocking on the batches until they finish.
+///
+/// Each pending batch has its own thread, so that the batches complete in any order, but the
+/// threads are reused across the batches and exit after [`KEEP_ALIVE`] without work.
+struct CompletionPool {
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+struct PoolState {
+    jobs: VecDeque<Job>,
+    /// Number of threads waiting for a job.
+    idle: usize,
+    /// Number of idle threads notified of a new job, which have not woken up yet.
+    notified: usize,
+}
+
+impl CompletionPool {
+    const fn new() -> CompletionPool {
+        CompletionPool {
+            state: Mutex::new(PoolState {
+                jobs: VecDeque::new(),
+                idle: 0,
+                notified: 0,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    fn global() -> &'static CompletionPool {
+        static POOL: CompletionPool = CompletionPool::new();
+        &POOL
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        // The jobs run without the lock, so the state is consistent even if one panicked.
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Runs the job on an idle thread, or on a new thread if all the threads are busy.
+    fn execute(&'static self, job: Job) {
+        let mut state = self.lock();
+        state.jobs.push_back(job);
+        if state.idle > state.notified {
+            state.notified += 1;
+            self.available.notify_one();
+            return;
+        }
+        drop(state);
+        thread::Builder::new()
+            .name("ctrans2-completion".to_string())
+            .spawn(move || self.run())
+            .expect("failed to spawn a completion thread");
+    }
+
+    fn run(&self) {
+        let mut state = self.lock();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                // A panicking callback drops the completer of its batch, which resolves the
+                // future to an error, and the thread keeps serving the other batches.
+                panic::catch_unwind(AssertUnwindSafe(job)).ok();
+                state = self.lock();
+                continue;
+            }
+
+            state.idle += 1;
+            let (next, _) = self
+                .available
+                .wait_timeout_while(state, KEEP_ALIVE, |state| state.notified == 0)
+                .unwrap_or_else(|err| err.into_inner());
+            state = next;
+            state.idle -= 1;
+            if state.notified == 0 {
+                // No job came during the keep-alive.
+                return;
+            }
+            state.notified -= 1;
+        }
+    }
+}
+
+impl<T> BatchFuture<T> {
+    /// Blocks the calling thread until the batch finishes.
+    pub fn wait(self) -> Result<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            state = self.shared.ready.wait(state).unwrap();
+        }
+    }
+
+    /// Calls `callback` with the result when the batch finishes, without blocking the calling
+    /// thread.
+    ///
+    /// The callback runs on the thread completing the batch, or on the calling thread if the
+    /// batch has already finished.
+    pub(crate) fn on_complete<F>(self, callback: F)
+    where
+        F: FnOnce(Result<T>) + Send + 'static,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => {
+                drop(state);
+                callback(result);
+            }
+            None => state.callback = Some(Box::new(callback)),
+        }
+    }
+}
+
+impl<T> Future for BatchFuture<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn callback_receives_the_result() {
+        // Registered before the batch finishes.
+        let (completer, future) = channel();
+        let (sender, receiver) = mpsc::channel();
+        future.on_complete(move |res: Result<i32>| sender.send(res.unwrap()).unwrap());
+        assert!(receiver.try_recv().is_err());
+        completer.complete(Ok(1));
+        assert_eq!(receiver.try_recv().unwrap(), 1);
+
+        // Registered after the batch finished.
+        let (completer, future) = channel();
+        completer.complete(Ok(2));
+        let (sender, receiver) = mpsc::channel();
+        future.on_complete(move |res: Result<i32>| sender.send(res.unwrap()).unwrap());
+        assert_eq!(receiver.try_recv().unwrap(), 2);
+    }
+
+    #[test]
+    fn batches_complete_in_any_order() {
+        let (first_sender, first_receiver) = mpsc::channel::<i32>();
+        let first = BatchFuture::spawn(move || Ok(first_receiver.recv().unwrap()));
+        let (second_sender, second_receiver) = mpsc::channel::<i32>();
+        let second = BatchFuture::spawn(move || Ok(second_receiver.recv().unwrap()));
+
+        // The second batch is completed while the first one is still running.
+        second_sender.send(2).unwrap();
+        assert_eq!(second.wait().unwrap(), 2);
+        first_sender.send(1).unwrap();
+        assert_eq!(first.wait().unwrap(), 1);
+    }
+
+    #[test]
+    fn idle_threads_are_reused() {
+        let pool: &'static CompletionPool = Box::leak(Box::new(CompletionPool::new()));
+        let run = || {
+            let (sender, receiver) = mpsc::channel();
+            pool.execute(Box::new(move || {
+                sender.send(thread::current().id()).unwrap()
+            }));
+            receiver.recv().unwrap()
+        };
+
+        let first = run();
+        // Wait for the thread to go back to the pool.
+        while pool.lock().idle == 0 {
+            thread::yield_now();
+        }
+        assert_eq!(run(), first);
+        assert_ne!(first, thread::current().id());
+    }
+
+    #[test]
+    fn panicking_callback_does_not_stop_the_completion_thread() {
+        let (sender, receiver) = mpsc::channel::<i32>();
+        let future = BatchFuture::spawn(move || Ok(receiver.recv().unwrap()));
+        future.on_complete(|_| panic!("callback panicked"));
+        sender.send(1).unwrap();
+
+        let future = BatchFuture::spawn(|| Ok(2));
+        assert_eq!(future.wait().unwrap(), 2);
+    }
+
+    #[test]
+    fn dropped_completer_resolves_to_an_error() {
+        let (completer, future) = channel::<i32>();
+        drop(completer);
+        assert!(matches!(future.wait(), Err(Error::Ffi(_))));
+    }
+}
@@ -0,0 +1,380 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tokenizers::{EncodeInput, Tokenizer};
+
+use crate::config::{BatchType, Config, Device};
+use crate::error::{Error, Result};
+use crate::future::{self, BatchFuture, Completer};
+use crate::source::ModelSource;
+
+use super::{GeneratedText, GenerationOptions, Generator};
+
+/// How [`BatchingGenerator`] collects requests into batches.
+#[derive(Clone, Debug)]
+pub struct BatchingOptions {
+    /// Maximum time to wait for more requests after the first request of a batch.
+    pub max_wait: Duration,
+    /// Maximum size of a batch, in number of `examples` or `tokens`.
+    pub max_batch_size: usize,
+    /// Whether `max_batch_size` is the number of `examples` or `tokens`. The number of tokens
+    /// of a batch includes the padding, i.e. it is the number of prompts times the length of
+    /// the longest one.
+    pub batch_type: BatchType,
+}
+
+impl Default for BatchingOptions {
+    fn default() -> Self {
+        Self {
+            max_wait: Duration::from_millis(10),
+            max_batch_size: 32,
+            batch_type: Default::default(),
+        }
+    }
+}
+
+/// A generator which batches the requests of concurrent callers.
+///
+/// The requests received within [`BatchingOptions::max_wait`] of each other are collected
+/// until the batch is full, grouped by their generation options, and each group is generated
+/// as a single batch. Requests with different options are never batched together.
+///
+/// The model is owned by a scheduler thread which submits each batch to the replicas without
+/// waiting for it to finish, and the requests of a batch are completed when it finishes. The
+/// scheduler blocks once CTranslate2 queues [`Config::max_queued_batches`] batches. This
+/// handle can be cloned and shared between threads, and the scheduler stops when all the
+/// handles are dropped.
+#[derive(Clone)]
+pub struct BatchingGenerator {
+    sender: Sender<Request>,
+    tokenizer: Tokenizer,
+}
+
+struct Request {
+    ids: Vec<usize>,
+    options: GenerationOptions<String, String>,
+    completer: Completer<GeneratedText>,
+}
+
+impl BatchingGenerator {
+    /// Initializes the generator and tokenizer on a new scheduler thread.
+    pub fn new<T: Into<ModelSource>>(
+        source: T,
+        device: Device,
+        config: Config,
+        options: BatchingOptions,
+    ) -> Result<BatchingGenerator> {
+        let source = source.into();
+        BatchingGenerator::spawn(move || Generator::new(source, device, config), options)
+    }
+
+    /// Initializes the generator with the given tokenizer on a new scheduler thread.
+    pub fn with_tokenizer<T: Into<ModelSource>>(
+        source: T,
+        device: Device,
+        config: Config,
+        tokenizer: Tokenizer,
+        options: BatchingOptions,
+    ) -> Result<BatchingGenerator> {
+        let source = source.into();
+        BatchingGenerator::spawn(
+            move || Generator::with_tokenizer(source, device, config, tokenizer),
+            options,
+        )
+    }
+
+    fn spawn<F>(load: F, options: BatchingOptions) -> Result<BatchingGenerator>
+    where
+        F: FnOnce() -> Result<Generator> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let (loaded, loaded_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let generator = match load() {
+                Ok(generator) => generator,
+                Err(err) => {
+                    loaded.send(Err(err)).ok();
+                    return;
+                }
+            };
+            loaded.send(Ok(generator.tokenizer().clone())).ok();
+            Scheduler {
+                generator: Arc::new(generator),
+                receiver,
+                options,
+            }
+            .run();
+        });
+
+        let tokenizer = loaded_receiver
+            .recv()
+            .map_err(|_| Error::ModelLoad("the scheduler thread panicked".to_string()))??;
+        Ok(BatchingGenerator { sender, tokenizer })
+    }
+
+    /// Generate a text with the given prompt, blocking until its batch finishes.
+    pub fn generate<'a, T>(
+        &self,
+        prompt: T,
+        options: &GenerationOptions<String, String>,
+    ) -> Result<GeneratedText>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        self.submit(prompt, options)?.wait()
+    }
+
+    /// Submits a prompt, returning a future resolved when its batch finishes.
+    ///
+    /// The prompt is encoded on the calling thread.
+    pub fn submit<'a, T>(
+        &self,
+        prompt: T,
+        options: &GenerationOptions<String, String>,
+    ) -> Result<BatchFuture<GeneratedText>>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        // Encoded as `Generator::generate_batch` does.
+        let ids = self
+            .tokenizer
+            .encode(prompt, false)
+            .map_err(|err| Error::Encode(err.to_string()))?
+            .get_ids()
+            .iter()
+            .map(|id| *id as usize)
+            .collect();
+
+        let (completer, future) = future::channel();
+        self.sender
+            .send(Request {
+                ids,
+                options: options.clone(),
+                completer,
+            })
+            .map_err(|_| Error::Ffi("the scheduler thread stopped".to_string()))?;
+        Ok(future)
+    }
+}
+
+struct Scheduler {
+    generator: Arc<Generator>,
+    receiver: Receiver<Request>,
+    options: BatchingOptions,
+}
+
+impl Scheduler {
+    fn run(self) {
+        // A request which did not fit in the previous batch.
+        let mut next = None;
+        loop {
+            // Block until the first request of the next batch.
+            let first = match next.take() {
+                Some(req) => req,
+                None => match self.receiver.recv() {
+                    Ok(req) => req,
+                    Err(_) => return,
+                },
+            };
+            let deadline = Instant::now() + self.options.max_wait;
+            let mut batch = Batch::default();
+            batch.push(first);
+
+            while !batch.is_full(&self.options) {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match self.receiver.recv_timeout(timeout) {
+                    Ok(req) if batch.fits(&req, &self.options) => batch.push(req),
+                    Ok(req) => {
+                        next = Some(req);
+                        break;
+                    }
+                    Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+
+            for group in batch.groups {
+                self.submit(group);
+            }
+        }
+    }
+
+    /// Submits a group of requests with the same options, completing them when the batch
+    /// finishes. The request is recorded in the metrics as [`Generator::generate_batch_ids`]
+    /// does.
+    fn submit(&self, group: Vec<Request>) {
+        let started = Instant::now();
+        let ids = group.iter().map(|r| r.ids.clone()).collect::<Vec<_>>();
+        let future = self
+            .generator
+            .prepare_options(&group[0].options)
+            .and_then(|options| {
+                self.generator
+                    .generator
+                    .generate_batch_ids_async(&ids, &options)
+            });
+        match future {
+            Ok(future) => {
+                let generator = self.generator.clone();
+                future.on_complete(move |output| {
+                    let res = output.and_then(|output| generator.decode(output, false));
+                    let res = generator.observe(started, &ids, &group[0].options, res);
+                    complete(group, res);
+                });
+            }
+            Err(err) => {
+                let res = self
+                    .generator
+                    .observe(started, &ids, &group[0].options, Err(err));
+                complete(group, res);
+            }
+        }
+    }
+}
+
+/// Completes each request of a group with its result.
+fn complete(group: Vec<Request>, res: Result<Vec<GeneratedText>>) {
+    match res {
+        Ok(results) => {
+            for (req, res) in group.into_iter().zip(results) {
+                req.completer.complete(Ok(res));
+            }
+        }
+        Err(err) => {
+            for req in group {
+                req.completer.complete(Err(err.clone()));
+            }
+        }
+    }
+}
+
+/// Requests collected for a batch, grouped by options.
+#[derive(Default)]
+struct Batch {
+    groups: Vec<Vec<Request>>,
+    num_examples: usize,
+    max_length: usize,
+}
+
+impl Batch {
+    fn push(&mut self, req: Request) {
+        self.num_examples += 1;
+        self.max_length = self.max_length.max(req.ids.len());
+        match self.groups.iter_mut().find(|g| g[0].options == req.options) {
+            Some(group) => group.push(req),
+            None => self.groups.push(vec![req]),
+        }
+    }
+
+    /// Returns the size of the batch with `num_examples` prompts of at most `max_length`
+    /// tokens.
+    fn size(num_examples: usize, max_length: usize, options: &BatchingOptions) -> usize {
+        match options.batch_type {
+            BatchType::Examples => num_examples,
+            BatchType::Tokens => num_examples * max_length,
+        }
+    }
+
+    fn is_full(&self, options: &BatchingOptions) -> bool {
+        Batch::size(self.num_examples, self.max_length, options) >= options.max_batch_size
+    }
+
+    /// Returns true if the request can be added without exceeding the maximum batch size.
+    fn fits(&self, req: &Request, options: &BatchingOptions) -> bool {
+        let size = Batch::size(
+            self.num_examples + 1,
+            self.max_length.max(req.ids.len()),
+            options,
+        );
+        size <= options.max_batch_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(len: usize, max_length: usize) -> Request {
+        Request {
+            ids: vec![0; len],
+            options: GenerationOptions {
+                max_length,
+                ..Default::default()
+            },
+            completer: future::channel().0,
+        }
+    }
+
+    fn options(max_batch_size: usize, batch_type: BatchType) -> BatchingOptions {
+        BatchingOptions {
+            max_batch_size,
+            batch_type,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn size_counts_examples_or_tokens() {
+        let examples = options(4, BatchType::Examples);
+        let tokens = options(16, BatchType::Tokens);
+        assert_eq!(Batch::size(3, 5, &examples), 3);
+        assert_eq!(Batch::size(3, 5, &tokens), 15);
+        assert_eq!(Batch::size(0, 0, &tokens), 0);
+    }
+
+    #[test]
+    fn batch_of_examples_is_full_at_max_batch_size() {
+        let options = options(2, BatchType::Examples);
+        let mut batch = Batch::default();
+        batch.push(request(100, 8));
+        assert!(!batch.is_full(&options));
+        assert!(batch.fits(&request(100, 8), &options));
+        batch.push(request(100, 8));
+        assert!(batch.is_full(&options));
+        assert!(!batch.fits(&request(1, 8), &options));
+    }
+
+    #[test]
+    fn batch_of_tokens_includes_the_padding() {
+        let options = options(16, BatchType::Tokens);
+        let mut batch = Batch::default();
+        batch.push(request(4, 8));
+        assert!(!batch.is_full(&options));
+        // The shorter prompt is padded to 8 tokens.
+        assert!(batch.fits(&request(8, 8), &options));
+        assert!(!batch.fits(&request(9, 8), &options));
+        batch.push(request(4, 8));
+        // 3 prompts of 5 tokens fit in 16 tokens, but not 3 prompts of 6 tokens.
+        assert!(batch.fits(&request(5, 8), &options));
+        assert!(!batch.fits(&request(6, 8), &options));
+    }
+
+    #[test]
+    fn oversized_first_request_fills_the_batch() {
+        let options = options(16, BatchType::Tokens);
+        let mut batch = Batch::default();
+        assert!(!batch.fits(&request(32, 8), &options));
+        // The first request is pushed regardless, and is generated alone.
+        batch.push(request(32, 8));
+        assert!(batch.is_full(&options));
+        assert!(!batch.fits(&request(1, 8), &options));
+    }
+
+    #[test]
+    fn requests_are_grouped_by_options() {
+        let mut batch = Batch::default();
+        batch.push(request(1, 8));
+        batch.push(request(2, 16));
+        batch.push(request(3, 8));
+        assert_eq!(batch.num_examples, 3);
+        assert_eq!(batch.max_length, 3);
+        let groups = batch
+            .groups
+            .iter()
+            .map(|g| g.iter().map(|r| r.ids.len()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(groups, vec![vec![1, 3], vec![2]]);
+        assert_eq!(batch.groups[1][0].options.max_length, 16);
+    }
+}
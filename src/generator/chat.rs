@@ -0,0 +1,259 @@
+use std::fs;
+use std::path::Path;
+
+use minijinja::{context, Environment, ErrorKind};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+const TEMPLATE_NAME: &str = "chat";
+
+/// A message of a conversation.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChatMessage {
+    /// Role of the author, such as `system`, `user` or `assistant`.
+    pub role: String,
+    /// Content of the message.
+    pub content: String,
+}
+
+impl ChatMessage {
+    /// Creates a message with the given role.
+    pub fn new<T: Into<String>, U: Into<String>>(role: T, content: U) -> Self {
+        Self {
+            role: role.into(),
+            content: content.into(),
+        }
+    }
+
+    /// Creates a system message.
+    pub fn system<T: Into<String>>(content: T) -> Self {
+        Self::new("system", content)
+    }
+
+    /// Creates a user message.
+    pub fn user<T: Into<String>>(content: T) -> Self {
+        Self::new("user", content)
+    }
+
+    /// Creates an assistant message.
+    pub fn assistant<T: Into<String>>(content: T) -> Self {
+        Self::new("assistant", content)
+    }
+}
+
+/// A chat template read from `tokenizer_config.json`, which renders conversations into prompts.
+pub struct ChatTemplate {
+    env: Environment<'static>,
+    /// The BOS token, if defined.
+    pub bos_token: Option<String>,
+    /// The EOS token, if defined. Instruction-tuned models usually end the replies with it.
+    pub eos_token: Option<String>,
+    /// Whether the tokenizer adds the BOS token at the beginning of the inputs.
+    pub add_bos_token: bool,
+}
+
+impl ChatTemplate {
+    /// Reads the chat template from the given `tokenizer_config.json`.
+    ///
+    /// Returns `None` if the config does not define a chat template.
+    pub fn from_file<T: AsRef<Path>>(path: T) -> Result<Option<ChatTemplate>> {
+        let path = path.as_ref();
+        let config = fs::read_to_string(path)
+            .map_err(|err| Error::Tokenizer(format!("failed to read {}: {err}", path.display())))?;
+        let config: Value = serde_json::from_str(&config).map_err(|err| {
+            Error::Tokenizer(format!("failed to parse {}: {err}", path.display()))
+        })?;
+        ChatTemplate::from_config(&config)
+    }
+
+    /// Reads the chat template from the given tokenizer config.
+    pub fn from_config(config: &Value) -> Result<Option<ChatTemplate>> {
+        let template = match &config["chat_template"] {
+            Value::String(template) => template.clone(),
+            // A list of named templates; use the default one.
+            Value::Array(templates) => match templates
+                .iter()
+                .find(|t| t["name"] == "default")
+                .and_then(|t| t["template"].as_str())
+            {
+                Some(template) => template.to_string(),
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        let mut env = Environment::new();
+        env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+        env.add_function("raise_exception", raise_exception);
+        env.add_template_owned(TEMPLATE_NAME, template)
+            .map_err(|err| Error::Tokenizer(format!("failed to parse the chat template: {err}")))?;
+
+        Ok(Some(ChatTemplate {
+            env,
+            bos_token: special_token(&config["bos_token"]),
+            eos_token: special_token(&config["eos_token"]),
+            add_bos_token: config["add_bos_token"].as_bool().unwrap_or(false),
+        }))
+    }
+
+    /// Renders the given conversation, followed by the prompt for the assistant reply.
+    pub fn render(&self, messages: &[ChatMessage]) -> Result<String> {
+        let messages = messages
+            .iter()
+            .map(|m| context! { role => m.role, content => m.content })
+            .collect::<Vec<_>>();
+        let rendered = self
+            .env
+            .get_template(TEMPLATE_NAME)
+            .and_then(|template| {
+                template.render(context! {
+                    messages => messages,
+                    bos_token => self.bos_token,
+                    eos_token => self.eos_token,
+                    add_generation_prompt => true,
+                })
+            })
+            .map_err(|err| Error::Encode(format!("failed to render the chat template: {err}")))?;
+
+        // Add the BOS token if the template does not, as the tokenizer would do.
+        match &self.bos_token {
+            Some(bos) if self.add_bos_token && !rendered.starts_with(bos.as_str()) => {
+                Ok(format!("{bos}{rendered}"))
+            }
+            _ => Ok(rendered),
+        }
+    }
+}
+
+/// Returns the content of a special token, which is either a string or an added token object.
+fn special_token(v: &Value) -> Option<String> {
+    match v {
+        Value::String(token) => Some(token.clone()),
+        Value::Object(token) => token
+            .get("content")
+            .and_then(|c| c.as_str())
+            .map(|c| c.to_string()),
+        _ => None,
+    }
+}
+
+/// `raise_exception` used by Hugging Face chat templates to reject invalid conversations.
+fn raise_exception(msg: String) -> Result<String, minijinja::Error> {
+    Err(minijinja::Error::new(ErrorKind::InvalidOperation, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `tokenizer_config.json` of Llama 2 Chat, trimmed to the relevant keys.
+    const LLAMA_CONFIG: &str = r#"{
+        "add_bos_token": true,
+        "add_eos_token": false,
+        "bos_token": {"__type": "AddedToken", "content": "<s>", "lstrip": false, "normalized": false, "rstrip": false, "single_word": false},
+        "chat_template": "{% if messages[0]['role'] == 'system' %}{% set loop_messages = messages[1:] %}{% set system_message = messages[0]['content'] %}{% else %}{% set loop_messages = messages %}{% set system_message = false %}{% endif %}{% for message in loop_messages %}{% if (message['role'] == 'user') != (loop.index0 % 2 == 0) %}{{ raise_exception('Conversation roles must alternate user/assistant/user/assistant/...') }}{% endif %}{% if loop.index0 == 0 and system_message != false %}{% set content = '<<SYS>>\\n' + system_message + '\\n<</SYS>>\\n\\n' + message['content'] %}{% else %}{% set content = message['content'] %}{% endif %}{% if message['role'] == 'user' %}{{ bos_token + '[INST] ' + content.strip() + ' [/INST]' }}{% elif message['role'] == 'assistant' %}{{ ' '  + content.strip() + ' ' + eos_token }}{% endif %}{% endfor %}",
+        "eos_token": {"__type": "AddedToken", "content": "</s>", "lstrip": false, "normalized": false, "rstrip": false, "single_word": false},
+        "unk_token": "<unk>"
+    }"#;
+
+    /// A ChatML template as used by Qwen, with the BOS token added by the tokenizer.
+    const CHATML_CONFIG: &str = r#"{
+        "add_bos_token": true,
+        "bos_token": "<s>",
+        "chat_template": "{% for message in messages %}{{'<|im_start|>' + message['role'] + '\n' + message['content'] + '<|im_end|>' + '\n'}}{% endfor %}{% if add_generation_prompt %}{{ '<|im_start|>assistant\n' }}{% endif %}",
+        "eos_token": "<|im_end|>"
+    }"#;
+
+    fn template(config: &str) -> ChatTemplate {
+        let config = serde_json::from_str(config).unwrap();
+        ChatTemplate::from_config(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn llama_template_is_rendered() {
+        let template = template(LLAMA_CONFIG);
+        assert_eq!(template.bos_token.as_deref(), Some("<s>"));
+        assert_eq!(template.eos_token.as_deref(), Some("</s>"));
+        assert!(template.add_bos_token);
+
+        let prompt = template
+            .render(&[
+                ChatMessage::system("Be brief."),
+                ChatMessage::user("Hi"),
+                ChatMessage::assistant("Hello!"),
+                ChatMessage::user("How are you?"),
+            ])
+            .unwrap();
+        // The template starts with the BOS token, so it is not added again.
+        assert_eq!(
+            prompt,
+            "<s>[INST] <<SYS>>\nBe brief.\n<</SYS>>\n\nHi [/INST] Hello! </s><s>[INST] How are you? [/INST]"
+        );
+    }
+
+    #[test]
+    fn raise_exception_fails_the_rendering() {
+        let template = template(LLAMA_CONFIG);
+        let res = template.render(&[ChatMessage::assistant("Hello!")]);
+        assert!(
+            matches!(&res, Err(Error::Encode(msg)) if msg.contains("Conversation roles must alternate")),
+            "{res:?}"
+        );
+    }
+
+    #[test]
+    fn chatml_template_adds_the_generation_prompt() {
+        let template = template(CHATML_CONFIG);
+        let prompt = template
+            .render(&[ChatMessage::system("Be brief."), ChatMessage::user("Hi")])
+            .unwrap();
+        assert_eq!(
+            prompt,
+            "<s><|im_start|>system\nBe brief.<|im_end|>\n<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn bos_token_is_only_added_if_the_tokenizer_does() {
+        let mut config: Value = serde_json::from_str(CHATML_CONFIG).unwrap();
+        config["add_bos_token"] = Value::Bool(false);
+        let template = ChatTemplate::from_config(&config).unwrap().unwrap();
+        let prompt = template.render(&[ChatMessage::user("Hi")]).unwrap();
+        assert_eq!(
+            prompt,
+            "<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn default_template_is_chosen_from_a_list() {
+        let config = serde_json::json!({
+            "chat_template": [
+                {"name": "tool_use", "template": "tools"},
+                {"name": "default", "template": "{{ messages[0].content }}"},
+            ],
+        });
+        let template = ChatTemplate::from_config(&config).unwrap().unwrap();
+        assert_eq!(template.render(&[ChatMessage::user("Hi")]).unwrap(), "Hi");
+        assert!(!template.add_bos_token);
+
+        let config = serde_json::json!({
+            "chat_template": [{"name": "tool_use", "template": "tools"}],
+        });
+        assert!(ChatTemplate::from_config(&config).unwrap().is_none());
+        assert!(ChatTemplate::from_config(&serde_json::json!({}))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_template_is_rejected() {
+        let config = serde_json::json!({"chat_template": "{% for message in messages %}"});
+        assert!(matches!(
+            ChatTemplate::from_config(&config),
+            Err(Error::Tokenizer(_))
+        ));
+    }
+}
@@ -1,6 +1,13 @@
 use cxx::UniquePtr;
 
-use crate::config::{BatchType, ComputeType, Config, Device};
+use crate::config::{
+    builder_setters, validate_decoding, BatchType, ComputeType, Config, Device, EndToken,
+};
+use crate::device::check_config;
+use crate::error::{Error, Result};
+use crate::future::BatchFuture;
+use crate::scoring::{ScoringOptions, ScoringResult};
+use crate::source::ModelSource;
 
 #[cxx::bridge]
 mod ffi {
@@ -24,6 +31,9 @@ mod ffi {
         Int8Float16,
         Int16,
         Float16,
+        BFloat16,
+        Int8BFloat16,
+        Int8Float32,
     }
 
     struct GeneratorConfig {
@@ -39,6 +49,31 @@ mod ffi {
         Tokens,
     }
 
+    struct GenScoringOptions {
+        max_input_length: usize,
+        offset: i64,
+        max_batch_size: usize,
+        batch_type: GenerationBatchType,
+    }
+
+    struct GenScoringResult {
+        tokens: Vec<String>,
+        tokens_score: Vec<f32>,
+    }
+
+    enum GenEndTokenKind {
+        Default,
+        Token,
+        Tokens,
+        Ids,
+    }
+
+    struct GenEndToken<'a> {
+        kind: GenEndTokenKind,
+        tokens: Vec<&'a str>,
+        ids: Vec<usize>,
+    }
+
     struct GenerationOptions<'a> {
         beam_size: usize,
         patience: f32,
@@ -47,6 +82,7 @@ mod ffi {
         no_repeat_ngram_size: usize,
         disable_unk: bool,
         suppress_sequences: Vec<GenVecStr<'a>>,
+        end_token: GenEndToken<'a>,
         return_end_token: bool,
         max_length: usize,
         min_length: usize,
@@ -55,6 +91,8 @@ mod ffi {
         sampling_temperature: f32,
         num_hypotheses: usize,
         return_scores: bool,
+        return_log_prob: bool,
+        return_logits_vocab: bool,
         return_alternatives: bool,
         min_alternative_expansion_prob: f32,
         static_prompt: Vec<&'a str>,
@@ -64,28 +102,121 @@ mod ffi {
         batch_type: GenerationBatchType,
     }
 
+    struct GenLogits {
+        data: Vec<f32>,
+        num_steps: usize,
+        vocab_size: usize,
+    }
+
     struct GenerationResult {
         sequences: Vec<GenVecString>,
         sequences_ids: Vec<GenVecUSize>,
         scores: Vec<f32>,
+        logits: Vec<GenLogits>,
+    }
+
+    struct GenerationStepResult {
+        step: usize,
+        batch_id: usize,
+        token_id: usize,
+        hypothesis_id: usize,
+        token: String,
+        log_prob: f32,
+        has_log_prob: bool,
+        is_last: bool,
+    }
+
+    extern "Rust" {
+        type GenerationCallbackBox<'a>;
+
+        fn execute(self: &mut GenerationCallbackBox, arg: GenerationStepResult) -> bool;
     }
 
     unsafe extern "C++" {
         include!("../../include/generator.h");
+        include!("../../include/source.h");
 
         type Generator;
+        type ModelReaderHandle = crate::source::ffi::ModelReaderHandle;
+        type GenerationStream;
 
         fn new_generator(
-            model_path: &str,
+            reader: &ModelReaderHandle,
             cuda: bool,
             config: GeneratorConfig,
         ) -> Result<UniquePtr<Generator>>;
 
         fn generate_batch(
-            &self,
+            self: &Generator,
+            start_tokens: Vec<GenVecStr>,
+            options: GenerationOptions,
+        ) -> Result<Vec<GenerationResult>>;
+
+        fn generate_batch_ids(
+            self: &Generator,
+            start_ids: Vec<GenVecUSize>,
+            options: GenerationOptions,
+        ) -> Result<Vec<GenerationResult>>;
+
+        fn generate_batch_with_callback(
+            self: &Generator,
             start_tokens: Vec<GenVecStr>,
             options: GenerationOptions,
+            callback: &mut GenerationCallbackBox,
+        ) -> Result<Vec<GenerationResult>>;
+
+        fn generate_batch_ids_with_callback(
+            self: &Generator,
+            start_ids: Vec<GenVecUSize>,
+            options: GenerationOptions,
+            callback: &mut GenerationCallbackBox,
         ) -> Result<Vec<GenerationResult>>;
+
+        fn generate_batch_async(
+            self: &Generator,
+            start_tokens: Vec<GenVecStr>,
+            options: GenerationOptions,
+        ) -> Result<UniquePtr<GenerationStream>>;
+
+        fn generate_batch_ids_async(
+            self: &Generator,
+            start_ids: Vec<GenVecUSize>,
+            options: GenerationOptions,
+        ) -> Result<UniquePtr<GenerationStream>>;
+
+        fn score_batch(
+            self: &Generator,
+            tokens: Vec<GenVecStr>,
+            options: GenScoringOptions,
+        ) -> Result<Vec<GenScoringResult>>;
+
+        fn generate_stream(
+            self: &Generator,
+            start_tokens: Vec<GenVecStr>,
+            options: GenerationOptions,
+            callback: Box<GenerationCallbackBox<'static>>,
+        ) -> Result<UniquePtr<GenerationStream>>;
+
+        fn generate_stream_ids(
+            self: &Generator,
+            start_ids: Vec<GenVecUSize>,
+            options: GenerationOptions,
+            callback: Box<GenerationCallbackBox<'static>>,
+        ) -> Result<UniquePtr<GenerationStream>>;
+
+        fn num_replicas(self: &Generator) -> usize;
+
+        fn num_queued_batches(self: &Generator) -> usize;
+
+        fn num_active_batches(self: &Generator) -> usize;
+
+        fn unload_model(self: &Generator, to_cpu: bool) -> Result<bool>;
+
+        fn load_model(self: &Generator) -> Result<()>;
+
+        fn model_is_loaded(self: &Generator) -> bool;
+
+        fn wait(self: Pin<&mut GenerationStream>) -> Result<Vec<GenerationResult>>;
     }
 }
 
@@ -95,20 +226,14 @@ pub struct Generator {
 }
 
 impl Generator {
-    pub fn new<T: AsRef<str>>(
-        model_path: T,
-        device: Device,
-        config: Config,
-    ) -> anyhow::Result<Generator> {
+    pub fn new(source: &ModelSource, device: Device, config: Config) -> Result<Generator> {
+        let (device, compute_type) = check_config(device, &config)?;
         Ok(Generator {
             ptr: ffi::new_generator(
-                model_path.as_ref(),
-                match device {
-                    Device::CPU => false,
-                    Device::CUDA => true,
-                },
+                &*source.to_ffi()?,
+                device == Device::CUDA,
                 ffi::GeneratorConfig {
-                    compute_type: match config.compute_type {
+                    compute_type: match compute_type {
                         ComputeType::Default => ffi::GenComputeType::Default,
                         ComputeType::Auto => ffi::GenComputeType::Auto,
                         ComputeType::Float32 => ffi::GenComputeType::Float32,
@@ -116,13 +241,17 @@ impl Generator {
                         ComputeType::Int8Float16 => ffi::GenComputeType::Int8Float16,
                         ComputeType::Int16 => ffi::GenComputeType::Int16,
                         ComputeType::Float16 => ffi::GenComputeType::Float16,
+                        ComputeType::BFloat16 => ffi::GenComputeType::BFloat16,
+                        ComputeType::Int8BFloat16 => ffi::GenComputeType::Int8BFloat16,
+                        ComputeType::Int8Float32 => ffi::GenComputeType::Int8Float32,
                     },
                     device_indices: config.device_indices,
                     num_threads_per_replica: config.num_threads_per_replica,
                     max_queued_batches: config.max_queued_batches,
                     cpu_core_offset: config.cpu_core_offset,
                 },
-            )?,
+            )
+            .map_err(Error::model_load)?,
         })
     }
 
@@ -134,7 +263,7 @@ impl Generator {
         &self,
         start_tokens: &[Vec<T>],
         options: &GenerationOptions<U, V>,
-    ) -> anyhow::Result<Vec<GenerationResult>> {
+    ) -> Result<Vec<GenerationResult>> {
         Ok(self
             .ptr
             .generate_batch(vec_ffi_vecstr(start_tokens), options.to_ffi())?
@@ -142,10 +271,259 @@ impl Generator {
             .map(GenerationResult::from)
             .collect())
     }
+
+    /// Generates from a batch of start token IDs.
+    ///
+    /// This is the same as [`Generator::generate_batch`], but the IDs are given directly instead
+    /// of being looked up from the token strings in the model vocabulary.
+    pub fn generate_batch_ids<U: AsRef<str>, V: AsRef<str>>(
+        &self,
+        start_ids: &[Vec<usize>],
+        options: &GenerationOptions<U, V>,
+    ) -> Result<Vec<GenerationResult>> {
+        Ok(self
+            .ptr
+            .generate_batch_ids(vec_ffi_vecusize(start_ids), options.to_ffi())?
+            .into_iter()
+            .map(GenerationResult::from)
+            .collect())
+    }
+
+    /// Generates from a batch of start tokens, calling `callback` for each generated token.
+    ///
+    /// The callback is only invoked in greedy search (`beam_size` is 1). Returning `true` from
+    /// the callback stops the decoding of the corresponding batch early.
+    pub fn generate_batch_with_callback<T, U, V, F>(
+        &self,
+        start_tokens: &[Vec<T>],
+        options: &GenerationOptions<U, V>,
+        callback: F,
+    ) -> Result<Vec<GenerationResult>>
+    where
+        T: AsRef<str>,
+        U: AsRef<str>,
+        V: AsRef<str>,
+        F: FnMut(GenerationStepResult) -> bool + Send,
+    {
+        let mut callback = GenerationCallbackBox(Box::new(callback));
+        Ok(self
+            .ptr
+            .generate_batch_with_callback(
+                vec_ffi_vecstr(start_tokens),
+                options.to_ffi(),
+                &mut callback,
+            )?
+            .into_iter()
+            .map(GenerationResult::from)
+            .collect())
+    }
+
+    /// Generates from a batch of start token IDs, calling `callback` for each generated token.
+    ///
+    /// See [`Generator::generate_batch_with_callback`].
+    pub fn generate_batch_ids_with_callback<U, V, F>(
+        &self,
+        start_ids: &[Vec<usize>],
+        options: &GenerationOptions<U, V>,
+        callback: F,
+    ) -> Result<Vec<GenerationResult>>
+    where
+        U: AsRef<str>,
+        V: AsRef<str>,
+        F: FnMut(GenerationStepResult) -> bool + Send,
+    {
+        let mut callback = GenerationCallbackBox(Box::new(callback));
+        Ok(self
+            .ptr
+            .generate_batch_ids_with_callback(
+                vec_ffi_vecusize(start_ids),
+                options.to_ffi(),
+                &mut callback,
+            )?
+            .into_iter()
+            .map(GenerationResult::from)
+            .collect())
+    }
+
+    /// Generates from a batch of start tokens without blocking the calling thread.
+    ///
+    /// The returned future resolves once every example of the batch has been generated.
+    pub fn generate_batch_async<T: AsRef<str>, U: AsRef<str>, V: AsRef<str>>(
+        &self,
+        start_tokens: &[Vec<T>],
+        options: &GenerationOptions<U, V>,
+    ) -> Result<BatchFuture<Vec<GenerationResult>>> {
+        let stream = GenerationStream {
+            ptr: self
+                .ptr
+                .generate_batch_async(vec_ffi_vecstr(start_tokens), options.to_ffi())?,
+        };
+        Ok(BatchFuture::spawn(move || stream.wait()))
+    }
+
+    /// Generates from a batch of start token IDs without blocking the calling thread.
+    ///
+    /// See [`Generator::generate_batch_async`].
+    pub fn generate_batch_ids_async<U: AsRef<str>, V: AsRef<str>>(
+        &self,
+        start_ids: &[Vec<usize>],
+        options: &GenerationOptions<U, V>,
+    ) -> Result<BatchFuture<Vec<GenerationResult>>> {
+        let stream = GenerationStream {
+            ptr: self
+                .ptr
+                .generate_batch_ids_async(vec_ffi_vecusize(start_ids), options.to_ffi())?,
+        };
+        Ok(BatchFuture::spawn(move || stream.wait()))
+    }
+
+    /// Scores a batch of tokens.
+    ///
+    /// If the model expects special start or end tokens, they should also be added to `tokens`.
+    pub fn score_batch<T: AsRef<str>>(
+        &self,
+        tokens: &[Vec<T>],
+        options: &ScoringOptions,
+    ) -> Result<Vec<ScoringResult>> {
+        Ok(self
+            .ptr
+            .score_batch(vec_ffi_vecstr(tokens), scoring_options_to_ffi(options))?
+            .into_iter()
+            .map(|r| ScoringResult {
+                tokens: r.tokens,
+                tokens_score: r.tokens_score,
+            })
+            .collect())
+    }
+
+    /// Starts generating from a batch of start tokens without waiting for the results.
+    ///
+    /// `callback` is called from a worker thread for each generated token, under the same
+    /// conditions as [`Generator::generate_batch_with_callback`].
+    pub fn generate_stream<T, U, V, F>(
+        &self,
+        start_tokens: &[Vec<T>],
+        options: &GenerationOptions<U, V>,
+        callback: F,
+    ) -> Result<GenerationStream>
+    where
+        T: AsRef<str>,
+        U: AsRef<str>,
+        V: AsRef<str>,
+        F: FnMut(GenerationStepResult) -> bool + Send + 'static,
+    {
+        Ok(GenerationStream {
+            ptr: self.ptr.generate_stream(
+                vec_ffi_vecstr(start_tokens),
+                options.to_ffi(),
+                Box::new(GenerationCallbackBox(Box::new(callback))),
+            )?,
+        })
+    }
+
+    /// Starts generating from a batch of start token IDs without waiting for the results.
+    ///
+    /// See [`Generator::generate_stream`].
+    pub fn generate_stream_ids<U, V, F>(
+        &self,
+        start_ids: &[Vec<usize>],
+        options: &GenerationOptions<U, V>,
+        callback: F,
+    ) -> Result<GenerationStream>
+    where
+        U: AsRef<str>,
+        V: AsRef<str>,
+        F: FnMut(GenerationStepResult) -> bool + Send + 'static,
+    {
+        Ok(GenerationStream {
+            ptr: self.ptr.generate_stream_ids(
+                vec_ffi_vecusize(start_ids),
+                options.to_ffi(),
+                Box::new(GenerationCallbackBox(Box::new(callback))),
+            )?,
+        })
+    }
+
+    /// Number of parallel replicas.
+    pub fn num_replicas(&self) -> usize {
+        self.ptr.num_replicas()
+    }
+
+    /// Number of batches in the work queue.
+    pub fn num_queued_batches(&self) -> usize {
+        self.ptr.num_queued_batches()
+    }
+
+    /// Number of batches in the work queue or currently processed by a worker.
+    pub fn num_active_batches(&self) -> usize {
+        self.ptr.num_active_batches()
+    }
+
+    /// Unloads the model, keeping it in the CPU memory if `to_cpu` is set.
+    ///
+    /// Returns false if some batches are still queued or running, in which case the model is
+    /// kept loaded.
+    pub fn unload_model(&self, to_cpu: bool) -> Result<bool> {
+        Ok(self.ptr.unload_model(to_cpu)?)
+    }
+
+    /// Loads the model again after [`unload_model`](Self::unload_model).
+    pub fn load_model(&self) -> Result<()> {
+        self.ptr.load_model().map_err(Error::model_load)
+    }
+
+    /// Returns true if the model is loaded.
+    pub fn model_is_loaded(&self) -> bool {
+        self.ptr.model_is_loaded()
+    }
+}
+
+// The C++ `Generator` only holds a `std::shared_ptr<ctranslate2::Generator>`, and all its
+// methods are `const`. `ctranslate2::Generator` is a pool of replicas: the batches are pushed
+// to a queue guarded by a mutex, and each replica is only used by its own worker thread, so
+// it can be called from any number of threads at once. The callbacks passed from Rust are
+// `Send` and serialized by a mutex in `cpp/generator.cc` since the replicas may call them
+// concurrently.
+unsafe impl Send for ffi::Generator {}
+unsafe impl Sync for ffi::Generator {}
+
+/// A generation running in the background, started by [`Generator::generate_stream`].
+pub struct GenerationStream {
+    ptr: UniquePtr<ffi::GenerationStream>,
+}
+
+// A `GenerationStream` only holds the futures of the submitted batches, which can be waited
+// for from any thread.
+unsafe impl Send for ffi::GenerationStream {}
+
+impl GenerationStream {
+    /// Blocks until the generation finishes and returns the results.
+    pub fn wait(mut self) -> Result<Vec<GenerationResult>> {
+        Ok(self
+            .ptr
+            .pin_mut()
+            .wait()?
+            .into_iter()
+            .map(GenerationResult::from)
+            .collect())
+    }
 }
 
 /// The set of generation options.
-#[derive(Debug)]
+///
+/// [`GenerationOptions::builder`] checks the options when they are built.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        default,
+        bound(
+            deserialize = "T: serde::Deserialize<'de>, U: serde::Deserialize<'de>, \
+                           GenerationOptions<T, U>: Default"
+        )
+    )
+)]
 pub struct GenerationOptions<T: AsRef<str>, U: AsRef<str>> {
     /// Beam size to use for beam search (set 1 to run greedy search).
     pub beam_size: usize,
@@ -167,8 +545,8 @@ pub struct GenerationOptions<T: AsRef<str>, U: AsRef<str>> {
     pub disable_unk: bool,
     /// Disable the generation of some sequences of tokens.
     pub suppress_sequences: Vec<Vec<T>>,
-    // Stop the decoding on one of these tokens (defaults to the model EOS token).
-    //std::variant<std::string, std::vector<std::string>, std::vector<size_t>> end_token;
+    /// Stop the decoding on one of these tokens (defaults to the model EOS token).
+    pub end_token: Option<EndToken>,
     /// Include the end token in the result.
     pub return_end_token: bool,
     /// Length constraints.
@@ -185,6 +563,10 @@ pub struct GenerationOptions<T: AsRef<str>, U: AsRef<str>> {
     pub num_hypotheses: usize,
     /// Include scores in the result.
     pub return_scores: bool,
+    /// Include the log probability of each token in the step results passed to the callback.
+    pub return_log_prob: bool,
+    /// Include the log probabilities over the full vocabulary at each step in the result.
+    pub return_logits_vocab: bool,
     /// Return alternatives at the first unconstrained decoding position. This is typically
     /// used with a prefix to provide alternatives at a specifc location.
     pub return_alternatives: bool,
@@ -197,9 +579,6 @@ pub struct GenerationOptions<T: AsRef<str>, U: AsRef<str>> {
     pub cache_static_prompt: bool,
     /// Include the input tokens in the generation result.
     pub include_prompt_in_result: bool,
-    // Function to call for each generated token in greedy search.
-    // Returns true indicate the current generation is considered finished thus can be stopped early.
-    //std::function<bool(GenerationStepResult)> callback = nullptr;
     /// The maximum batch size. If the number of inputs is greater than `max_batch_size`,
     /// the inputs are sorted by length and split by chunks of `max_batch_size` examples
     /// so that the number of padding positions is minimized.
@@ -218,6 +597,7 @@ impl Default for GenerationOptions<String, String> {
             no_repeat_ngram_size: 0,
             disable_unk: false,
             suppress_sequences: vec![],
+            end_token: None,
             return_end_token: false,
             max_length: 512,
             min_length: 0,
@@ -226,6 +606,8 @@ impl Default for GenerationOptions<String, String> {
             sampling_temperature: 1.,
             num_hypotheses: 1,
             return_scores: false,
+            return_log_prob: false,
+            return_logits_vocab: false,
             return_alternatives: false,
             min_alternative_expansion_prob: 0.,
             static_prompt: vec![],
@@ -237,7 +619,25 @@ impl Default for GenerationOptions<String, String> {
     }
 }
 
+impl GenerationOptions<String, String> {
+    /// Returns a builder of generation options, starting from the default values.
+    pub fn builder() -> GenerationOptionsBuilder {
+        GenerationOptionsBuilder::default()
+    }
+}
+
 impl<T: AsRef<str>, U: AsRef<str>> GenerationOptions<T, U> {
+    /// Checks the options, returning [`Error::InvalidOptions`] if they cannot be used.
+    pub fn validate(&self) -> Result<()> {
+        validate_decoding(
+            self.beam_size,
+            self.num_hypotheses,
+            self.sampling_topp,
+            self.min_length,
+            self.max_length,
+        )
+    }
+
     #[inline]
     fn to_ffi(&self) -> ffi::GenerationOptions {
         ffi::GenerationOptions {
@@ -248,6 +648,7 @@ impl<T: AsRef<str>, U: AsRef<str>> GenerationOptions<T, U> {
             no_repeat_ngram_size: self.no_repeat_ngram_size,
             disable_unk: self.disable_unk,
             suppress_sequences: vec_ffi_vecstr(self.suppress_sequences.as_ref()),
+            end_token: ffi_end_token(self.end_token.as_ref()),
             return_end_token: self.return_end_token,
             max_length: self.max_length,
             min_length: self.min_length,
@@ -256,6 +657,8 @@ impl<T: AsRef<str>, U: AsRef<str>> GenerationOptions<T, U> {
             sampling_temperature: self.sampling_temperature,
             num_hypotheses: self.num_hypotheses,
             return_scores: self.return_scores,
+            return_log_prob: self.return_log_prob,
+            return_logits_vocab: self.return_logits_vocab,
             return_alternatives: self.return_alternatives,
             min_alternative_expansion_prob: self.min_alternative_expansion_prob,
             static_prompt: self.static_prompt.iter().map(|v| v.as_ref()).collect(),
@@ -270,6 +673,71 @@ impl<T: AsRef<str>, U: AsRef<str>> GenerationOptions<T, U> {
     }
 }
 
+/// Builds [`GenerationOptions`], checking them.
+///
+/// ```
+/// use ctrans2::GenerationOptions;
+///
+/// let options = GenerationOptions::builder()
+///     .max_length(128)
+///     .sampling_topk(10)
+///     .sampling_topp(0.9)
+///     .build()
+///     .unwrap();
+/// assert!(GenerationOptions::builder().beam_size(0).build().is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct GenerationOptionsBuilder {
+    options: GenerationOptions<String, String>,
+}
+
+impl GenerationOptionsBuilder {
+    builder_setters!(options: GenerationOptions {
+        beam_size: usize,
+        patience: f32,
+        length_penalty: f32,
+        repetition_penalty: f32,
+        no_repeat_ngram_size: usize,
+        disable_unk: bool,
+        suppress_sequences: Vec<Vec<String>>,
+        return_end_token: bool,
+        max_length: usize,
+        min_length: usize,
+        sampling_topk: usize,
+        sampling_topp: f32,
+        sampling_temperature: f32,
+        num_hypotheses: usize,
+        return_scores: bool,
+        return_log_prob: bool,
+        return_logits_vocab: bool,
+        return_alternatives: bool,
+        min_alternative_expansion_prob: f32,
+        static_prompt: Vec<String>,
+        cache_static_prompt: bool,
+        include_prompt_in_result: bool,
+        max_batch_size: usize,
+        batch_type: BatchType,
+    });
+
+    /// Sets [`GenerationOptions::end_token`].
+    pub fn end_token(mut self, end_token: EndToken) -> Self {
+        self.options.end_token = Some(end_token);
+        self
+    }
+
+    /// Returns the options, or [`Error::InvalidOptions`] if they are invalid.
+    pub fn build(self) -> Result<GenerationOptions<String, String>> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+}
+
+impl From<GenerationOptions<String, String>> for GenerationOptionsBuilder {
+    fn from(options: GenerationOptions<String, String>) -> Self {
+        Self { options }
+    }
+}
+
 /// A generation result.
 #[derive(Debug)]
 pub struct GenerationResult {
@@ -279,6 +747,9 @@ pub struct GenerationResult {
     pub sequences_ids: Vec<Vec<usize>>,
     /// Score of each sequence (empty if `return_scores` was disabled).
     pub scores: Vec<f32>,
+    /// Log probabilities over the vocabulary at each step of each sequence (empty if
+    /// `return_logits_vocab` was disabled).
+    pub logits: Vec<Logits>,
 }
 
 impl From<ffi::GenerationResult> for GenerationResult {
@@ -287,22 +758,140 @@ impl From<ffi::GenerationResult> for GenerationResult {
             sequences: res.sequences.into_iter().map(|c| c.v).collect(),
             sequences_ids: res.sequences_ids.into_iter().map(|c| c.v).collect(),
             scores: res.scores,
+            logits: res.logits.into_iter().map(Logits::from).collect(),
         }
     }
 }
 
 impl GenerationResult {
     /// Returns the number of sequences.
-    #[allow(dead_code)]
     pub fn num_sequences(&self) -> usize {
         self.sequences.len()
     }
 
     /// Returns true if this result has scores.
-    #[allow(dead_code)]
     pub fn has_scores(&self) -> bool {
         !self.scores.is_empty()
     }
+
+    /// Returns true if this result has logits.
+    pub fn has_logits(&self) -> bool {
+        !self.logits.is_empty()
+    }
+}
+
+/// Log probabilities over the vocabulary at each decoding step of a sequence.
+///
+/// The values are stored in row-major order with the shape `[num_steps, vocab_size]`.
+#[derive(Clone, Debug)]
+pub struct Logits {
+    /// The flattened values.
+    pub data: Vec<f32>,
+    /// The shape `[num_steps, vocab_size]` of the values.
+    pub shape: [usize; 2],
+}
+
+impl From<ffi::GenLogits> for Logits {
+    fn from(res: ffi::GenLogits) -> Self {
+        Self {
+            data: res.data,
+            shape: [res.num_steps, res.vocab_size],
+        }
+    }
+}
+
+impl Logits {
+    /// Returns the number of decoding steps.
+    pub fn num_steps(&self) -> usize {
+        self.shape[0]
+    }
+
+    /// Returns the values of the given step, if any.
+    pub fn step(&self, step: usize) -> Option<&[f32]> {
+        let vocab_size = self.shape[1];
+        self.data.get(step * vocab_size..(step + 1) * vocab_size)
+    }
+}
+
+/// The result for a single generation step.
+#[derive(Clone, Debug)]
+pub struct GenerationStepResult {
+    /// The decoding step.
+    pub step: usize,
+    /// The batch index.
+    pub batch_id: usize,
+    /// ID of the generated token.
+    pub token_id: usize,
+    /// Index of the hypothesis in the batch.
+    pub hypothesis_id: usize,
+    /// String value of the generated token.
+    pub token: String,
+    /// Log probability of the token (`None` if `return_log_prob` was disabled).
+    pub log_prob: Option<f32>,
+    /// Whether this step is the last decoding step for this batch.
+    pub is_last: bool,
+}
+
+impl From<ffi::GenerationStepResult> for GenerationStepResult {
+    fn from(res: ffi::GenerationStepResult) -> Self {
+        Self {
+            step: res.step,
+            batch_id: res.batch_id,
+            token_id: res.token_id,
+            hypothesis_id: res.hypothesis_id,
+            token: res.token,
+            log_prob: res.has_log_prob.then_some(res.log_prob),
+            is_last: res.is_last,
+        }
+    }
+}
+
+/// A step callback handed over to the C++ side.
+struct GenerationCallbackBox<'a>(Box<dyn FnMut(GenerationStepResult) -> bool + Send + 'a>);
+
+impl GenerationCallbackBox<'_> {
+    fn execute(&mut self, arg: ffi::GenerationStepResult) -> bool {
+        (self.0)(GenerationStepResult::from(arg))
+    }
+}
+
+#[inline]
+fn scoring_options_to_ffi(options: &ScoringOptions) -> ffi::GenScoringOptions {
+    ffi::GenScoringOptions {
+        max_input_length: options.max_input_length,
+        offset: options.offset,
+        max_batch_size: options.max_batch_size,
+        batch_type: match options.batch_type {
+            BatchType::Examples => ffi::GenerationBatchType::Examples,
+            BatchType::Tokens => ffi::GenerationBatchType::Tokens,
+        },
+    }
+}
+
+#[inline]
+fn ffi_end_token(end_token: Option<&EndToken>) -> ffi::GenEndToken {
+    match end_token {
+        None => ffi::GenEndToken {
+            kind: ffi::GenEndTokenKind::Default,
+            tokens: vec![],
+            ids: vec![],
+        },
+        Some(EndToken::Token(token)) => ffi::GenEndToken {
+            kind: ffi::GenEndTokenKind::Token,
+            tokens: vec![token.as_str()],
+            ids: vec![],
+        },
+        Some(EndToken::Tokens(tokens)) => ffi::GenEndToken {
+            kind: ffi::GenEndTokenKind::Tokens,
+            tokens: tokens.iter().map(|s| s.as_str()).collect(),
+            ids: vec![],
+        },
+        Some(EndToken::Ids(ids)) => ffi::GenEndToken {
+            kind: ffi::GenEndTokenKind::Ids,
+            tokens: vec![],
+            ids: ids.clone(),
+        },
+    }
 }
 
 #[inline]
@@ -313,3 +902,60 @@ fn vec_ffi_vecstr<T: AsRef<str>>(src: &[Vec<T>]) -> Vec<ffi::GenVecStr> {
         })
         .collect()
 }
+
+#[inline]
+fn vec_ffi_vecusize(src: &[Vec<usize>]) -> Vec<ffi::GenVecUSize> {
+    src.iter()
+        .map(|v| ffi::GenVecUSize { v: v.clone() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_rejects_invalid_options() {
+        assert!(GenerationOptions::builder().build().is_ok());
+        for builder in [
+            GenerationOptions::builder().beam_size(0),
+            GenerationOptions::builder().num_hypotheses(0),
+            GenerationOptions::builder().sampling_topp(0.),
+            GenerationOptions::builder().sampling_topp(f32::NAN),
+            GenerationOptions::builder().min_length(16).max_length(8),
+        ] {
+            assert!(matches!(builder.build(), Err(Error::InvalidOptions(_))));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn options_round_trip() {
+        let options = GenerationOptions::builder()
+            .max_length(64)
+            .sampling_topk(10)
+            .end_token(EndToken::Tokens(vec!["</s>".to_string()]))
+            .batch_type(BatchType::Tokens)
+            .build()
+            .unwrap();
+        let json = serde_json::to_value(&options).unwrap();
+        let decoded =
+            serde_json::from_value::<GenerationOptions<String, String>>(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        assert_eq!(decoded.end_token, options.end_token);
+
+        // The missing fields take their default values.
+        let partial = serde_json::from_str::<GenerationOptions<String, String>>(
+            r#"{"max_length": 32, "end_token": "<eot>", "batch_type": "tokens"}"#,
+        )
+        .unwrap();
+        assert_eq!(partial.max_length, 32);
+        assert_eq!(
+            partial.end_token,
+            Some(EndToken::Token("<eot>".to_string()))
+        );
+        assert_eq!(partial.batch_type, BatchType::Tokens);
+        assert_eq!(partial.beam_size, 1);
+        assert!(partial.include_prompt_in_result);
+    }
+}
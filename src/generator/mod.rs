@@ -1,79 +1,752 @@
-use std::path::Path;
+use std::borrow::Cow;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Instant;
 
-use anyhow::{anyhow, Result};
-use tokenizers::{Decoder, EncodeInput, Tokenizer};
-use crate::config::{Config, Device};
-use self::generator::GenerationOptions;
+use tokenizers::{EncodeInput, Encoding, Tokenizer};
+use crate::config::{Config, Device, EndToken};
+use crate::error::{Error, Result};
+use crate::metrics::{Metrics, ReplicaStats, Stats};
+use crate::model::Architecture;
+use crate::scoring::{ScoringOptions, ScoringResult};
+use crate::source::ModelSource;
+pub use self::batching::{BatchingGenerator, BatchingOptions};
+pub use self::chat::{ChatMessage, ChatTemplate};
+pub use self::generator::{
+    GenerationOptions, GenerationOptionsBuilder, GenerationStepResult, Logits,
+};
+pub use self::prompt_cache::{PromptCache, PromptHandle};
+use self::generator::{GenerationResult, GenerationStream};
+use self::prompt_cache::Prompts;
 
-mod generator;
+mod batching;
+mod chat;
+/// The bridge to the CTranslate2 generator, taking tokens or token IDs without a tokenizer.
+pub mod generator;
+mod prompt_cache;
 
 const TOKENIZER_FILENAME: &str = "tokenizer.json";
+const TOKENIZER_CONFIG_FILENAME: &str = "tokenizer_config.json";
 
+/// A text generator with a tokenizer.
+///
+/// The generator is `Send` and `Sync`: a single instance can be shared between threads, e.g.
+/// in an `Arc`, and the concurrent calls are dispatched to the replicas of the model.
 pub struct Generator {
     generator: self::generator::Generator,
     tokenizer: Tokenizer,
+    chat_template: Option<ChatTemplate>,
+    metrics: Metrics,
+    prompts: Prompts,
 }
 
 impl Generator {
     /// Initializes the generator and tokenizer.
-    pub fn new<T: AsRef<Path>>(path: T, device: Device, config: Config) -> Result<Generator> {
-        Generator::with_tokenizer(
-            &path,
-            device,
-            config,
-            Tokenizer::from_file(path.as_ref().join(TOKENIZER_FILENAME))
-                .map_err(|err| anyhow!("failed to load a tokenizer: {err}"))?,
-        )
-    }
-
-    /// Initializes the generator with the given tokenizer.
-    pub fn with_tokenizer<T: AsRef<Path>>(
-        path: T,
+    ///
+    /// The model is loaded from a directory path or any other [`ModelSource`], and the
+    /// tokenizer from its `tokenizer.json` file.
+    pub fn new<T: Into<ModelSource>>(
+        source: T,
+        device: Device,
+        config: Config,
+    ) -> Result<Generator> {
+        let source = source.into().check(Architecture::DecoderOnly)?;
+        let tokenizer = source.read_tokenizer(TOKENIZER_FILENAME)?;
+        Generator::with_tokenizer(source, device, config, tokenizer)
+    }
+
+    /// Initializes the generator with the given tokenizer, e.g. loaded from memory with
+    /// [`Tokenizer::from_bytes`].
+    ///
+    /// The chat template is read from the `tokenizer_config.json` file of the model, if any.
+    pub fn with_tokenizer<T: Into<ModelSource>>(
+        source: T,
         device: Device,
         config: Config,
         tokenizer: Tokenizer,
     ) -> Result<Generator> {
+        let source = source.into().check(Architecture::DecoderOnly)?;
+        let chat_template = match source.read_file(TOKENIZER_CONFIG_FILENAME)? {
+            Some(tokenizer_config) => {
+                let tokenizer_config =
+                    serde_json::from_slice(&tokenizer_config).map_err(|err| {
+                        Error::Tokenizer(format!(
+                            "failed to parse {TOKENIZER_CONFIG_FILENAME}: {err}"
+                        ))
+                    })?;
+                ChatTemplate::from_config(&tokenizer_config)?
+            }
+            None => None,
+        };
         Ok(Generator {
-            generator: self::generator::Generator::new(path.as_ref().to_str().unwrap(), device, config)?,
+            generator: self::generator::Generator::new(&source, device, config)?,
             tokenizer,
+            chat_template,
+            metrics: Metrics::default(),
+            prompts: Prompts::default(),
         })
     }
 
+    /// Returns the tokenizer.
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    /// Returns the chat template of the model, if any.
+    pub fn chat_template(&self) -> Option<&ChatTemplate> {
+        self.chat_template.as_ref()
+    }
+
+    /// Returns the metrics of the requests processed by this generator.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Returns the state of the replica pool.
+    pub fn replica_stats(&self) -> ReplicaStats {
+        ReplicaStats {
+            num_replicas: self.generator.num_replicas(),
+            num_queued_batches: self.generator.num_queued_batches(),
+            num_active_batches: self.generator.num_active_batches(),
+        }
+    }
+
+    /// Returns the state of the replica pool with a snapshot of the metrics.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            replicas: self.replica_stats(),
+            metrics: self.metrics.snapshot(),
+        }
+    }
+
+    /// Unloads the model to free the device memory, keeping the tokenizer and configuration.
+    ///
+    /// If `to_cpu` is set, the model is moved to the CPU memory so that
+    /// [`load_model`](Self::load_model) is faster than reading the model directory again.
+    /// Returns false if some batches are still queued or running, in which case the model is
+    /// kept loaded. The requests made while the model is unloaded fail with
+    /// [`Error::ModelUnloaded`].
+    ///
+    /// The prompts of the [`prompt_cache`](Self::prompt_cache) must be warmed again once the
+    /// model is loaded.
+    pub fn unload_model(&self, to_cpu: bool) -> Result<bool> {
+        let unloaded = self.generator.unload_model(to_cpu)?;
+        if unloaded {
+            self.prompts.reset();
+        }
+        Ok(unloaded)
+    }
+
+    /// Loads the model again after [`unload_model`](Self::unload_model), on the device and
+    /// with the configuration it was initialized with.
+    pub fn load_model(&self) -> Result<()> {
+        self.generator.load_model()
+    }
+
+    /// Returns true if the model is loaded.
+    pub fn model_is_loaded(&self) -> bool {
+        self.generator.model_is_loaded()
+    }
+
+    /// Returns the named system prompts whose model state is cached by the replicas.
+    pub fn prompt_cache(&self) -> PromptCache<'_> {
+        PromptCache { generator: self }
+    }
+
     /// Generate texts with the given prompts.
     pub fn generate_batch<'a, T, U, V>(
         &self,
         prompts: Vec<T>,
         options: &GenerationOptions<U, V>,
-    ) -> Result<Vec<(Vec<String>, Vec<f32>)>>
+    ) -> Result<Vec<GeneratedText>>
     where
         T: Into<EncodeInput<'a>>,
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        let ids = self.encode_ids(prompts)?;
+        self.generate_batch_ids(&ids, options)
+    }
+
+    /// Generate texts with the given prompts already encoded to token IDs.
+    ///
+    /// The IDs are passed to the model as is, so they must be the IDs of the tokenizer of the
+    /// model, without the special tokens the model does not expect.
+    pub fn generate_batch_ids<U, V>(
+        &self,
+        ids: &[Vec<usize>],
+        options: &GenerationOptions<U, V>,
+    ) -> Result<Vec<GeneratedText>>
+    where
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        self.generate_ids(ids, options, false)
+    }
+
+    /// Generate the assistant replies to the given conversations.
+    ///
+    /// The conversations are rendered with the chat template of the model. Unless
+    /// `options.end_token` is set, the generation stops on the EOS token of the tokenizer
+    /// config. The results only contain the replies, without the special tokens.
+    pub fn generate_chat<U, V>(
+        &self,
+        conversations: &[Vec<ChatMessage>],
+        options: &GenerationOptions<U, V>,
+    ) -> Result<Vec<GeneratedText>>
+    where
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        let mut options = options.clone();
+        let ids = self.chat_prompts(conversations, &mut options)?;
+        self.generate_ids(&ids, &options, true)
+    }
+
+    /// Generate texts with the given prompts without blocking the calling thread.
+    pub async fn generate_batch_async<'a, T, U, V>(
+        &self,
+        prompts: Vec<T>,
+        options: &GenerationOptions<U, V>,
+    ) -> Result<Vec<GeneratedText>>
+    where
+        T: Into<EncodeInput<'a>>,
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        let ids = self.encode_ids(prompts)?;
+        self.generate_ids_async(&ids, options, false).await
+    }
+
+    /// Generate the assistant replies to the given conversations without blocking the calling
+    /// thread, like [`generate_chat`](Self::generate_chat).
+    pub async fn generate_chat_async<U, V>(
+        &self,
+        conversations: &[Vec<ChatMessage>],
+        options: &GenerationOptions<U, V>,
+    ) -> Result<Vec<GeneratedText>>
+    where
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        let mut options = options.clone();
+        let ids = self.chat_prompts(conversations, &mut options)?;
+        self.generate_ids_async(&ids, &options, true).await
+    }
+
+    /// Generate texts with the given prompts, calling `callback` for each generated token.
+    ///
+    /// The callback is only invoked in greedy search (`beam_size` is 1). Returning `true` from
+    /// the callback stops the generation of the corresponding prompt early.
+    pub fn generate_batch_with_callback<'a, T, U, V, F>(
+        &self,
+        prompts: Vec<T>,
+        options: &GenerationOptions<U, V>,
+        callback: F,
+    ) -> Result<Vec<GeneratedText>>
+    where
+        T: Into<EncodeInput<'a>>,
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+        F: FnMut(GenerationStepResult) -> bool + Send,
+    {
+        let started = Instant::now();
+        let ids = self.encode_ids(prompts)?;
+        let res = self
+            .prepare_options(options)
+            .and_then(|options| {
+                self.generator
+                    .generate_batch_ids_with_callback(&ids, &options, callback)
+            })
+            .and_then(|output| self.decode(output, false));
+        self.observe(started, &ids, options, res)
+    }
+
+    /// Generate a text with the given prompt, returning an iterator which yields the decoded
+    /// text incrementally while the tokens are generated.
+    ///
+    /// Tokens are only streamed in greedy search, so [`Error::InvalidOptions`] is returned if
+    /// `beam_size` is not 1. Dropping the iterator stops the generation, and the request is
+    /// recorded as cancelled in the metrics.
+    pub fn generate_tokens<'a, T, U, V>(
+        &self,
+        prompt: T,
+        options: &GenerationOptions<U, V>,
+    ) -> Result<TokenStream<'_>>
+    where
+        T: Into<EncodeInput<'a>>,
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        if options.beam_size != 1 {
+            return Err(Error::InvalidOptions(format!(
+                "tokens are only streamed with beam_size 1, got {}",
+                options.beam_size
+            )));
+        }
+        let started = Instant::now();
+        let ids = self.encode_ids(vec![prompt])?;
+        let (sender, receiver) = channel();
+        let stream = self
+            .prepare_options(options)
+            .and_then(|options| {
+                self.generator
+                    .generate_stream_ids(&ids, &options, move |step| sender.send(step).is_err())
+            })
+            .inspect_err(|_| self.metrics.record_failure())?;
+
+        Ok(TokenStream {
+            tokenizer: &self.tokenizer,
+            metrics: &self.metrics,
+            started,
+            input_tokens: ids[0].len(),
+            finished: false,
+            stream: Some(stream),
+            receiver,
+            decoder: IncrementalDecoder::default(),
+        })
+    }
+
+    /// Scores the given texts.
+    ///
+    /// The texts are encoded with the special tokens of the tokenizer, such as the BOS token,
+    /// since the model needs them to score the first tokens.
+    pub fn score_batch<'a, T>(
+        &self,
+        texts: Vec<T>,
+        options: &ScoringOptions,
+    ) -> Result<Vec<ScoringResult>>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        let started = Instant::now();
+        let tokens = self
+            .encode(texts, true)?
+            .iter()
+            .map(|r| r.get_tokens().to_vec())
+            .collect::<Vec<_>>();
+        let input_tokens = tokens.iter().map(|t| t.len()).sum();
+        let res = self.generator.score_batch(&tokens, options);
+        self.metrics
+            .observe(started, tokens.len(), input_tokens, res, |_| 0)
+    }
+
+    /// Generates from the given token IDs and decodes the results, recording the request in
+    /// the metrics.
+    fn generate_ids<U, V>(
+        &self,
+        ids: &[Vec<usize>],
+        options: &GenerationOptions<U, V>,
+        skip_special_tokens: bool,
+    ) -> Result<Vec<GeneratedText>>
+    where
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        let started = Instant::now();
+        let res = self
+            .prepare_options(options)
+            .and_then(|options| self.generator.generate_batch_ids(ids, &options))
+            .and_then(|output| self.decode(output, skip_special_tokens));
+        self.observe(started, ids, options, res)
+    }
+
+    /// Like [`generate_ids`](Self::generate_ids), without blocking the calling thread.
+    async fn generate_ids_async<U, V>(
+        &self,
+        ids: &[Vec<usize>],
+        options: &GenerationOptions<U, V>,
+        skip_special_tokens: bool,
+    ) -> Result<Vec<GeneratedText>>
+    where
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        let started = Instant::now();
+        let future = self
+            .prepare_options(options)
+            .and_then(|options| self.generator.generate_batch_ids_async(ids, &options));
+        let res = match future {
+            Ok(future) => future
+                .await
+                .and_then(|output| self.decode(output, skip_special_tokens)),
+            Err(err) => Err(err),
+        };
+        self.observe(started, ids, options, res)
+    }
+
+    /// Renders the conversations with the chat template and encodes them, setting the options
+    /// to stop on the EOS token of the template unless an end token is set.
+    fn chat_prompts<U, V>(
+        &self,
+        conversations: &[Vec<ChatMessage>],
+        options: &mut GenerationOptions<U, V>,
+    ) -> Result<Vec<Vec<usize>>>
+    where
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        let Some(template) = &self.chat_template else {
+            return Err(Error::InvalidOptions(
+                "the model has no chat template".to_string(),
+            ));
+        };
+        let prompts = conversations
+            .iter()
+            .map(|messages| template.render(messages))
+            .collect::<Result<Vec<_>>>()?;
+
+        options.include_prompt_in_result = false;
+        if options.end_token.is_none() {
+            options.end_token = template.eos_token.clone().map(EndToken::Token);
+        }
+        self.encode_ids(prompts)
+    }
+
+    /// Records a generation request in the metrics.
+    fn observe<U, V>(
+        &self,
+        started: Instant,
+        ids: &[Vec<usize>],
+        options: &GenerationOptions<U, V>,
+        res: Result<Vec<GeneratedText>>,
+    ) -> Result<Vec<GeneratedText>>
+    where
         U: AsRef<str>,
         V: AsRef<str>,
     {
-        let tokens = prompts
+        let input_tokens = ids.iter().map(|p| p.len()).sum();
+        self.metrics
+            .observe(started, ids.len(), input_tokens, res, |results| {
+                results
+                    .iter()
+                    .zip(ids)
+                    .flat_map(|(r, prompt)| {
+                        // The hypotheses start with the prompt when it is included.
+                        let prompt_len = if options.include_prompt_in_result {
+                            prompt.len()
+                        } else {
+                            0
+                        };
+                        r.ids
+                            .iter()
+                            .map(move |h| h.len().saturating_sub(prompt_len))
+                    })
+                    .sum()
+            })
+    }
+
+    /// Validates the options and resolves end tokens given as strings to their IDs with the
+    /// tokenizer, so that they match the tokens the prompts are encoded with.
+    fn prepare_options<'b, U, V>(
+        &self,
+        options: &'b GenerationOptions<U, V>,
+    ) -> Result<Cow<'b, GenerationOptions<U, V>>>
+    where
+        U: AsRef<str> + Clone,
+        V: AsRef<str> + Clone,
+    {
+        options.validate()?;
+        let tokens = match &options.end_token {
+            Some(EndToken::Token(token)) => vec![token],
+            Some(EndToken::Tokens(tokens)) => tokens.iter().collect(),
+            _ => return Ok(Cow::Borrowed(options)),
+        };
+        let ids = tokens
+            .into_iter()
+            .map(|token| {
+                self.tokenizer
+                    .token_to_id(token)
+                    .map(|id| id as usize)
+                    .ok_or_else(|| Error::InvalidOptions(format!("unknown end token: {token}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut options = options.clone();
+        options.end_token = Some(EndToken::Ids(ids));
+        Ok(Cow::Owned(options))
+    }
+
+    fn encode<'a, T>(&self, prompts: Vec<T>, add_special_tokens: bool) -> Result<Vec<Encoding>>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        prompts
             .into_iter()
             .map(|s| {
                 self.tokenizer
-                    .encode(s, false)
-                    .map(|r| r.get_tokens().to_vec())
-                    .map_err(|err| anyhow!("failed to encode the given input: {err}"))
+                    .encode(s, add_special_tokens)
+                    .map_err(|err| Error::Encode(err.to_string()))
             })
-            .collect::<Result<Vec<Vec<String>>>>()?;
-
-        let output = self.generator.generate_batch(&tokens, options)?;
-
-        let decoder = self.tokenizer.get_decoder().unwrap();
-        let mut res = Vec::new();
-        for r in output.into_iter() {
-            let sequence = r
-                .sequences
-                .into_iter()
-                .map(|seq| decoder.decode(seq))
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(|err| anyhow!("failed to decode: {err}"))?;
-            let scores = r.scores;
-            res.push((sequence, scores))
+            .collect()
+    }
+
+    /// Encodes the given prompts to token IDs, which are passed to the model as is so that
+    /// they do not depend on how the tokens are spelled in the model vocabulary.
+    fn encode_ids<'a, T>(&self, prompts: Vec<T>) -> Result<Vec<Vec<usize>>>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        Ok(self
+            .encode(prompts, false)?
+            .iter()
+            .map(|r| r.get_ids().iter().map(|id| *id as usize).collect())
+            .collect())
+    }
+
+    fn decode(
+        &self,
+        output: Vec<GenerationResult>,
+        skip_special_tokens: bool,
+    ) -> Result<Vec<GeneratedText>> {
+        output
+            .into_iter()
+            .map(|r| {
+                let texts = r
+                    .sequences_ids
+                    .iter()
+                    .map(|ids| {
+                        let ids = ids.iter().map(|id| *id as u32).collect::<Vec<_>>();
+                        self.tokenizer.decode(&ids, skip_special_tokens)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| Error::Decode(err.to_string()))?;
+                Ok(GeneratedText {
+                    texts,
+                    tokens: r.sequences,
+                    ids: r.sequences_ids,
+                    scores: r.scores,
+                    logits: r.logits,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The hypotheses generated from a prompt.
+#[derive(Clone, Debug)]
+pub struct GeneratedText {
+    /// Decoded text of each hypothesis.
+    pub texts: Vec<String>,
+    /// Tokens of each hypothesis.
+    pub tokens: Vec<Vec<String>>,
+    /// Token IDs of each hypothesis.
+    pub ids: Vec<Vec<usize>>,
+    /// Score of each hypothesis (empty if `return_scores` was disabled).
+    pub scores: Vec<f32>,
+    /// Log probabilities over the vocabulary at each step of each hypothesis (empty if
+    /// `return_logits_vocab` was disabled).
+    pub logits: Vec<Logits>,
+}
+
+impl GeneratedText {
+    /// Returns the text of the best hypothesis.
+    pub fn text(&self) -> &str {
+        self.texts.first().map_or("", |s| s.as_str())
+    }
+
+    /// Returns the score of the best hypothesis, if available.
+    pub fn score(&self) -> Option<f32> {
+        self.scores.first().copied()
+    }
+
+    /// Returns the number of hypotheses.
+    pub fn num_hypotheses(&self) -> usize {
+        self.texts.len()
+    }
+}
+
+/// An iterator over pieces of a text being generated, created by [`Generator::generate_tokens`].
+pub struct TokenStream<'a> {
+    tokenizer: &'a Tokenizer,
+    metrics: &'a Metrics,
+    started: Instant,
+    input_tokens: usize,
+    /// Whether the request has been recorded in the metrics.
+    finished: bool,
+    stream: Option<GenerationStream>,
+    receiver: Receiver<GenerationStepResult>,
+    decoder: IncrementalDecoder,
+}
+
+impl TokenStream<'_> {
+    /// Records the request in the metrics, once.
+    fn finish(&mut self, ok: bool) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        if ok {
+            self.metrics
+                .record(self.started, 1, self.input_tokens, self.decoder.ids.len());
+        } else {
+            self.metrics.record_failure();
         }
-        Ok(res)
+    }
+}
+
+impl Iterator for TokenStream<'_> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let stream = self.stream.take()?;
+            let step = match self.receiver.recv() {
+                Ok(step) => step,
+                // The generation finished without reporting a last step, which happens when
+                // it failed.
+                Err(_) => {
+                    let err = stream.wait().err();
+                    self.finish(err.is_none());
+                    return err.map(Err);
+                }
+            };
+
+            if self.decoder.ids.is_empty() {
+                self.metrics.record_first_token(self.started);
+            }
+            let piece = match self
+                .decoder
+                .push(self.tokenizer, step.token_id as u32, step.is_last)
+            {
+                Ok(piece) => piece,
+                Err(err) => {
+                    self.finish(false);
+                    return Some(Err(err));
+                }
+            };
+
+            if step.is_last {
+                let res = stream.wait();
+                self.finish(res.is_ok());
+                if let Err(err) = res {
+                    return Some(Err(err));
+                }
+            } else {
+                self.stream = Some(stream);
+            }
+
+            match piece {
+                Some(piece) => return Some(Ok(piece)),
+                None if step.is_last => return None,
+                None => continue,
+            }
+        }
+    }
+}
+
+impl Drop for TokenStream<'_> {
+    fn drop(&mut self) {
+        // The generation was stopped early by dropping the iterator.
+        if !self.finished {
+            self.finished = true;
+            self.metrics.record_cancellation();
+        }
+    }
+}
+
+/// Decodes the tokens of a stream into pieces of text.
+#[derive(Default)]
+struct IncrementalDecoder {
+    /// Token IDs generated so far.
+    ids: Vec<u32>,
+    /// Index of the first token decoded with the new tokens, as context for their spacing.
+    prefix_offset: usize,
+    /// Index of the first token whose text has not been returned yet.
+    read_offset: usize,
+}
+
+impl IncrementalDecoder {
+    /// Adds a token, returning the text of the tokens not returned yet if it is complete.
+    ///
+    /// The new tokens are decoded after the tokens since `prefix_offset`, whose text is then
+    /// removed. Decoding this window rather than the whole text keeps the earlier pieces from
+    /// changing, e.g. when a tokenizer strips the leading space of the text.
+    fn push(&mut self, tokenizer: &Tokenizer, id: u32, is_last: bool) -> Result<Option<String>> {
+        self.ids.push(id);
+        let decode = |ids: &[u32]| {
+            tokenizer
+                .decode(ids, true)
+                .map_err(|err| Error::Decode(err.to_string()))
+        };
+        let prefix = decode(&self.ids[self.prefix_offset..self.read_offset])?;
+        let text = decode(&self.ids[self.prefix_offset..])?;
+        // Wait for more tokens while the text ends with an incomplete character.
+        if text.len() <= prefix.len() || (text.ends_with('\u{FFFD}') && !is_last) {
+            return Ok(None);
+        }
+        let piece = match text.strip_prefix(prefix.as_str()) {
+            Some(piece) => piece,
+            None => text.get(prefix.len()..).unwrap_or(&text),
+        };
+        let piece = piece.to_string();
+        self.prefix_offset = self.read_offset;
+        self.read_offset = self.ids.len();
+        Ok(Some(piece))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizers::decoders::byte_fallback::ByteFallback;
+    use tokenizers::decoders::metaspace::Metaspace;
+    use tokenizers::decoders::sequence::Sequence;
+    use tokenizers::models::wordlevel::WordLevel;
+
+    use super::*;
+
+    /// A SentencePiece-like tokenizer, which strips the leading space of a decoded text.
+    fn tokenizer() -> Tokenizer {
+        let vocab = ["<unk>", "▁Hello", "▁world", "!", "▁caf", "<0xC3>", "<0xA9>"]
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), id as u32))
+            .collect();
+        let mut tokenizer = Tokenizer::new(WordLevel::builder().vocab(vocab).build().unwrap());
+        tokenizer.with_decoder(Sequence::new(vec![
+            ByteFallback::default().into(),
+            Metaspace::default().into(),
+        ]));
+        tokenizer
+    }
+
+    /// Pushes the given tokens one by one, returning the pieces decoded after each of them.
+    fn decode_pieces(ids: &[u32]) -> Vec<Option<String>> {
+        let tokenizer = tokenizer();
+        let mut decoder = IncrementalDecoder::default();
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| decoder.push(&tokenizer, *id, i == ids.len() - 1).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn pieces_keep_the_spaces_between_tokens() {
+        assert_eq!(
+            decode_pieces(&[1, 2, 3]),
+            vec![
+                Some("Hello".to_string()),
+                Some(" world".to_string()),
+                Some("!".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn incomplete_characters_are_held_back() {
+        assert_eq!(
+            decode_pieces(&[4, 5, 6, 3]),
+            vec![
+                Some("caf".to_string()),
+                None,
+                Some("é".to_string()),
+                Some("!".to_string()),
+            ]
+        );
+
+        // The last token is returned even if its character is incomplete.
+        assert_eq!(
+            decode_pieces(&[4, 5]),
+            vec![Some("caf".to_string()), Some("\u{FFFD}".to_string())]
+        );
     }
 }
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::{Error, Result};
+
+use super::{GenerationOptions, Generator};
+
+/// A system prompt registered in the [`PromptCache`] of a generator.
+///
+/// The handle is passed to the generation calls through [`PromptHandle::apply`], which sets
+/// the tokens of the prompt as the static prompt of the options. It stays usable after the
+/// prompt is evicted, in which case the model state is computed again for each request.
+#[derive(Clone, Debug)]
+pub struct PromptHandle {
+    inner: Arc<Prompt>,
+}
+
+#[derive(Debug)]
+struct Prompt {
+    name: String,
+    text: String,
+    tokens: Vec<String>,
+    ids: Vec<usize>,
+}
+
+impl PromptHandle {
+    /// Returns the name the prompt is registered with.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Returns the text of the prompt.
+    pub fn text(&self) -> &str {
+        &self.inner.text
+    }
+
+    /// Returns the tokens of the prompt.
+    pub fn tokens(&self) -> &[String] {
+        &self.inner.tokens
+    }
+
+    /// Returns the options with the prompt as the static prompt, cached by the model.
+    pub fn apply<U>(&self, options: &GenerationOptions<U, String>) -> GenerationOptions<U, String>
+    where
+        U: AsRef<str> + Clone,
+    {
+        let mut options = options.clone();
+        options.static_prompt = self.inner.tokens.clone();
+        options.cache_static_prompt = true;
+        options
+    }
+}
+
+/// The registered prompts of a generator.
+#[derive(Default)]
+pub(super) struct Prompts {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+struct Entry {
+    handle: PromptHandle,
+    /// Whether [`PromptCache::warm`] completed for the prompt since the model was loaded.
+    warm: bool,
+}
+
+impl Prompts {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        // The entries are consistent even if a thread panicked while holding the lock.
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Marks every prompt as cold, e.g. after the model was unloaded.
+    pub(super) fn reset(&self) {
+        for entry in self.lock().values_mut() {
+            entry.warm = false;
+        }
+    }
+}
+
+/// Named system prompts of a [`Generator`], whose model state is cached by the replicas
+/// running them.
+///
+/// Created by [`Generator::prompt_cache`].
+///
+/// ```no_run
+/// # fn main() -> ctrans2::Result<()> {
+/// use ctrans2::{Config, Device, GenerationOptions, Generator};
+///
+/// let generator = Generator::new("/path/to/model", Device::CPU, Config::default())?;
+/// let prompts = generator.prompt_cache();
+/// let support = prompts.register("support", "You are a helpful support agent.\n")?;
+/// // Compute the state of the prompts before serving the first requests.
+/// prompts.warm_all()?;
+///
+/// let options = support.apply(&GenerationOptions::default());
+/// let res = generator.generate_batch(vec!["How do I reset my password?"], &options)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// CTranslate2 cannot drop the state of a single prompt, so the replicas keep the states of
+/// the evicted prompts until the model is unloaded.
+pub struct PromptCache<'a> {
+    pub(super) generator: &'a Generator,
+}
+
+impl PromptCache<'_> {
+    /// Registers a prompt, replacing the prompt previously registered with the same name.
+    ///
+    /// The prompt is tokenized without the special tokens, like the prompts of the requests.
+    pub fn register<T: Into<String>, U: Into<String>>(
+        &self,
+        name: T,
+        text: U,
+    ) -> Result<PromptHandle> {
+        let name = name.into();
+        let text = text.into();
+        let encoding = self
+            .generator
+            .tokenizer
+            .encode(text.as_str(), false)
+            .map_err(|err| Error::Encode(err.to_string()))?;
+        if encoding.get_ids().is_empty() {
+            return Err(Error::InvalidOptions(format!("the prompt {name} is empty")));
+        }
+        let handle = PromptHandle {
+            inner: Arc::new(Prompt {
+                name: name.clone(),
+                text,
+                tokens: encoding.get_tokens().to_vec(),
+                ids: encoding.get_ids().iter().map(|id| *id as usize).collect(),
+            }),
+        };
+        self.generator.prompts.lock().insert(
+            name,
+            Entry {
+                handle: handle.clone(),
+                warm: false,
+            },
+        );
+        Ok(handle)
+    }
+
+    /// Returns the prompt registered with the given name.
+    pub fn get(&self, name: &str) -> Option<PromptHandle> {
+        self.generator
+            .prompts
+            .lock()
+            .get(name)
+            .map(|entry| entry.handle.clone())
+    }
+
+    /// Returns the registered prompts, sorted by name.
+    pub fn list(&self) -> Vec<PromptHandle> {
+        let mut handles = self
+            .generator
+            .prompts
+            .lock()
+            .values()
+            .map(|entry| entry.handle.clone())
+            .collect::<Vec<_>>();
+        handles.sort_by(|a, b| a.name().cmp(b.name()));
+        handles
+    }
+
+    /// Returns true if [`warm`](Self::warm) completed for the given prompt since it was
+    /// registered and the model was loaded.
+    ///
+    /// This does not guarantee that every replica has cached the state of the prompt.
+    pub fn is_warm(&self, name: &str) -> bool {
+        self.generator
+            .prompts
+            .lock()
+            .get(name)
+            .is_some_and(|entry| entry.warm)
+    }
+
+    /// Computes the model state after the given prompt so that the requests using it do not
+    /// pay for it.
+    ///
+    /// One batch is submitted per replica, but CTranslate2 does not choose which replica runs
+    /// a batch, so a busy replica may still compute the state on its first request using the
+    /// prompt. The requests are not recorded in the metrics.
+    pub fn warm(&self, prompt: &PromptHandle) -> Result<()> {
+        let options = prompt.apply(&GenerationOptions {
+            max_length: 1,
+            include_prompt_in_result: false,
+            ..Default::default()
+        });
+        // Any start token works since the generated tokens are discarded.
+        let start_ids = vec![vec![*prompt.inner.ids.last().unwrap()]];
+        let generator = &self.generator.generator;
+        let futures = (0..generator.num_replicas().max(1))
+            .map(|_| generator.generate_batch_ids_async(&start_ids, &options))
+            .collect::<Result<Vec<_>>>()?;
+        for future in futures {
+            future.wait()?;
+        }
+
+        if let Some(entry) = self.generator.prompts.lock().get_mut(prompt.name()) {
+            if Arc::ptr_eq(&entry.handle.inner, &prompt.inner) {
+                entry.warm = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Warms the registered prompts which are not warm yet.
+    pub fn warm_all(&self) -> Result<()> {
+        let cold = self
+            .generator
+            .prompts
+            .lock()
+            .values()
+            .filter(|entry| !entry.warm)
+            .map(|entry| entry.handle.clone())
+            .collect::<Vec<_>>();
+        cold.iter().try_for_each(|prompt| self.warm(prompt))
+    }
+
+    /// Removes the prompt registered with the given name, returning it if it was registered.
+    pub fn evict(&self, name: &str) -> Option<PromptHandle> {
+        self.generator
+            .prompts
+            .lock()
+            .remove(name)
+            .map(|entry| entry.handle)
+    }
+
+    /// Removes all the prompts.
+    pub fn clear(&self) {
+        self.generator.prompts.lock().clear();
+    }
+}
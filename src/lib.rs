@@ -0,0 +1,42 @@
+// lib.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Rust bindings for CTranslate2.
+
+pub mod config;
+pub mod device;
+pub mod encoder;
+pub mod error;
+pub mod future;
+pub mod generator;
+pub mod metrics;
+pub mod model;
+pub mod registry;
+pub mod scoring;
+pub mod source;
+pub mod translator;
+pub mod whisper;
+
+pub use crate::config::{BatchType, ComputeType, Config, ConfigBuilder, Device, EndToken};
+pub use crate::encoder::{Encoder, EncoderForwardOutput, Pooling};
+pub use crate::error::{Error, Result};
+pub use crate::future::BatchFuture;
+pub use crate::generator::{
+    BatchingGenerator, BatchingOptions, ChatMessage, GeneratedText, GenerationOptions,
+    GenerationOptionsBuilder, GenerationStepResult, Generator, Logits, PromptCache, PromptHandle,
+    TokenStream,
+};
+pub use crate::metrics::{Metrics, MetricsSnapshot, ReplicaStats, Stats};
+pub use crate::model::{Architecture, ModelDir, ModelDirError, ModelInfo};
+pub use crate::registry::{LoadableModel, ModelRegistry};
+pub use crate::scoring::{ScoringOptions, ScoringResult};
+pub use crate::source::{ModelReader, ModelSource};
+pub use crate::translator::{
+    TranslatedText, TranslationOptions, TranslationOptionsBuilder, Translator,
+};
+pub use crate::whisper::{TranscribedText, Whisper, WhisperOptions, WordTimestamp};
@@ -0,0 +1,506 @@
+// metrics.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Runtime statistics of the models.
+//!
+//! Each [`Generator`](crate::Generator) and [`Translator`](crate::Translator) records its
+//! requests in a [`Metrics`] object. [`Stats`] is a snapshot of these metrics together with the
+//! state of the replica pool, which can be written in the Prometheus text format:
+//!
+//! ```no_run
+//! # fn main() -> ctrans2::Result<()> {
+//! use ctrans2::{Config, Device, Generator};
+//! use ctrans2::metrics;
+//!
+//! let generator = Generator::new("/path/to/model", Device::CPU, Config::default())?;
+//! let stats = generator.stats();
+//! print!("{}", metrics::encode_prometheus(&[(&[("model", "gpt2")], &stats)]));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+
+/// Upper bounds of the buckets of the latency histograms, in seconds.
+pub const LATENCY_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30., 60., 120.,
+];
+
+/// Counters of the requests processed by a model.
+///
+/// The counters are updated atomically, so the requests can be recorded from any thread.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: AtomicU64,
+    failed_requests: AtomicU64,
+    cancelled_requests: AtomicU64,
+    examples: AtomicU64,
+    input_tokens: AtomicU64,
+    output_tokens: AtomicU64,
+    latency: Histogram,
+    time_to_first_token: Histogram,
+}
+
+impl Metrics {
+    /// Records a request started at `started`, returning its result as is.
+    ///
+    /// `output_tokens` counts the generated tokens of a successful result.
+    pub(crate) fn observe<T, F>(
+        &self,
+        started: Instant,
+        examples: usize,
+        input_tokens: usize,
+        res: Result<T>,
+        output_tokens: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(&T) -> usize,
+    {
+        match &res {
+            Ok(output) => self.record(started, examples, input_tokens, output_tokens(output)),
+            Err(_) => self.record_failure(),
+        }
+        res
+    }
+
+    /// Records a successful request started at `started`.
+    pub(crate) fn record(
+        &self,
+        started: Instant,
+        examples: usize,
+        input_tokens: usize,
+        output_tokens: usize,
+    ) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.examples.fetch_add(examples as u64, Ordering::Relaxed);
+        self.input_tokens
+            .fetch_add(input_tokens as u64, Ordering::Relaxed);
+        self.output_tokens
+            .fetch_add(output_tokens as u64, Ordering::Relaxed);
+        self.latency.observe(started.elapsed());
+    }
+
+    /// Records a failed request.
+    pub(crate) fn record_failure(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.failed_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a streamed request abandoned by the caller before it finished.
+    pub(crate) fn record_cancellation(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.cancelled_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the first token of a streamed request started at `started`.
+    pub(crate) fn record_first_token(&self, started: Instant) {
+        self.time_to_first_token.observe(started.elapsed());
+    }
+
+    /// Returns the current values of the metrics.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            failed_requests: self.failed_requests.load(Ordering::Relaxed),
+            cancelled_requests: self.cancelled_requests.load(Ordering::Relaxed),
+            examples: self.examples.load(Ordering::Relaxed),
+            input_tokens: self.input_tokens.load(Ordering::Relaxed),
+            output_tokens: self.output_tokens.load(Ordering::Relaxed),
+            latency: self.latency.snapshot(),
+            time_to_first_token: self.time_to_first_token.snapshot(),
+        }
+    }
+}
+
+/// The values of [`Metrics`] at some point in time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    /// Number of requests, including the failed and cancelled ones.
+    pub requests: u64,
+    /// Number of failed requests.
+    pub failed_requests: u64,
+    /// Number of streamed requests dropped by the caller before they finished.
+    pub cancelled_requests: u64,
+    /// Number of prompts or sources in the successful requests.
+    pub examples: u64,
+    /// Number of tokens of the prompts or sources.
+    pub input_tokens: u64,
+    /// Number of generated tokens, over all the hypotheses.
+    pub output_tokens: u64,
+    /// Latency of the successful requests.
+    pub latency: HistogramSnapshot,
+    /// Time to the first token of the streamed requests.
+    pub time_to_first_token: HistogramSnapshot,
+}
+
+impl MetricsSnapshot {
+    /// Returns the number of generated tokens per second spent processing the requests.
+    ///
+    /// Requests processed concurrently are counted separately, so this is the throughput of a
+    /// single request rather than of the whole model.
+    pub fn tokens_per_second(&self) -> f64 {
+        if self.latency.sum > 0. {
+            self.output_tokens as f64 / self.latency.sum
+        } else {
+            0.
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Histogram {
+    /// Number of observations in each bucket, the last one having no upper bound.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    /// Sum of the observations, in microseconds.
+    sum: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            sum: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let i = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        self.sum
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        let mut count = 0;
+        let mut buckets = Vec::with_capacity(LATENCY_BUCKETS.len());
+        for (bound, n) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+            count += n.load(Ordering::Relaxed);
+            buckets.push((*bound, count));
+        }
+        count += self.buckets[LATENCY_BUCKETS.len()].load(Ordering::Relaxed);
+        HistogramSnapshot {
+            buckets,
+            count,
+            sum: self.sum.load(Ordering::Relaxed) as f64 / 1e6,
+        }
+    }
+}
+
+/// The values of a latency histogram at some point in time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HistogramSnapshot {
+    /// Upper bound of each bucket in seconds, with the number of observations less than or
+    /// equal to it.
+    pub buckets: Vec<(f64, u64)>,
+    /// Number of observations.
+    pub count: u64,
+    /// Sum of the observations, in seconds.
+    pub sum: f64,
+}
+
+impl HistogramSnapshot {
+    /// Returns the mean of the observations in seconds, if any.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// State of the replica pool of a model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplicaStats {
+    /// Number of parallel replicas.
+    pub num_replicas: usize,
+    /// Number of batches in the work queue.
+    pub num_queued_batches: usize,
+    /// Number of batches in the work queue or currently processed by a worker.
+    pub num_active_batches: usize,
+}
+
+/// Statistics of a generator or translator, returned by their `stats` methods.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub replicas: ReplicaStats,
+    pub metrics: MetricsSnapshot,
+}
+
+impl Stats {
+    /// Writes the statistics in the Prometheus text format, with the given labels.
+    ///
+    /// Use [`encode_prometheus`] to write the statistics of several models at once.
+    pub fn to_prometheus(&self, labels: &[(&str, &str)]) -> String {
+        encode_prometheus(&[(labels, self)])
+    }
+}
+
+/// Writes the statistics of several models in the Prometheus text format.
+///
+/// The labels of each model, e.g. `[("model", "gpt2")]`, should distinguish it from the
+/// others. The metrics are prefixed with `ctrans2_`.
+pub fn encode_prometheus(models: &[(&[(&str, &str)], &Stats)]) -> String {
+    let mut out = String::new();
+    let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&Stats) -> f64| {
+        writeln!(out, "# HELP ctrans2_{name} {help}").unwrap();
+        writeln!(out, "# TYPE ctrans2_{name} {kind}").unwrap();
+        for (labels, stats) in models {
+            let labels = format_labels(labels, None);
+            writeln!(out, "ctrans2_{name}{labels} {}", value(stats)).unwrap();
+        }
+    };
+
+    family("replicas", "gauge", "Number of parallel replicas.", &|s| {
+        s.replicas.num_replicas as f64
+    });
+    family(
+        "queued_batches",
+        "gauge",
+        "Number of batches in the work queue.",
+        &|s| s.replicas.num_queued_batches as f64,
+    );
+    family(
+        "active_batches",
+        "gauge",
+        "Number of batches in the work queue or currently processed by a worker.",
+        &|s| s.replicas.num_active_batches as f64,
+    );
+    family(
+        "requests_total",
+        "counter",
+        "Number of requests, including the failed and cancelled ones.",
+        &|s| s.metrics.requests as f64,
+    );
+    family(
+        "failed_requests_total",
+        "counter",
+        "Number of failed requests.",
+        &|s| s.metrics.failed_requests as f64,
+    );
+    family(
+        "cancelled_requests_total",
+        "counter",
+        "Number of streamed requests dropped by the caller before they finished.",
+        &|s| s.metrics.cancelled_requests as f64,
+    );
+    family(
+        "examples_total",
+        "counter",
+        "Number of prompts or sources in the successful requests.",
+        &|s| s.metrics.examples as f64,
+    );
+    family(
+        "input_tokens_total",
+        "counter",
+        "Number of tokens of the prompts or sources.",
+        &|s| s.metrics.input_tokens as f64,
+    );
+    family(
+        "output_tokens_total",
+        "counter",
+        "Number of generated tokens.",
+        &|s| s.metrics.output_tokens as f64,
+    );
+    family(
+        "output_tokens_per_second",
+        "gauge",
+        "Generated tokens per second spent in the successful requests, i.e. the throughput of a single request rather than of the model.",
+        &|s| s.metrics.tokens_per_second(),
+    );
+
+    write_histogram(
+        &mut out,
+        models,
+        "request_duration_seconds",
+        "Latency of the successful requests.",
+        |s| &s.metrics.latency,
+    );
+    write_histogram(
+        &mut out,
+        models,
+        "time_to_first_token_seconds",
+        "Time to the first token of the streamed requests.",
+        |s| &s.metrics.time_to_first_token,
+    );
+    out
+}
+
+fn write_histogram<F>(
+    out: &mut String,
+    models: &[(&[(&str, &str)], &Stats)],
+    name: &str,
+    help: &str,
+    histogram: F,
+) where
+    F: Fn(&Stats) -> &HistogramSnapshot,
+{
+    writeln!(out, "# HELP ctrans2_{name} {help}").unwrap();
+    writeln!(out, "# TYPE ctrans2_{name} histogram").unwrap();
+    for (labels, stats) in models {
+        let h = histogram(stats);
+        for (bound, count) in &h.buckets {
+            let labels = format_labels(labels, Some(&bound.to_string()));
+            writeln!(out, "ctrans2_{name}_bucket{labels} {count}").unwrap();
+        }
+        let inf = format_labels(labels, Some("+Inf"));
+        writeln!(out, "ctrans2_{name}_bucket{inf} {}", h.count).unwrap();
+        let labels = format_labels(labels, None);
+        writeln!(out, "ctrans2_{name}_sum{labels} {}", h.sum).unwrap();
+        writeln!(out, "ctrans2_{name}_count{labels} {}", h.count).unwrap();
+    }
+}
+
+/// Formats the labels of a sample, with the `le` label of a histogram bucket if given.
+fn format_labels(labels: &[(&str, &str)], le: Option<&str>) -> String {
+    let mut pairs = labels
+        .iter()
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+        .collect::<Vec<_>>();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{le}\""));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> Stats {
+        Stats {
+            replicas: ReplicaStats {
+                num_replicas: 2,
+                num_queued_batches: 1,
+                num_active_batches: 3,
+            },
+            metrics: MetricsSnapshot {
+                requests: 7,
+                failed_requests: 1,
+                cancelled_requests: 2,
+                examples: 4,
+                input_tokens: 40,
+                output_tokens: 100,
+                latency: HistogramSnapshot {
+                    buckets: vec![(0.5, 1), (2.5, 3)],
+                    count: 4,
+                    sum: 8.,
+                },
+                time_to_first_token: HistogramSnapshot {
+                    buckets: vec![(0.05, 2)],
+                    count: 2,
+                    sum: 0.0625,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn prometheus_text_format() {
+        let stats = stats();
+        let text = encode_prometheus(&[(&[("model", "a\"b\\c\nd"), ("device", "cpu")], &stats)]);
+        let labels = r#"model="a\"b\\c\nd",device="cpu""#;
+        let expected = format!(
+            r#"# HELP ctrans2_replicas Number of parallel replicas.
+# TYPE ctrans2_replicas gauge
+ctrans2_replicas{{{labels}}} 2
+# HELP ctrans2_queued_batches Number of batches in the work queue.
+# TYPE ctrans2_queued_batches gauge
+ctrans2_queued_batches{{{labels}}} 1
+# HELP ctrans2_active_batches Number of batches in the work queue or currently processed by a worker.
+# TYPE ctrans2_active_batches gauge
+ctrans2_active_batches{{{labels}}} 3
+# HELP ctrans2_requests_total Number of requests, including the failed and cancelled ones.
+# TYPE ctrans2_requests_total counter
+ctrans2_requests_total{{{labels}}} 7
+# HELP ctrans2_failed_requests_total Number of failed requests.
+# TYPE ctrans2_failed_requests_total counter
+ctrans2_failed_requests_total{{{labels}}} 1
+# HELP ctrans2_cancelled_requests_total Number of streamed requests dropped by the caller before they finished.
+# TYPE ctrans2_cancelled_requests_total counter
+ctrans2_cancelled_requests_total{{{labels}}} 2
+# HELP ctrans2_examples_total Number of prompts or sources in the successful requests.
+# TYPE ctrans2_examples_total counter
+ctrans2_examples_total{{{labels}}} 4
+# HELP ctrans2_input_tokens_total Number of tokens of the prompts or sources.
+# TYPE ctrans2_input_tokens_total counter
+ctrans2_input_tokens_total{{{labels}}} 40
+# HELP ctrans2_output_tokens_total Number of generated tokens.
+# TYPE ctrans2_output_tokens_total counter
+ctrans2_output_tokens_total{{{labels}}} 100
+# HELP ctrans2_output_tokens_per_second Generated tokens per second spent in the successful requests, i.e. the throughput of a single request rather than of the model.
+# TYPE ctrans2_output_tokens_per_second gauge
+ctrans2_output_tokens_per_second{{{labels}}} 12.5
+# HELP ctrans2_request_duration_seconds Latency of the successful requests.
+# TYPE ctrans2_request_duration_seconds histogram
+ctrans2_request_duration_seconds_bucket{{{labels},le="0.5"}} 1
+ctrans2_request_duration_seconds_bucket{{{labels},le="2.5"}} 3
+ctrans2_request_duration_seconds_bucket{{{labels},le="+Inf"}} 4
+ctrans2_request_duration_seconds_sum{{{labels}}} 8
+ctrans2_request_duration_seconds_count{{{labels}}} 4
+# HELP ctrans2_time_to_first_token_seconds Time to the first token of the streamed requests.
+# TYPE ctrans2_time_to_first_token_seconds histogram
+ctrans2_time_to_first_token_seconds_bucket{{{labels},le="0.05"}} 2
+ctrans2_time_to_first_token_seconds_bucket{{{labels},le="+Inf"}} 2
+ctrans2_time_to_first_token_seconds_sum{{{labels}}} 0.0625
+ctrans2_time_to_first_token_seconds_count{{{labels}}} 2
+"#
+        );
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn samples_of_each_model_follow_their_family() {
+        let (a, b) = (stats(), Stats::default());
+        let text = encode_prometheus(&[(&[("model", "a")], &a), (&[("model", "b")], &b)]);
+        assert!(text.contains(
+            "# TYPE ctrans2_replicas gauge\nctrans2_replicas{model=\"a\"} 2\nctrans2_replicas{model=\"b\"} 0\n"
+        ));
+        // Without labels, the bucket only has the `le` label.
+        let text = Stats::default().to_prometheus(&[]);
+        assert!(text.contains("ctrans2_requests_total 0\n"));
+        assert!(text.contains("ctrans2_request_duration_seconds_bucket{le=\"+Inf\"} 0\n"));
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::default();
+        let started = Instant::now() - Duration::from_millis(1500);
+        metrics.record(started, 2, 10, 20);
+        metrics.record_failure();
+        metrics.record_cancellation();
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.requests, 3);
+        assert_eq!(snapshot.failed_requests, 1);
+        assert_eq!(snapshot.cancelled_requests, 1);
+        assert_eq!(snapshot.examples, 2);
+        assert_eq!(snapshot.latency.count, 1);
+        assert_eq!(snapshot.latency.buckets.len(), LATENCY_BUCKETS.len());
+        for (bound, count) in &snapshot.latency.buckets {
+            assert_eq!(*count, u64::from(*bound >= 2.5), "{bound}");
+        }
+    }
+}
@@ -0,0 +1,482 @@
+// model.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Inspection and validation of CTranslate2 model directories.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use thiserror::Error;
+
+const MODEL_FILENAME: &str = "model.bin";
+const CONFIG_FILENAME: &str = "config.json";
+
+/// Names of the vocabulary files, without the `.json` or `.txt` extension.
+const VOCABULARY_NAMES: [&str; 4] = [
+    "vocabulary",
+    "shared_vocabulary",
+    "source_vocabulary",
+    "target_vocabulary",
+];
+
+/// Errors found while inspecting a model directory.
+#[derive(Debug, Error)]
+pub enum ModelDirError {
+    /// The path does not exist or is not a directory.
+    #[error("{0} is not a directory")]
+    NotADirectory(PathBuf),
+    /// The path cannot be passed to CTranslate2 since it is not valid UTF-8.
+    #[error("{0} is not a valid UTF-8 path")]
+    NonUtf8Path(PathBuf),
+    /// The directory has no `model.bin`.
+    #[error("{0} is not found")]
+    MissingModel(PathBuf),
+    /// `model.bin` cannot be read or has an unsupported header.
+    #[error("failed to read the header of {path}: {source}")]
+    InvalidModel {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// `config.json` cannot be read or parsed.
+    #[error("failed to read {path}: {message}")]
+    InvalidConfig { path: PathBuf, message: String },
+    /// The directory has no vocabulary file expected by the architecture.
+    #[error("no vocabulary is found in {0}")]
+    MissingVocabulary(PathBuf),
+    /// The spec name in `model.bin` is not one of a supported architecture.
+    #[error("unknown model spec {0}")]
+    UnknownArchitecture(String),
+    /// The model has an architecture other than the expected one.
+    #[error("expected a {expected} model but {path} contains a {found} model")]
+    UnexpectedArchitecture {
+        path: PathBuf,
+        expected: Architecture,
+        found: Architecture,
+    },
+}
+
+/// Architecture of a model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Architecture {
+    /// A decoder-only language model, loaded by [`crate::generator::Generator`].
+    DecoderOnly,
+    /// An encoder-decoder model, loaded by [`crate::translator::Translator`].
+    SequenceToSequence,
+    /// A Whisper speech recognition model, loaded by [`crate::whisper::Whisper`].
+    Whisper,
+    /// An encoder-only model, loaded by [`crate::encoder::Encoder`].
+    Encoder,
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Architecture::DecoderOnly => "decoder-only",
+            Architecture::SequenceToSequence => "sequence-to-sequence",
+            Architecture::Whisper => "Whisper",
+            Architecture::Encoder => "encoder",
+        })
+    }
+}
+
+impl Architecture {
+    /// Returns the architecture of the given model spec name, as written in `model.bin`.
+    pub fn from_spec(spec: &str) -> Option<Architecture> {
+        match spec {
+            "TransformerDecoderModelSpec" => Some(Architecture::DecoderOnly),
+            "TransformerSpec" | "TransformerBase" | "TransformerBig" => {
+                Some(Architecture::SequenceToSequence)
+            }
+            "WhisperSpec" => Some(Architecture::Whisper),
+            "TransformerEncoderModelSpec" => Some(Architecture::Encoder),
+            _ => None,
+        }
+    }
+}
+
+/// Information about a model, read from its directory.
+#[derive(Clone, Debug)]
+pub struct ModelInfo {
+    /// Version of the binary format of `model.bin`.
+    pub binary_version: u32,
+    /// Name of the model spec, such as `TransformerDecoderModelSpec` (`None` for models
+    /// converted with a binary version older than 2).
+    pub spec: Option<String>,
+    /// Revision of the model spec.
+    pub spec_revision: u32,
+    /// The detected architecture.
+    pub architecture: Architecture,
+    /// The vocabulary files.
+    pub vocabularies: Vec<PathBuf>,
+    /// Contents of `config.json` (`None` if the model has no config).
+    pub config: Option<Value>,
+}
+
+impl ModelInfo {
+    /// Returns the BOS token defined in the config, if any.
+    pub fn bos_token(&self) -> Option<&str> {
+        self.config_str("bos_token")
+    }
+
+    /// Returns the EOS token defined in the config, if any.
+    pub fn eos_token(&self) -> Option<&str> {
+        self.config_str("eos_token")
+    }
+
+    /// Returns the unknown token defined in the config, if any.
+    pub fn unk_token(&self) -> Option<&str> {
+        self.config_str("unk_token")
+    }
+
+    fn config_str(&self, key: &str) -> Option<&str> {
+        self.config.as_ref()?.get(key)?.as_str()
+    }
+}
+
+/// A validated CTranslate2 model directory.
+#[derive(Clone, Debug)]
+pub struct ModelDir {
+    path: PathBuf,
+    info: ModelInfo,
+}
+
+impl ModelDir {
+    /// Inspects the given model directory.
+    ///
+    /// This checks that `model.bin` exists and reads the spec from its header, parses
+    /// `config.json` if any, and lists the vocabulary files.
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<ModelDir, ModelDirError> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Err(ModelDirError::NotADirectory(path.to_path_buf()));
+        }
+        if path.to_str().is_none() {
+            return Err(ModelDirError::NonUtf8Path(path.to_path_buf()));
+        }
+
+        let model = path.join(MODEL_FILENAME);
+        if !model.is_file() {
+            return Err(ModelDirError::MissingModel(model));
+        }
+        let (binary_version, spec, spec_revision) =
+            read_header(&model).map_err(|source| ModelDirError::InvalidModel {
+                path: model.clone(),
+                source,
+            })?;
+
+        let config = path.join(CONFIG_FILENAME);
+        let config = if config.is_file() {
+            let contents =
+                fs::read_to_string(&config).map_err(|err| ModelDirError::InvalidConfig {
+                    path: config.clone(),
+                    message: err.to_string(),
+                })?;
+            Some(
+                serde_json::from_str(&contents).map_err(|err| ModelDirError::InvalidConfig {
+                    path: config.clone(),
+                    message: err.to_string(),
+                })?,
+            )
+        } else {
+            None
+        };
+
+        let vocabularies = VOCABULARY_NAMES
+            .iter()
+            .flat_map(|name| ["json", "txt"].map(|ext| path.join(format!("{name}.{ext}"))))
+            .filter(|p| p.is_file())
+            .collect::<Vec<_>>();
+        let has_vocabulary = |name: &str| {
+            vocabularies
+                .iter()
+                .any(|p| p.file_stem().is_some_and(|stem| stem == name))
+        };
+
+        let architecture = match spec.as_deref() {
+            Some(spec) => Architecture::from_spec(spec)
+                .ok_or_else(|| ModelDirError::UnknownArchitecture(spec.to_string()))?,
+            // Models converted before the spec was written to `model.bin` are translation
+            // models.
+            None => Architecture::SequenceToSequence,
+        };
+
+        let has_vocabularies = match architecture {
+            Architecture::SequenceToSequence => {
+                has_vocabulary("shared_vocabulary")
+                    || (has_vocabulary("source_vocabulary") && has_vocabulary("target_vocabulary"))
+            }
+            _ => has_vocabulary("vocabulary"),
+        };
+        if !has_vocabularies {
+            return Err(ModelDirError::MissingVocabulary(path.to_path_buf()));
+        }
+
+        Ok(ModelDir {
+            path: path.to_path_buf(),
+            info: ModelInfo {
+                binary_version,
+                spec,
+                spec_revision,
+                architecture,
+                vocabularies,
+                config,
+            },
+        })
+    }
+
+    /// Inspects the given model directory and checks it contains a model of the given
+    /// architecture.
+    pub fn open_as<T: AsRef<Path>>(
+        path: T,
+        expected: Architecture,
+    ) -> Result<ModelDir, ModelDirError> {
+        let dir = ModelDir::open(path)?;
+        if dir.info.architecture != expected {
+            return Err(ModelDirError::UnexpectedArchitecture {
+                path: dir.path,
+                expected,
+                found: dir.info.architecture,
+            });
+        }
+        Ok(dir)
+    }
+
+    /// Returns the path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the directory as a string, as passed to CTranslate2.
+    pub fn path_str(&self) -> &str {
+        // Checked to be valid UTF-8 when opened.
+        self.path.to_str().unwrap_or_default()
+    }
+
+    /// Returns the information about the model.
+    pub fn info(&self) -> &ModelInfo {
+        &self.info
+    }
+
+    /// Returns the path of the given file in the directory.
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+/// Reads the binary version, the spec name and the spec revision from the header of a
+/// `model.bin` file.
+fn read_header(path: &Path) -> io::Result<(u32, Option<String>, u32)> {
+    let mut file = File::open(path)?;
+    let binary_version = read_u32(&mut file)?;
+    if binary_version < 2 {
+        return Ok((binary_version, None, 1));
+    }
+
+    // The spec name is a null-terminated string prefixed by its length.
+    let mut len = [0u8; 2];
+    file.read_exact(&mut len)?;
+    let mut name = vec![0u8; u16::from_le_bytes(len) as usize];
+    file.read_exact(&mut name)?;
+    let name = name.split(|b| *b == 0).next().unwrap_or_default();
+    let spec = String::from_utf8(name.to_vec())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let spec_revision = read_u32(&mut file)?;
+    Ok((binary_version, Some(spec), spec_revision))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// A temporary directory removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "ctrans2-model-{}-{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::SeqCst)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, name: &str, contents: &[u8]) {
+            fs::write(self.0.join(name), contents).unwrap();
+        }
+
+        /// Writes a `model.bin` with the header of the given spec, followed by no variables.
+        fn write_model(&self, binary_version: u32, spec: &str, spec_revision: u32) {
+            let mut header = binary_version.to_le_bytes().to_vec();
+            if binary_version >= 2 {
+                header.extend((spec.len() as u16 + 1).to_le_bytes());
+                header.extend(spec.as_bytes());
+                header.push(0);
+                header.extend(spec_revision.to_le_bytes());
+            }
+            header.extend(0u32.to_le_bytes());
+            self.write(MODEL_FILENAME, &header);
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn header_and_config_are_read() {
+        let dir = TempDir::new();
+        dir.write_model(6, "TransformerDecoderModelSpec", 3);
+        dir.write("vocabulary.json", b"[]");
+        dir.write(
+            CONFIG_FILENAME,
+            br#"{"bos_token": "<s>", "eos_token": "</s>"}"#,
+        );
+
+        let model = ModelDir::open(&dir.0).unwrap();
+        let info = model.info();
+        assert_eq!(info.binary_version, 6);
+        assert_eq!(info.spec.as_deref(), Some("TransformerDecoderModelSpec"));
+        assert_eq!(info.spec_revision, 3);
+        assert_eq!(info.architecture, Architecture::DecoderOnly);
+        assert_eq!(info.bos_token(), Some("<s>"));
+        assert_eq!(info.eos_token(), Some("</s>"));
+        assert_eq!(info.unk_token(), None);
+        assert_eq!(model.path_str(), dir.0.to_str().unwrap());
+    }
+
+    #[test]
+    fn old_models_are_translation_models() {
+        let dir = TempDir::new();
+        dir.write_model(1, "", 0);
+        dir.write("shared_vocabulary.txt", b"<s>\n");
+
+        let info = ModelDir::open(&dir.0).unwrap().info().clone();
+        assert_eq!(info.spec, None);
+        assert_eq!(info.spec_revision, 1);
+        assert_eq!(info.architecture, Architecture::SequenceToSequence);
+        assert!(info.config.is_none());
+    }
+
+    #[test]
+    fn vocabularies_are_listed() {
+        let dir = TempDir::new();
+        dir.write_model(6, "TransformerSpec", 7);
+        dir.write("source_vocabulary.json", b"[]");
+        dir.write("target_vocabulary.txt", b"");
+        dir.write("vocab.json", b"[]");
+
+        let model = ModelDir::open(&dir.0).unwrap();
+        assert_eq!(
+            model.info().vocabularies,
+            vec![
+                dir.0.join("source_vocabulary.json"),
+                dir.0.join("target_vocabulary.txt"),
+            ]
+        );
+
+        // A translation model needs both the source and target vocabularies.
+        fs::remove_file(dir.0.join("target_vocabulary.txt")).unwrap();
+        assert!(matches!(
+            ModelDir::open(&dir.0),
+            Err(ModelDirError::MissingVocabulary(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_spec_is_rejected() {
+        let dir = TempDir::new();
+        dir.write_model(6, "Wav2Vec2Spec", 1);
+        dir.write("vocabulary.json", b"[]");
+        assert!(matches!(
+            ModelDir::open(&dir.0),
+            Err(ModelDirError::UnknownArchitecture(spec)) if spec == "Wav2Vec2Spec"
+        ));
+    }
+
+    #[test]
+    fn unexpected_architecture_is_rejected() {
+        let dir = TempDir::new();
+        dir.write_model(6, "WhisperSpec", 3);
+        dir.write("vocabulary.json", b"[]");
+        assert!(ModelDir::open_as(&dir.0, Architecture::Whisper).is_ok());
+        assert!(matches!(
+            ModelDir::open_as(&dir.0, Architecture::DecoderOnly),
+            Err(ModelDirError::UnexpectedArchitecture {
+                expected: Architecture::DecoderOnly,
+                found: Architecture::Whisper,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn missing_files_are_rejected() {
+        let dir = TempDir::new();
+        assert!(matches!(
+            ModelDir::open(dir.0.join("missing")),
+            Err(ModelDirError::NotADirectory(_))
+        ));
+        assert!(matches!(
+            ModelDir::open(&dir.0),
+            Err(ModelDirError::MissingModel(path)) if path == dir.0.join(MODEL_FILENAME)
+        ));
+
+        // The header is truncated after the length of the spec name.
+        dir.write(MODEL_FILENAME, &[6, 0, 0, 0, 16, 0]);
+        assert!(matches!(
+            ModelDir::open(&dir.0),
+            Err(ModelDirError::InvalidModel { .. })
+        ));
+
+        dir.write_model(6, "TransformerDecoderModelSpec", 3);
+        assert!(matches!(
+            ModelDir::open(&dir.0),
+            Err(ModelDirError::MissingVocabulary(_))
+        ));
+
+        dir.write("vocabulary.json", b"[]");
+        dir.write(CONFIG_FILENAME, b"{");
+        assert!(matches!(
+            ModelDir::open(&dir.0),
+            Err(ModelDirError::InvalidConfig { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_path_is_rejected() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new();
+        let path = dir.0.join(OsStr::from_bytes(b"model-\xff"));
+        fs::create_dir(&path).unwrap();
+        assert!(matches!(
+            ModelDir::open(&path),
+            Err(ModelDirError::NonUtf8Path(_))
+        ));
+    }
+}
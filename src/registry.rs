@@ -0,0 +1,338 @@
+// registry.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! A registry of named models keeping a limited number of them loaded.
+//!
+//! ```no_run
+//! # fn main() -> ctrans2::Result<()> {
+//! use ctrans2::{Config, Device, Generator, ModelRegistry};
+//!
+//! // Keep two models on the GPU, parking the others in the CPU memory.
+//! let registry = ModelRegistry::new(2, true);
+//! for name in ["a", "b", "c"] {
+//!     let path = format!("/path/to/{name}");
+//!     registry.insert(name, Generator::new(path, Device::CUDA, Config::default())?)?;
+//! }
+//!
+//! // Loads "a" again, unloading "b" which is the least recently used.
+//! let generator = registry.get("a")?.unwrap();
+//! let res = generator.generate_batch(vec!["Hello"], &Default::default())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::Result;
+use crate::generator::Generator;
+use crate::translator::Translator;
+
+/// A model which can be unloaded and loaded again, such as [`Generator`] and [`Translator`].
+pub trait LoadableModel {
+    /// Unloads the model, keeping it in the CPU memory if `to_cpu` is set. Returns false if
+    /// the model is still in use and was kept loaded.
+    fn unload_model(&self, to_cpu: bool) -> Result<bool>;
+    /// Loads the model again.
+    fn load_model(&self) -> Result<()>;
+    /// Returns true if the model is loaded.
+    fn model_is_loaded(&self) -> bool;
+}
+
+impl LoadableModel for Generator {
+    fn unload_model(&self, to_cpu: bool) -> Result<bool> {
+        Generator::unload_model(self, to_cpu)
+    }
+
+    fn load_model(&self) -> Result<()> {
+        Generator::load_model(self)
+    }
+
+    fn model_is_loaded(&self) -> bool {
+        Generator::model_is_loaded(self)
+    }
+}
+
+impl LoadableModel for Translator {
+    fn unload_model(&self, to_cpu: bool) -> Result<bool> {
+        Translator::unload_model(self, to_cpu)
+    }
+
+    fn load_model(&self) -> Result<()> {
+        Translator::load_model(self)
+    }
+
+    fn model_is_loaded(&self) -> bool {
+        Translator::model_is_loaded(self)
+    }
+}
+
+/// Named models of which at most `capacity` are kept loaded, unloading the least recently
+/// used ones.
+///
+/// The models are returned in an `Arc`, and a model is not unloaded while a clone of its
+/// `Arc` is held outside of the registry, so the callers should drop it once their requests
+/// finish. The models in use are skipped when choosing which model to unload, so more than
+/// `capacity` models may be loaded while they are all in use.
+///
+/// The models are loaded under the lock of the registry, so the calls made meanwhile wait
+/// for the loading to finish.
+pub struct ModelRegistry<M> {
+    capacity: usize,
+    to_cpu: bool,
+    inner: Mutex<Inner<M>>,
+}
+
+struct Inner<M> {
+    models: HashMap<String, Arc<M>>,
+    /// Names of the loaded models, the least recently used first.
+    loaded: Vec<String>,
+}
+
+impl<M: LoadableModel> ModelRegistry<M> {
+    /// Creates a registry keeping at most `capacity` models loaded.
+    ///
+    /// If `to_cpu` is set, the unloaded models are kept in the CPU memory so that they are
+    /// loaded again faster.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize, to_cpu: bool) -> Self {
+        assert!(capacity > 0, "the capacity must be at least 1");
+        Self {
+            capacity,
+            to_cpu,
+            inner: Mutex::new(Inner {
+                models: HashMap::new(),
+                loaded: Vec::new(),
+            }),
+        }
+    }
+
+    /// Returns the maximum number of loaded models.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Registers a model, returning the model previously registered with the same name.
+    ///
+    /// If the model is loaded, it becomes the most recently used one and the least recently
+    /// used models are unloaded beyond the capacity.
+    pub fn insert<T: Into<String>>(&self, name: T, model: M) -> Result<Option<Arc<M>>> {
+        let name = name.into();
+        let mut inner = self.lock();
+        inner.loaded.retain(|n| *n != name);
+        if model.model_is_loaded() {
+            inner.loaded.push(name.clone());
+        }
+        let prev = inner.models.insert(name.clone(), Arc::new(model));
+        self.evict(&mut inner, &name)?;
+        Ok(prev)
+    }
+
+    /// Removes a model from the registry, returning it if it was registered.
+    pub fn remove(&self, name: &str) -> Option<Arc<M>> {
+        let mut inner = self.lock();
+        inner.loaded.retain(|n| n != name);
+        inner.models.remove(name)
+    }
+
+    /// Returns the model registered with the given name, loading it if needed.
+    ///
+    /// The model becomes the most recently used one, and the least recently used models are
+    /// unloaded beyond the capacity.
+    pub fn get(&self, name: &str) -> Result<Option<Arc<M>>> {
+        let mut inner = self.lock();
+        let Some(model) = inner.models.get(name).cloned() else {
+            return Ok(None);
+        };
+        if !model.model_is_loaded() {
+            model.load_model()?;
+        }
+        inner.loaded.retain(|n| n != name);
+        inner.loaded.push(name.to_string());
+        self.evict(&mut inner, name)?;
+        Ok(Some(model))
+    }
+
+    /// Returns the names of the registered models, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self.lock().models.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Returns the names of the loaded models, the least recently used first.
+    pub fn loaded(&self) -> Vec<String> {
+        self.lock().loaded.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner<M>> {
+        // The state is consistent even if a thread panicked while holding the lock.
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Unloads the least recently used models which are not in use, except `keep`, until at
+    /// most `capacity` models are loaded.
+    fn evict(&self, inner: &mut Inner<M>, keep: &str) -> Result<()> {
+        let mut i = 0;
+        while inner.loaded.len() > self.capacity && i < inner.loaded.len() {
+            let name = &inner.loaded[i];
+            let model = &inner.models[name];
+            // The registry holds one reference; any other one may be running a request.
+            let in_use = name == keep || Arc::strong_count(model) > 1;
+            if in_use || !model.unload_model(self.to_cpu)? {
+                i += 1;
+            } else {
+                inner.loaded.remove(i);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// A model counting its loads, which refuses to unload while `busy` is set.
+    #[derive(Default)]
+    struct FakeModel {
+        loaded: AtomicBool,
+        busy: AtomicBool,
+        loads: AtomicUsize,
+    }
+
+    impl FakeModel {
+        fn loaded() -> Self {
+            let model = Self::default();
+            model.loaded.store(true, Ordering::SeqCst);
+            model
+        }
+    }
+
+    impl LoadableModel for FakeModel {
+        fn unload_model(&self, _to_cpu: bool) -> Result<bool> {
+            if self.busy.load(Ordering::SeqCst) {
+                return Ok(false);
+            }
+            self.loaded.store(false, Ordering::SeqCst);
+            Ok(true)
+        }
+
+        fn load_model(&self) -> Result<()> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            self.loaded.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn model_is_loaded(&self) -> bool {
+            self.loaded.load(Ordering::SeqCst)
+        }
+    }
+
+    fn registry(capacity: usize, names: &[&str]) -> ModelRegistry<FakeModel> {
+        let registry = ModelRegistry::new(capacity, true);
+        for name in names {
+            registry.insert(*name, FakeModel::loaded()).unwrap();
+        }
+        registry
+    }
+
+    fn is_loaded(registry: &ModelRegistry<FakeModel>, name: &str) -> bool {
+        registry.lock().models[name].model_is_loaded()
+    }
+
+    #[test]
+    fn capacity_is_enforced_on_insert() {
+        let registry = registry(2, &["a", "b", "c"]);
+        assert_eq!(registry.loaded(), vec!["b", "c"]);
+        assert!(!is_loaded(&registry, "a"));
+        assert_eq!(registry.names(), vec!["a", "b", "c"]);
+
+        // Unloaded models are registered without evicting the loaded ones.
+        registry.insert("d", FakeModel::default()).unwrap();
+        assert_eq!(registry.loaded(), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn least_recently_used_model_is_unloaded() {
+        let registry = registry(2, &["a", "b"]);
+        drop(registry.get("a").unwrap());
+        assert_eq!(registry.loaded(), vec!["b", "a"]);
+
+        registry.insert("c", FakeModel::loaded()).unwrap();
+        assert_eq!(registry.loaded(), vec!["a", "c"]);
+        assert!(!is_loaded(&registry, "b"));
+    }
+
+    #[test]
+    fn unloaded_model_is_loaded_again_on_get() {
+        let registry = registry(1, &["a", "b"]);
+        assert!(!is_loaded(&registry, "a"));
+
+        let a = registry.get("a").unwrap().unwrap();
+        assert!(a.model_is_loaded());
+        assert_eq!(a.loads.load(Ordering::SeqCst), 1);
+        drop(a);
+        assert_eq!(registry.loaded(), vec!["a"]);
+        assert!(!is_loaded(&registry, "b"));
+
+        // A loaded model is not loaded again.
+        let a = registry.get("a").unwrap().unwrap();
+        assert_eq!(a.loads.load(Ordering::SeqCst), 1);
+        assert!(registry.get("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn models_in_use_are_skipped() {
+        let registry = registry(1, &["a"]);
+        let a = registry.get("a").unwrap().unwrap();
+
+        // "a" is held by the caller, so both models stay loaded beyond the capacity.
+        registry.insert("b", FakeModel::loaded()).unwrap();
+        assert_eq!(registry.loaded(), vec!["a", "b"]);
+        assert!(a.model_is_loaded());
+
+        // Once released, "a" is unloaded by the next eviction.
+        drop(a);
+        drop(registry.get("b").unwrap());
+        assert_eq!(registry.loaded(), vec!["b"]);
+        assert!(!is_loaded(&registry, "a"));
+    }
+
+    #[test]
+    fn models_refusing_to_unload_stay_loaded() {
+        let registry = registry(1, &["a"]);
+        registry.lock().models["a"]
+            .busy
+            .store(true, Ordering::SeqCst);
+
+        registry.insert("b", FakeModel::loaded()).unwrap();
+        assert_eq!(registry.loaded(), vec!["a", "b"]);
+        assert!(is_loaded(&registry, "a"));
+
+        registry.lock().models["a"]
+            .busy
+            .store(false, Ordering::SeqCst);
+        registry.insert("c", FakeModel::loaded()).unwrap();
+        assert_eq!(registry.loaded(), vec!["c"]);
+    }
+
+    #[test]
+    fn removed_model_is_forgotten() {
+        let registry = registry(2, &["a", "b"]);
+        assert!(registry.remove("a").is_some());
+        assert!(registry.remove("a").is_none());
+        assert_eq!(registry.loaded(), vec!["b"]);
+        assert_eq!(registry.names(), vec!["b"]);
+    }
+}
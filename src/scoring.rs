@@ -0,0 +1,61 @@
+// scoring.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Options and results of scoring.
+
+use crate::config::BatchType;
+
+/// The set of scoring options.
+#[derive(Clone, Debug)]
+pub struct ScoringOptions {
+    /// Truncate the inputs after this many tokens (set 0 to disable truncation).
+    pub max_input_length: usize,
+    /// Index of the first position to score (previous positions are only used as context).
+    pub offset: i64,
+    /// The maximum batch size. If the number of inputs is greater than `max_batch_size`,
+    /// the inputs are sorted by length and split by chunks of `max_batch_size` examples
+    /// so that the number of padding positions is minimized.
+    pub max_batch_size: usize,
+    /// Whether `max_batch_size` is the number of `examples` or `tokens`.
+    pub batch_type: BatchType,
+}
+
+impl Default for ScoringOptions {
+    fn default() -> Self {
+        Self {
+            max_input_length: 1024,
+            offset: 0,
+            max_batch_size: 0,
+            batch_type: Default::default(),
+        }
+    }
+}
+
+/// A scoring result.
+#[derive(Clone, Debug)]
+pub struct ScoringResult {
+    /// The scored tokens.
+    pub tokens: Vec<String>,
+    /// Log probability of each token.
+    pub tokens_score: Vec<f32>,
+}
+
+impl ScoringResult {
+    /// Returns the sum of the token log probabilities.
+    pub fn cumulated_score(&self) -> f32 {
+        self.tokens_score.iter().sum()
+    }
+
+    /// Returns the average of the token log probabilities.
+    pub fn normalized_score(&self) -> f32 {
+        if self.tokens_score.is_empty() {
+            return 0.;
+        }
+        self.cumulated_score() / self.tokens_score.len() as f32
+    }
+}
@@ -0,0 +1,255 @@
+// source.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Sources the models are loaded from: a directory, files held in memory, or a custom reader.
+//!
+//! ```no_run
+//! # fn main() -> ctrans2::Result<()> {
+//! use std::collections::HashMap;
+//! use ctrans2::{Config, Device, Generator, ModelSource};
+//!
+//! // E.g. decrypted from an archive or fetched from an object store.
+//! let mut files = HashMap::new();
+//! for name in ["model.bin", "config.json", "vocabulary.json", "tokenizer.json"] {
+//!     files.insert(name.to_string(), std::fs::read(format!("/path/to/model/{name}")).unwrap());
+//! }
+//! let generator = Generator::new(
+//!     ModelSource::memory("my-model", files),
+//!     Device::CPU,
+//!     Config::default(),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use cxx::UniquePtr;
+use tokenizers::Tokenizer;
+
+use crate::error::{Error, Result};
+use crate::model::{Architecture, ModelDir};
+
+#[cxx::bridge]
+pub(crate) mod ffi {
+    struct ModelFile<'a> {
+        name: &'a str,
+        content: &'a [u8],
+    }
+
+    struct ReaderFile {
+        found: bool,
+        content: Vec<u8>,
+    }
+
+    extern "Rust" {
+        type ModelReaderBox;
+
+        fn model_id(self: &ModelReaderBox) -> String;
+
+        fn read_file(self: &ModelReaderBox, filename: &str) -> Result<ReaderFile>;
+    }
+
+    unsafe extern "C++" {
+        include!("../include/source.h");
+
+        type ModelReaderHandle;
+
+        fn new_file_reader(model_dir: &str) -> UniquePtr<ModelReaderHandle>;
+
+        fn new_memory_reader(model_id: &str, files: Vec<ModelFile>)
+            -> UniquePtr<ModelReaderHandle>;
+
+        fn new_rust_reader(reader: Box<ModelReaderBox>) -> UniquePtr<ModelReaderHandle>;
+    }
+}
+
+/// Reads the files of a model from a custom storage.
+///
+/// The files are read while the model is loaded, which includes
+/// [`Generator::load_model`](crate::Generator::load_model) after the model was unloaded, so
+/// the reader may be called from any thread.
+pub trait ModelReader: Send + Sync {
+    /// Returns an identifier of the model, used in the error messages.
+    fn model_id(&self) -> String;
+
+    /// Returns the contents of the given file of the model, such as `model.bin`, or `None` if
+    /// the model has no such file.
+    fn read_file(&self, filename: &str) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// Where a model is loaded from.
+///
+/// Paths are converted to [`ModelSource::Dir`], so the constructors of the models accept
+/// them as before.
+#[derive(Clone)]
+pub enum ModelSource {
+    /// A model directory.
+    Dir(PathBuf),
+    /// The files of a model held in memory, by file name.
+    ///
+    /// The files are copied to CTranslate2, which keeps them to load the model again after
+    /// it is unloaded.
+    Memory {
+        id: String,
+        files: HashMap<String, Vec<u8>>,
+    },
+    /// A custom reader.
+    Reader(Arc<dyn ModelReader>),
+}
+
+impl ModelSource {
+    /// Creates a source from the files of a model held in memory.
+    pub fn memory<T: Into<String>>(id: T, files: HashMap<String, Vec<u8>>) -> Self {
+        ModelSource::Memory {
+            id: id.into(),
+            files,
+        }
+    }
+
+    /// Creates a source from a custom reader.
+    pub fn reader<R: ModelReader + 'static>(reader: R) -> Self {
+        ModelSource::Reader(Arc::new(reader))
+    }
+
+    /// Returns the path of the directory or the identifier of the model.
+    pub fn id(&self) -> String {
+        match self {
+            ModelSource::Dir(path) => path.display().to_string(),
+            ModelSource::Memory { id, .. } => id.clone(),
+            ModelSource::Reader(reader) => reader.model_id(),
+        }
+    }
+
+    /// Returns the contents of the given file of the model, or `None` if the model has no such
+    /// file.
+    pub fn read_file(&self, filename: &str) -> Result<Option<Vec<u8>>> {
+        let res = match self {
+            ModelSource::Dir(path) => match fs::read(path.join(filename)) {
+                Ok(content) => Ok(Some(content)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            },
+            ModelSource::Memory { files, .. } => Ok(files.get(filename).cloned()),
+            ModelSource::Reader(reader) => reader.read_file(filename),
+        };
+        res.map_err(|err| {
+            Error::ModelLoad(format!("failed to read {filename} of {}: {err}", self.id()))
+        })
+    }
+
+    /// Loads the tokenizer from the given file of the model.
+    pub(crate) fn read_tokenizer(&self, filename: &str) -> Result<Tokenizer> {
+        let Some(content) = self.read_file(filename)? else {
+            return Err(Error::Tokenizer(format!(
+                "{filename} is not found in {}",
+                self.id()
+            )));
+        };
+        Tokenizer::from_bytes(content).map_err(|err| Error::Tokenizer(err.to_string()))
+    }
+
+    /// Checks that a model directory contains a model of the given architecture.
+    ///
+    /// The other sources are not inspected; CTranslate2 fails to load them if they are
+    /// invalid.
+    pub(crate) fn check(self, expected: Architecture) -> Result<ModelSource> {
+        match self {
+            ModelSource::Dir(path) => Ok(ModelSource::Dir(
+                ModelDir::open_as(path, expected)?.path().to_path_buf(),
+            )),
+            source => Ok(source),
+        }
+    }
+
+    pub(crate) fn to_ffi(&self) -> Result<UniquePtr<ffi::ModelReaderHandle>> {
+        Ok(match self {
+            ModelSource::Dir(path) => {
+                let Some(path) = path.to_str() else {
+                    return Err(Error::ModelLoad(format!(
+                        "{} is not a valid UTF-8 path",
+                        path.display()
+                    )));
+                };
+                ffi::new_file_reader(path)
+            }
+            ModelSource::Memory { id, files } => ffi::new_memory_reader(
+                id,
+                files
+                    .iter()
+                    .map(|(name, content)| ffi::ModelFile { name, content })
+                    .collect(),
+            ),
+            ModelSource::Reader(reader) => {
+                ffi::new_rust_reader(Box::new(ModelReaderBox(reader.clone())))
+            }
+        })
+    }
+}
+
+impl From<PathBuf> for ModelSource {
+    fn from(path: PathBuf) -> Self {
+        ModelSource::Dir(path)
+    }
+}
+
+impl From<&PathBuf> for ModelSource {
+    fn from(path: &PathBuf) -> Self {
+        ModelSource::Dir(path.clone())
+    }
+}
+
+impl From<&Path> for ModelSource {
+    fn from(path: &Path) -> Self {
+        ModelSource::Dir(path.to_path_buf())
+    }
+}
+
+impl From<String> for ModelSource {
+    fn from(path: String) -> Self {
+        ModelSource::Dir(path.into())
+    }
+}
+
+impl From<&String> for ModelSource {
+    fn from(path: &String) -> Self {
+        ModelSource::Dir(path.into())
+    }
+}
+
+impl From<&str> for ModelSource {
+    fn from(path: &str) -> Self {
+        ModelSource::Dir(path.into())
+    }
+}
+
+/// A custom reader passed to CTranslate2.
+pub(crate) struct ModelReaderBox(Arc<dyn ModelReader>);
+
+impl ModelReaderBox {
+    fn model_id(&self) -> String {
+        self.0.model_id()
+    }
+
+    fn read_file(&self, filename: &str) -> io::Result<ffi::ReaderFile> {
+        Ok(match self.0.read_file(filename)? {
+            Some(content) => ffi::ReaderFile {
+                found: true,
+                content,
+            },
+            None => ffi::ReaderFile {
+                found: false,
+                content: Vec::new(),
+            },
+        })
+    }
+}
@@ -1,97 +1,385 @@
-use std::path::Path;
+use std::time::Instant;
 
-use anyhow::{anyhow, bail, Result};
-use tokenizers::{Decoder, EncodeInput, Tokenizer};
+use tokenizers::{EncodeInput, Tokenizer};
 
 use crate::config::{Config, Device};
+use crate::error::{Error, Result};
+use crate::metrics::{Metrics, ReplicaStats, Stats};
+use crate::model::Architecture;
+use crate::scoring::{ScoringOptions, ScoringResult};
+use crate::source::ModelSource;
+pub use self::translator::{TranslationOptions, TranslationOptionsBuilder};
+use self::translator::TranslationResult;
 
 const TOKENIZER_FILENAME: &str = "tokenizer.json";
 
-mod translator;
+/// The bridge to the CTranslate2 translator, taking tokens or token IDs without a tokenizer.
+pub mod translator;
 
 /// A text translator with a tokenizer.
+///
+/// The translator is `Send` and `Sync`: a single instance can be shared between threads, e.g.
+/// in an `Arc`, and the concurrent calls are dispatched to the replicas of the model.
 pub struct Translator {
     translator: self::translator::Translator,
     tokenizer: Tokenizer,
+    metrics: Metrics,
 }
 
 impl Translator {
     /// Initializes the translator and tokenizer.
-    pub fn new<T: AsRef<Path>>(path: T, device: Device, config: Config) -> Result<Translator> {
-        Translator::with_tokenizer(
-            &path,
-            device,
-            config,
-            Tokenizer::from_file(path.as_ref().join(TOKENIZER_FILENAME))
-                .map_err(|err| anyhow!("failed to load a tokenizer: {err}"))?,
-        )
+    ///
+    /// The model is loaded from a directory path or any other [`ModelSource`], and the
+    /// tokenizer from its `tokenizer.json` file.
+    pub fn new<T: Into<ModelSource>>(
+        source: T,
+        device: Device,
+        config: Config,
+    ) -> Result<Translator> {
+        let source = source.into().check(Architecture::SequenceToSequence)?;
+        let tokenizer = source.read_tokenizer(TOKENIZER_FILENAME)?;
+        Translator::with_tokenizer(source, device, config, tokenizer)
     }
 
-    /// Initializes the translator and tokenizer.
-    pub fn with_tokenizer<T: AsRef<Path>>(
-        path: T,
+    /// Initializes the translator with the given tokenizer, e.g. loaded from memory with
+    /// [`Tokenizer::from_bytes`].
+    pub fn with_tokenizer<T: Into<ModelSource>>(
+        source: T,
         device: Device,
         config: Config,
         tokenizer: Tokenizer,
     ) -> Result<Translator> {
+        let source = source.into().check(Architecture::SequenceToSequence)?;
         Ok(Translator {
-            translator: self::translator::Translator::new(
-                path.as_ref().to_str().unwrap(),
-                device,
-                config,
-            )?,
+            translator: self::translator::Translator::new(&source, device, config)?,
             tokenizer,
+            metrics: Metrics::default(),
         })
     }
 
+    /// Returns the metrics of the requests processed by this translator.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Returns the state of the replica pool.
+    pub fn replica_stats(&self) -> ReplicaStats {
+        ReplicaStats {
+            num_replicas: self.translator.num_replicas(),
+            num_queued_batches: self.translator.num_queued_batches(),
+            num_active_batches: self.translator.num_active_batches(),
+        }
+    }
+
+    /// Returns the state of the replica pool with a snapshot of the metrics.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            replicas: self.replica_stats(),
+            metrics: self.metrics.snapshot(),
+        }
+    }
+
+    /// Unloads the model to free the device memory, keeping the tokenizer and configuration.
+    ///
+    /// If `to_cpu` is set, the model is moved to the CPU memory so that
+    /// [`load_model`](Self::load_model) is faster than reading the model directory again.
+    /// Returns false if some batches are still queued or running, in which case the model is
+    /// kept loaded. The requests made while the model is unloaded fail with
+    /// [`Error::ModelUnloaded`].
+    pub fn unload_model(&self, to_cpu: bool) -> Result<bool> {
+        self.translator.unload_model(to_cpu)
+    }
+
+    /// Loads the model again after [`unload_model`](Self::unload_model), on the device and
+    /// with the configuration it was initialized with.
+    pub fn load_model(&self) -> Result<()> {
+        self.translator.load_model()
+    }
+
+    /// Returns true if the model is loaded.
+    pub fn model_is_loaded(&self) -> bool {
+        self.translator.model_is_loaded()
+    }
+
     /// Translates a batch of strings.
     pub fn translate_batch<'a, T, U, V>(
         &self,
         sources: Vec<T>,
         target_prefixes: Vec<Vec<U>>,
         options: &TranslationOptions<V>,
-    ) -> Result<Vec<(String, Option<f32>)>>
+    ) -> Result<Vec<TranslatedText>>
+    where
+        T: Into<EncodeInput<'a>>,
+        U: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let source = self.encode_ids(sources)?;
+        let target_prefix = self.token_ids(&target_prefixes)?;
+        self.translate_batch_ids(&source, &target_prefix, options)
+    }
+
+    /// Translates a batch of sources already encoded to token IDs.
+    ///
+    /// The IDs are those of the tokenizer of the model, and the sources should include the
+    /// special tokens the model expects, such as the EOS token. `target_prefix` holds the IDs
+    /// of the target prefix of each source, or is empty.
+    pub fn translate_batch_ids<V: AsRef<str>>(
+        &self,
+        source: &[Vec<usize>],
+        target_prefix: &[Vec<usize>],
+        options: &TranslationOptions<V>,
+    ) -> Result<Vec<TranslatedText>> {
+        let started = Instant::now();
+        let res = options
+            .validate()
+            .and_then(|_| {
+                self.translator
+                    .translate_batch_ids(source, target_prefix, options)
+            })
+            .and_then(|output| self.decode(output, target_prefix));
+        self.observe(started, source, res)
+    }
+
+    /// Translates a batch of strings without blocking the calling thread.
+    pub async fn translate_batch_async<'a, T, U, V>(
+        &self,
+        sources: Vec<T>,
+        target_prefixes: Vec<Vec<U>>,
+        options: &TranslationOptions<V>,
+    ) -> Result<Vec<TranslatedText>>
     where
         T: Into<EncodeInput<'a>>,
         U: AsRef<str>,
         V: AsRef<str>,
     {
-        let tokens = sources
+        let started = Instant::now();
+        let source = self.encode_ids(sources)?;
+        let target_prefix = self.token_ids(&target_prefixes)?;
+        let future = options.validate().and_then(|_| {
+            self.translator
+                .translate_batch_ids_async(&source, &target_prefix, options)
+        });
+        let res = match future {
+            Ok(future) => future
+                .await
+                .and_then(|output| self.decode(output, &target_prefix)),
+            Err(err) => Err(err),
+        };
+        self.observe(started, &source, res)
+    }
+
+    /// Scores the given target texts as translations of the source texts.
+    pub fn score_batch<'a, 'b, T, U>(
+        &self,
+        sources: Vec<T>,
+        targets: Vec<U>,
+        options: &ScoringOptions,
+    ) -> Result<Vec<ScoringResult>>
+    where
+        T: Into<EncodeInput<'a>>,
+        U: Into<EncodeInput<'b>>,
+    {
+        let started = Instant::now();
+        let source = self.encode(sources)?;
+        let target = self.encode(targets)?;
+        let input_tokens = source.iter().chain(&target).map(|t| t.len()).sum();
+        let res = self.translator.score_batch(&source, &target, options);
+        self.metrics
+            .observe(started, source.len(), input_tokens, res, |_| 0)
+    }
+
+    /// Records a translation request in the metrics.
+    fn observe(
+        &self,
+        started: Instant,
+        source: &[Vec<usize>],
+        res: Result<Vec<TranslatedText>>,
+    ) -> Result<Vec<TranslatedText>> {
+        let input_tokens = source.iter().map(|s| s.len()).sum();
+        self.metrics
+            .observe(started, source.len(), input_tokens, res, |results| {
+                results
+                    .iter()
+                    .flat_map(|r| r.ids.iter().map(|h| h.len()))
+                    .sum()
+            })
+    }
+
+    fn encode<'a, T>(&self, sources: Vec<T>) -> Result<Vec<Vec<String>>>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        sources
             .into_iter()
             .map(|s| {
                 self.tokenizer
                     .encode(s, true)
                     .map(|r| r.get_tokens().to_vec())
-                    .map_err(|err| anyhow!("failed to encode the given input: {err}"))
+                    .map_err(|err| Error::Encode(err.to_string()))
             })
-            .collect::<Result<Vec<Vec<String>>>>()?;
-
-        let output = self
-            .translator
-            .translate_batch(&tokens, &target_prefixes, options)?;
-
-        let decoder = self.tokenizer.get_decoder().unwrap();
-        let mut res = Vec::new();
-        for (r, prefix) in output.into_iter().zip(target_prefixes) {
-            let score = r.score();
-            match r.hypotheses.into_iter().next() {
-                None => bail!("no results are returned"),
-                Some(h) => {
-                    res.push((
-                        decoder
-                            .decode(h.into_iter().skip(prefix.len()).collect())
-                            .map_err(|err| anyhow!("failed to decode: {err}"))?,
-                        score,
-                    ));
-                }
-            }
-        }
-        Ok(res)
+            .collect()
+    }
+
+    /// Encodes the given sources to token IDs, which are passed to the model as is so that
+    /// they do not depend on how the tokens are spelled in the model vocabulary.
+    fn encode_ids<'a, T>(&self, sources: Vec<T>) -> Result<Vec<Vec<usize>>>
+    where
+        T: Into<EncodeInput<'a>>,
+    {
+        sources
+            .into_iter()
+            .map(|s| {
+                self.tokenizer
+                    .encode(s, true)
+                    .map(|r| r.get_ids().iter().map(|id| *id as usize).collect())
+                    .map_err(|err| Error::Encode(err.to_string()))
+            })
+            .collect()
+    }
+
+    /// Looks up the IDs of the given tokens with the tokenizer.
+    fn token_ids<U: AsRef<str>>(&self, tokens: &[Vec<U>]) -> Result<Vec<Vec<usize>>> {
+        tokens
+            .iter()
+            .map(|v| {
+                v.iter()
+                    .map(|token| {
+                        let token = token.as_ref();
+                        self.tokenizer
+                            .token_to_id(token)
+                            .map(|id| id as usize)
+                            .ok_or_else(|| Error::Encode(format!("unknown token: {token}")))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn decode(
+        &self,
+        output: Vec<TranslationResult>,
+        target_prefix: &[Vec<usize>],
+    ) -> Result<Vec<TranslatedText>> {
+        output
+            .into_iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let prefix_len = target_prefix.get(i).map_or(0, |p| p.len());
+                decode_result(&self.tokenizer, r, prefix_len)
+            })
+            .collect()
+    }
+}
+
+/// Decodes the hypotheses of a result without the target prefix of `prefix_len` tokens.
+fn decode_result(
+    tokenizer: &Tokenizer,
+    r: TranslationResult,
+    prefix_len: usize,
+) -> Result<TranslatedText> {
+    if r.hypotheses.is_empty() {
+        return Err(Error::Ffi("no results are returned".to_string()));
+    }
+    let tokens = r
+        .hypotheses
+        .into_iter()
+        .map(|h| h.into_iter().skip(prefix_len).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // The IDs of the target vocabulary are those of the tokenizer, like the IDs of the
+    // sources, so the tokens are not looked up in the tokenizer, which may not have them.
+    let ids = r
+        .hypotheses_ids
+        .into_iter()
+        .map(|h| h.into_iter().skip(prefix_len).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // The tokenizer joins the tokens with spaces when it has no decoder.
+    let texts = ids
+        .iter()
+        .map(|h| {
+            let h = h.iter().map(|id| *id as u32).collect::<Vec<_>>();
+            tokenizer.decode(&h, true)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::Decode(err.to_string()))?;
+    let attention = r
+        .attention
+        .into_iter()
+        .map(|a| a.into_iter().skip(prefix_len).collect())
+        .collect();
+
+    Ok(TranslatedText {
+        texts,
+        tokens,
+        ids,
+        scores: r.scores,
+        attention,
+    })
+}
+
+/// The translation hypotheses of a source text.
+///
+/// The target prefix, if given, is not included in the hypotheses.
+#[derive(Clone, Debug)]
+pub struct TranslatedText {
+    /// Decoded text of each hypothesis.
+    pub texts: Vec<String>,
+    /// Tokens of each hypothesis.
+    pub tokens: Vec<Vec<String>>,
+    /// Token IDs of each hypothesis.
+    pub ids: Vec<Vec<usize>>,
+    /// Score of each hypothesis (empty if `return_scores` was disabled).
+    pub scores: Vec<f32>,
+    /// Attention matrix of each hypothesis, of the shape `[target_length, source_length]`
+    /// (empty if `return_attention` was disabled).
+    pub attention: Vec<Vec<Vec<f32>>>,
+}
+
+impl TranslatedText {
+    /// Returns the text of the best hypothesis.
+    pub fn text(&self) -> &str {
+        self.texts.first().map_or("", |s| s.as_str())
+    }
+
+    /// Returns the score of the best hypothesis, if available.
+    pub fn score(&self) -> Option<f32> {
+        self.scores.first().copied()
+    }
+
+    /// Returns the number of hypotheses.
+    pub fn num_hypotheses(&self) -> usize {
+        self.texts.len()
     }
 }
 
-/// A text translator options.
-pub struct TranslationOptions<'a> {
-    /// Suppress sequences.
-    pub suppress_sequences: Vec<Vec<&'a str>>,
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use tokenizers::models::wordlevel::WordLevel;
+
+    use super::*;
+
+    #[test]
+    fn hypotheses_are_decoded_from_their_ids() {
+        let vocab = [("<unk>", 0), ("de", 1), ("Hallo", 2), ("Welt", 3)]
+            .iter()
+            .map(|(token, id)| (token.to_string(), *id))
+            .collect();
+        let tokenizer = Tokenizer::new(WordLevel::builder().vocab(vocab).build().unwrap());
+        // The target vocabulary of the model spells the tokens differently from the
+        // tokenizer.
+        let r = TranslationResult {
+            hypotheses: vec![
+                vec!["de".to_string(), "▁Hallo".to_string(), "▁Welt".to_string()],
+                vec!["de".to_string(), "▁Hallo".to_string()],
+            ],
+            hypotheses_ids: vec![vec![1, 2, 3], vec![1, 2]],
+            scores: vec![-0.5, -1.5],
+            attention: vec![],
+        };
+
+        let res = decode_result(&tokenizer, r, 1).unwrap();
+        assert_eq!(res.texts, ["Hallo Welt", "Hallo"]);
+        assert_eq!(res.ids, [vec![2, 3], vec![2]]);
+        assert_eq!(res.tokens[0], ["▁Hallo", "▁Welt"]);
+        assert_eq!(res.scores, [-0.5, -1.5]);
+    }
+}
@@ -1,9 +1,16 @@
 use cxx::UniquePtr;
 
-/// Model computation type or a dictionary mapping a device name to the computation type.
+use crate::config::{
+    builder_setters, validate_decoding, BatchType, ComputeType, Config, Device, EndToken,
+};
+use crate::device::check_config;
+use crate::error::{Error, Result};
+use crate::future::BatchFuture;
+use crate::scoring::{ScoringOptions, ScoringResult};
+use crate::source::ModelSource;
+
 #[cxx::bridge]
 mod ffi {
-
     struct TransVecStr<'a> {
         v: Vec<&'a str>,
     }
@@ -16,7 +23,15 @@ mod ffi {
         v: Vec<usize>,
     }
 
-    pub enum ComputeType {
+    struct TransVecF32 {
+        v: Vec<f32>,
+    }
+
+    struct TransAttention {
+        v: Vec<TransVecF32>,
+    }
+
+    enum TransComputeType {
         Default,
         Auto,
         Float32,
@@ -24,75 +39,737 @@ mod ffi {
         Int8Float16,
         Int16,
         Float16,
+        BFloat16,
+        Int8BFloat16,
+        Int8Float32,
     }
 
-    pub struct Config {
-        /// Model computation type or a dictionary mapping a device name to the computation type.
-        pub compute_type: ComputeType,
-        /// Device IDs where to place this generator on.
-        pub device_indices: Vec<i32>,
-        pub num_threads_per_replica: usize,
-        pub max_queued_batches: i64,
-        pub cpu_core_offset: i32,
+    struct TranslatorConfig {
+        compute_type: TransComputeType,
+        device_indices: Vec<i32>,
+        num_threads_per_replica: usize,
+        max_queued_batches: i64,
+        cpu_core_offset: i32,
     }
 
-    pub struct TranslationOptions<'a> {
-        suppress_sequences: Vec<TransVecStr<'a>>,
+    enum TranslationBatchType {
+        Examples,
+        Tokens,
     }
 
-    struct TraslationResult {
-        sequences: Vec<TransVecString>,
-        sequences_ids: Vec<TransVecUSize>,
-        scores: Vec<f32>,
+    struct TransScoringOptions {
+        max_input_length: usize,
+        offset: i64,
+        max_batch_size: usize,
+        batch_type: TranslationBatchType,
     }
 
-    /// Device to use.
-    #[derive(Debug)]
-    pub enum Device {
-        CPU,
-        CUDA,
+    struct TransScoringResult {
+        tokens: Vec<String>,
+        tokens_score: Vec<f32>,
+    }
+
+    enum TransEndTokenKind {
+        Default,
+        Token,
+        Tokens,
+        Ids,
+    }
+
+    struct TransEndToken<'a> {
+        kind: TransEndTokenKind,
+        tokens: Vec<&'a str>,
+        ids: Vec<usize>,
+    }
+
+    struct TranslationOptions<'a> {
+        beam_size: usize,
+        patience: f32,
+        length_penalty: f32,
+        coverage_penalty: f32,
+        repetition_penalty: f32,
+        no_repeat_ngram_size: usize,
+        disable_unk: bool,
+        suppress_sequences: Vec<TransVecStr<'a>>,
+        end_token: TransEndToken<'a>,
+        prefix_bias_beta: f32,
+        return_end_token: bool,
+        max_input_length: usize,
+        max_decoding_length: usize,
+        min_decoding_length: usize,
+        sampling_topk: usize,
+        sampling_topp: f32,
+        sampling_temperature: f32,
+        use_vmap: bool,
+        num_hypotheses: usize,
+        return_scores: bool,
+        return_attention: bool,
+        return_alternatives: bool,
+        min_alternative_expansion_prob: f32,
+        replace_unknowns: bool,
+        max_batch_size: usize,
+        batch_type: TranslationBatchType,
+    }
+
+    struct TranslationResult {
+        hypotheses: Vec<TransVecString>,
+        hypotheses_ids: Vec<TransVecUSize>,
+        scores: Vec<f32>,
+        attention: Vec<TransAttention>,
     }
 
     unsafe extern "C++" {
         include!("../../include/translator.h");
+        include!("../../include/source.h");
 
         type Translator;
+        type ModelReaderHandle = crate::source::ffi::ModelReaderHandle;
+        type TranslationStream;
 
         fn new_translator(
-            model_path: &str,
+            reader: &ModelReaderHandle,
             cuda: bool,
-            config: Config,
+            config: TranslatorConfig,
         ) -> Result<UniquePtr<Translator>>;
 
         fn translate_batch(
-            &self,
+            self: &Translator,
             source: Vec<TransVecStr>,
             target_prefix: Vec<TransVecStr>,
             options: TranslationOptions,
-        ) -> Result<Vec<TraslationResult>>;
+        ) -> Result<Vec<TranslationResult>>;
+
+        fn translate_batch_ids(
+            self: &Translator,
+            source: Vec<TransVecUSize>,
+            target_prefix: Vec<TransVecUSize>,
+            options: TranslationOptions,
+        ) -> Result<Vec<TranslationResult>>;
+
+        fn translate_batch_async(
+            self: &Translator,
+            source: Vec<TransVecStr>,
+            target_prefix: Vec<TransVecStr>,
+            options: TranslationOptions,
+        ) -> Result<UniquePtr<TranslationStream>>;
+
+        fn translate_batch_ids_async(
+            self: &Translator,
+            source: Vec<TransVecUSize>,
+            target_prefix: Vec<TransVecUSize>,
+            options: TranslationOptions,
+        ) -> Result<UniquePtr<TranslationStream>>;
+
+        fn score_batch(
+            self: &Translator,
+            source: Vec<TransVecStr>,
+            target: Vec<TransVecStr>,
+            options: TransScoringOptions,
+        ) -> Result<Vec<TransScoringResult>>;
+
+        fn num_replicas(self: &Translator) -> usize;
+
+        fn num_queued_batches(self: &Translator) -> usize;
+
+        fn num_active_batches(self: &Translator) -> usize;
+
+        fn unload_model(self: &Translator, to_cpu: bool) -> Result<bool>;
+
+        fn load_model(self: &Translator) -> Result<()>;
+
+        fn model_is_loaded(self: &Translator) -> bool;
+
+        fn wait(self: Pin<&mut TranslationStream>) -> Result<Vec<TranslationResult>>;
     }
 }
 
+/// A text translator.
 pub struct Translator {
     ptr: UniquePtr<ffi::Translator>,
 }
 
-// impl Default for Config {
-//     fn default() -> Self {
-//         Self {
-//             compute_type: Default::default(),
-//             device_indices: vec![0],
-//             num_threads_per_replica: 0,
-//             max_queued_batches: 0,
-//             cpu_core_offset: -1,
-//         }
-//     }
-// }
-
-/// Whether max_batch_size is the number of “examples” or “tokens”.
-#[derive(Debug, Default)]
-pub enum BatchType {
-    #[default]
-    Examples,
-    Tokens,
+impl Translator {
+    pub fn new(source: &ModelSource, device: Device, config: Config) -> Result<Translator> {
+        let (device, compute_type) = check_config(device, &config)?;
+        Ok(Translator {
+            ptr: ffi::new_translator(
+                &*source.to_ffi()?,
+                device == Device::CUDA,
+                ffi::TranslatorConfig {
+                    compute_type: match compute_type {
+                        ComputeType::Default => ffi::TransComputeType::Default,
+                        ComputeType::Auto => ffi::TransComputeType::Auto,
+                        ComputeType::Float32 => ffi::TransComputeType::Float32,
+                        ComputeType::Int8 => ffi::TransComputeType::Int8,
+                        ComputeType::Int8Float16 => ffi::TransComputeType::Int8Float16,
+                        ComputeType::Int16 => ffi::TransComputeType::Int16,
+                        ComputeType::Float16 => ffi::TransComputeType::Float16,
+                        ComputeType::BFloat16 => ffi::TransComputeType::BFloat16,
+                        ComputeType::Int8BFloat16 => ffi::TransComputeType::Int8BFloat16,
+                        ComputeType::Int8Float32 => ffi::TransComputeType::Int8Float32,
+                    },
+                    device_indices: config.device_indices,
+                    num_threads_per_replica: config.num_threads_per_replica,
+                    max_queued_batches: config.max_queued_batches,
+                    cpu_core_offset: config.cpu_core_offset,
+                },
+            )
+            .map_err(Error::model_load)?,
+        })
+    }
+
+    /// Translates a batch of tokens.
+    ///
+    /// `source` is a batch of source tokens and `target_prefix` is an optional batch of target
+    /// prefixes; pass an empty slice to translate without prefixes.
+    pub fn translate_batch<T: AsRef<str>, U: AsRef<str>, V: AsRef<str>>(
+        &self,
+        source: &[Vec<T>],
+        target_prefix: &[Vec<U>],
+        options: &TranslationOptions<V>,
+    ) -> Result<Vec<TranslationResult>> {
+        Ok(self
+            .ptr
+            .translate_batch(
+                vec_ffi_vecstr(source),
+                vec_ffi_vecstr(target_prefix),
+                options.to_ffi(),
+            )?
+            .into_iter()
+            .map(TranslationResult::from)
+            .collect())
+    }
+
+    /// Translates a batch of token IDs.
+    ///
+    /// This is the same as [`Translator::translate_batch`], but the source and target prefix
+    /// are given as IDs of the model vocabularies.
+    pub fn translate_batch_ids<V: AsRef<str>>(
+        &self,
+        source: &[Vec<usize>],
+        target_prefix: &[Vec<usize>],
+        options: &TranslationOptions<V>,
+    ) -> Result<Vec<TranslationResult>> {
+        Ok(self
+            .ptr
+            .translate_batch_ids(
+                vec_ffi_vecusize(source),
+                vec_ffi_vecusize(target_prefix),
+                options.to_ffi(),
+            )?
+            .into_iter()
+            .map(TranslationResult::from)
+            .collect())
+    }
+
+    /// Translates a batch of tokens without blocking the calling thread.
+    ///
+    /// The returned future resolves once every example of the batch has been translated.
+    pub fn translate_batch_async<T: AsRef<str>, U: AsRef<str>, V: AsRef<str>>(
+        &self,
+        source: &[Vec<T>],
+        target_prefix: &[Vec<U>],
+        options: &TranslationOptions<V>,
+    ) -> Result<BatchFuture<Vec<TranslationResult>>> {
+        let stream = TranslationStream {
+            ptr: self.ptr.translate_batch_async(
+                vec_ffi_vecstr(source),
+                vec_ffi_vecstr(target_prefix),
+                options.to_ffi(),
+            )?,
+        };
+        Ok(BatchFuture::spawn(move || stream.wait()))
+    }
+
+    /// Translates a batch of token IDs without blocking the calling thread.
+    ///
+    /// See [`Translator::translate_batch_async`].
+    pub fn translate_batch_ids_async<V: AsRef<str>>(
+        &self,
+        source: &[Vec<usize>],
+        target_prefix: &[Vec<usize>],
+        options: &TranslationOptions<V>,
+    ) -> Result<BatchFuture<Vec<TranslationResult>>> {
+        let stream = TranslationStream {
+            ptr: self.ptr.translate_batch_ids_async(
+                vec_ffi_vecusize(source),
+                vec_ffi_vecusize(target_prefix),
+                options.to_ffi(),
+            )?,
+        };
+        Ok(BatchFuture::spawn(move || stream.wait()))
+    }
+
+    /// Scores a batch of target tokens given their source tokens.
+    pub fn score_batch<T: AsRef<str>, U: AsRef<str>>(
+        &self,
+        source: &[Vec<T>],
+        target: &[Vec<U>],
+        options: &ScoringOptions,
+    ) -> Result<Vec<ScoringResult>> {
+        Ok(self
+            .ptr
+            .score_batch(
+                vec_ffi_vecstr(source),
+                vec_ffi_vecstr(target),
+                scoring_options_to_ffi(options),
+            )?
+            .into_iter()
+            .map(|r| ScoringResult {
+                tokens: r.tokens,
+                tokens_score: r.tokens_score,
+            })
+            .collect())
+    }
+
+    /// Number of parallel replicas.
+    pub fn num_replicas(&self) -> usize {
+        self.ptr.num_replicas()
+    }
+
+    /// Number of batches in the work queue.
+    pub fn num_queued_batches(&self) -> usize {
+        self.ptr.num_queued_batches()
+    }
+
+    /// Number of batches in the work queue or currently processed by a worker.
+    pub fn num_active_batches(&self) -> usize {
+        self.ptr.num_active_batches()
+    }
+
+    /// Unloads the model, keeping it in the CPU memory if `to_cpu` is set.
+    ///
+    /// Returns false if some batches are still queued or running, in which case the model is
+    /// kept loaded.
+    pub fn unload_model(&self, to_cpu: bool) -> Result<bool> {
+        Ok(self.ptr.unload_model(to_cpu)?)
+    }
+
+    /// Loads the model again after [`unload_model`](Self::unload_model).
+    pub fn load_model(&self) -> Result<()> {
+        self.ptr.load_model().map_err(Error::model_load)
+    }
+
+    /// Returns true if the model is loaded.
+    pub fn model_is_loaded(&self) -> bool {
+        self.ptr.model_is_loaded()
+    }
+}
+
+// The C++ `Translator` only holds a `std::shared_ptr<ctranslate2::Translator>`, and all its
+// methods are `const`. `ctranslate2::Translator` is a pool of replicas: the batches are
+// pushed to a queue guarded by a mutex, and each replica is only used by its own worker
+// thread. The vocabularies read to map token IDs in `cpp/translator.cc` are immutable once
+// the model is loaded.
+unsafe impl Send for ffi::Translator {}
+unsafe impl Sync for ffi::Translator {}
+
+/// A translation running in the background.
+struct TranslationStream {
+    ptr: UniquePtr<ffi::TranslationStream>,
+}
+
+// A `TranslationStream` only holds the futures of the submitted batches, which can be waited
+// for from any thread.
+unsafe impl Send for ffi::TranslationStream {}
+
+impl TranslationStream {
+    /// Blocks until the translation finishes and returns the results.
+    fn wait(mut self) -> Result<Vec<TranslationResult>> {
+        Ok(self
+            .ptr
+            .pin_mut()
+            .wait()?
+            .into_iter()
+            .map(TranslationResult::from)
+            .collect())
+    }
+}
+
+/// The set of translation options.
+///
+/// [`TranslationOptions::builder`] checks the options when they are built.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        default,
+        bound(deserialize = "T: serde::Deserialize<'de>, TranslationOptions<T>: Default")
+    )
+)]
+pub struct TranslationOptions<T: AsRef<str>> {
+    /// Beam size to use for beam search (set 1 to run greedy search).
+    pub beam_size: usize,
+    /// Beam search patience factor, as described in <https://arxiv.org/abs/2204.05424>.
+    /// The decoding will continue until beam_size*patience hypotheses are finished.
+    pub patience: f32,
+    /// Exponential penalty applied to the length during beam search.
+    /// The scores are normalized with:
+    /// ```math
+    ///   hypothesis_score /= (hypothesis_length ** length_penalty)
+    /// ```
+    pub length_penalty: f32,
+    /// Coverage penalty weight applied during beam search.
+    pub coverage_penalty: f32,
+    /// Penalty applied to the score of previously generated tokens, as described in
+    /// <https://arxiv.org/abs/1909.05858> (set > 1 to penalize).
+    pub repetition_penalty: f32,
+    /// Prevent repetitions of ngrams with this size (set 0 to disable).
+    pub no_repeat_ngram_size: usize,
+    /// Disable the generation of the unknown token.
+    pub disable_unk: bool,
+    /// Disable the generation of some sequences of tokens.
+    pub suppress_sequences: Vec<Vec<T>>,
+    /// Stop the decoding on one of these tokens (defaults to the model EOS token).
+    pub end_token: Option<EndToken>,
+    /// Biases decoding towards a given prefix, see <https://arxiv.org/abs/1912.03393> --section 4.2
+    /// Only activates biased-decoding when beta is in range (0, 1) and SearchStrategy is set to BeamSearch.
+    /// The closer beta is to 1, the stronger the bias is towards the given prefix.
+    ///
+    /// If beta <= 0 and a non-empty prefix is given, then the prefix will be used as a
+    /// hard-prefix rather than a soft, biased-prefix.
+    pub prefix_bias_beta: f32,
+    /// Include the end token in the result.
+    pub return_end_token: bool,
+    /// Truncate the inputs after this many tokens (set 0 to disable truncation).
+    pub max_input_length: usize,
+    /// Decoding length constraints.
+    pub max_decoding_length: usize,
+    /// Decoding length constraints.
+    pub min_decoding_length: usize,
+    /// Randomly sample from the top K candidates (set 0 to sample from the full output distribution).
+    pub sampling_topk: usize,
+    /// Keep the most probable tokens whose cumulative probability exceeds this value.
+    pub sampling_topp: f32,
+    /// High temperature increase randomness.
+    pub sampling_temperature: f32,
+    /// Allow using the vocabulary map included in the model directory, if it exists.
+    pub use_vmap: bool,
+    /// Number of hypotheses to include in the result.
+    pub num_hypotheses: usize,
+    /// Include scores in the result.
+    pub return_scores: bool,
+    /// Include the attention vectors in the result.
+    pub return_attention: bool,
+    /// Return alternatives at the first unconstrained decoding position. This is typically
+    /// used with a target prefix to provide alternatives at a specifc location in the
+    /// translation.
+    pub return_alternatives: bool,
+    /// Minimum probability to expand an alternative.
+    pub min_alternative_expansion_prob: f32,
+    /// Replace unknown target tokens by the original source token with the highest attention.
+    pub replace_unknowns: bool,
+    /// The maximum batch size. If the number of inputs is greater than `max_batch_size`,
+    /// the inputs are sorted by length and split by chunks of `max_batch_size` examples
+    /// so that the number of padding positions is minimized.
+    pub max_batch_size: usize,
+    /// Whether `max_batch_size` is the number of `examples` or `tokens`.
+    pub batch_type: BatchType,
+}
+
+impl Default for TranslationOptions<String> {
+    fn default() -> Self {
+        Self {
+            beam_size: 2,
+            patience: 1.,
+            length_penalty: 1.,
+            coverage_penalty: 0.,
+            repetition_penalty: 1.,
+            no_repeat_ngram_size: 0,
+            disable_unk: false,
+            suppress_sequences: vec![],
+            end_token: None,
+            prefix_bias_beta: 0.,
+            return_end_token: false,
+            max_input_length: 1024,
+            max_decoding_length: 256,
+            min_decoding_length: 1,
+            sampling_topk: 1,
+            sampling_topp: 1.,
+            sampling_temperature: 1.,
+            use_vmap: false,
+            num_hypotheses: 1,
+            return_scores: false,
+            return_attention: false,
+            return_alternatives: false,
+            min_alternative_expansion_prob: 0.,
+            replace_unknowns: false,
+            max_batch_size: 0,
+            batch_type: Default::default(),
+        }
+    }
+}
+
+impl TranslationOptions<String> {
+    /// Returns a builder of translation options, starting from the default values.
+    pub fn builder() -> TranslationOptionsBuilder {
+        TranslationOptionsBuilder::default()
+    }
+}
+
+impl<T: AsRef<str>> TranslationOptions<T> {
+    /// Checks the options, returning [`Error::InvalidOptions`] if they cannot be used.
+    pub fn validate(&self) -> Result<()> {
+        validate_decoding(
+            self.beam_size,
+            self.num_hypotheses,
+            self.sampling_topp,
+            self.min_decoding_length,
+            self.max_decoding_length,
+        )
+    }
+
+    #[inline]
+    fn to_ffi(&self) -> ffi::TranslationOptions {
+        ffi::TranslationOptions {
+            beam_size: self.beam_size,
+            patience: self.patience,
+            length_penalty: self.length_penalty,
+            coverage_penalty: self.coverage_penalty,
+            repetition_penalty: self.repetition_penalty,
+            no_repeat_ngram_size: self.no_repeat_ngram_size,
+            disable_unk: self.disable_unk,
+            suppress_sequences: vec_ffi_vecstr(self.suppress_sequences.as_ref()),
+            end_token: ffi_end_token(self.end_token.as_ref()),
+            prefix_bias_beta: self.prefix_bias_beta,
+            return_end_token: self.return_end_token,
+            max_input_length: self.max_input_length,
+            max_decoding_length: self.max_decoding_length,
+            min_decoding_length: self.min_decoding_length,
+            sampling_topk: self.sampling_topk,
+            sampling_topp: self.sampling_topp,
+            sampling_temperature: self.sampling_temperature,
+            use_vmap: self.use_vmap,
+            num_hypotheses: self.num_hypotheses,
+            return_scores: self.return_scores,
+            return_attention: self.return_attention,
+            return_alternatives: self.return_alternatives,
+            min_alternative_expansion_prob: self.min_alternative_expansion_prob,
+            replace_unknowns: self.replace_unknowns,
+            max_batch_size: self.max_batch_size,
+            batch_type: match self.batch_type {
+                BatchType::Examples => ffi::TranslationBatchType::Examples,
+                BatchType::Tokens => ffi::TranslationBatchType::Tokens,
+            },
+        }
+    }
+}
+
+/// Builds [`TranslationOptions`], checking them.
+///
+/// ```
+/// use ctrans2::TranslationOptions;
+///
+/// let options = TranslationOptions::builder()
+///     .beam_size(4)
+///     .max_decoding_length(128)
+///     .build()
+///     .unwrap();
+/// assert!(TranslationOptions::builder()
+///     .min_decoding_length(10)
+///     .max_decoding_length(5)
+///     .build()
+///     .is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TranslationOptionsBuilder {
+    options: TranslationOptions<String>,
+}
+
+impl TranslationOptionsBuilder {
+    builder_setters!(options: TranslationOptions {
+        beam_size: usize,
+        patience: f32,
+        length_penalty: f32,
+        coverage_penalty: f32,
+        repetition_penalty: f32,
+        no_repeat_ngram_size: usize,
+        disable_unk: bool,
+        suppress_sequences: Vec<Vec<String>>,
+        prefix_bias_beta: f32,
+        return_end_token: bool,
+        max_input_length: usize,
+        max_decoding_length: usize,
+        min_decoding_length: usize,
+        sampling_topk: usize,
+        sampling_topp: f32,
+        sampling_temperature: f32,
+        use_vmap: bool,
+        num_hypotheses: usize,
+        return_scores: bool,
+        return_attention: bool,
+        return_alternatives: bool,
+        min_alternative_expansion_prob: f32,
+        replace_unknowns: bool,
+        max_batch_size: usize,
+        batch_type: BatchType,
+    });
+
+    /// Sets [`TranslationOptions::end_token`].
+    pub fn end_token(mut self, end_token: EndToken) -> Self {
+        self.options.end_token = Some(end_token);
+        self
+    }
+
+    /// Returns the options, or [`Error::InvalidOptions`] if they are invalid.
+    pub fn build(self) -> Result<TranslationOptions<String>> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+}
+
+impl From<TranslationOptions<String>> for TranslationOptionsBuilder {
+    fn from(options: TranslationOptions<String>) -> Self {
+        Self { options }
+    }
+}
+
+/// A translation result.
+#[derive(Debug)]
+pub struct TranslationResult {
+    /// Translation hypotheses.
+    pub hypotheses: Vec<Vec<String>>,
+    /// Token IDs of each translation hypothesis in the target vocabulary of the model.
+    pub hypotheses_ids: Vec<Vec<usize>>,
+    /// Score of each translation hypothesis (empty if `return_scores` was disabled).
+    pub scores: Vec<f32>,
+    /// Attention matrix of each translation hypothesis (empty if `return_attention` was disabled).
+    pub attention: Vec<Vec<Vec<f32>>>,
+}
+
+impl From<ffi::TranslationResult> for TranslationResult {
+    fn from(res: ffi::TranslationResult) -> Self {
+        Self {
+            hypotheses: res.hypotheses.into_iter().map(|c| c.v).collect(),
+            hypotheses_ids: res.hypotheses_ids.into_iter().map(|c| c.v).collect(),
+            scores: res.scores,
+            attention: res
+                .attention
+                .into_iter()
+                .map(|a| a.v.into_iter().map(|c| c.v).collect())
+                .collect(),
+        }
+    }
+}
+
+impl TranslationResult {
+    /// Returns the first translation hypothesis if exists.
+    pub fn output(&self) -> Option<&Vec<String>> {
+        self.hypotheses.first()
+    }
+
+    /// Returns the score of the first translation hypothesis if exists.
+    pub fn score(&self) -> Option<f32> {
+        self.scores.first().copied()
+    }
+
+    /// Returns the number of translation hypotheses.
+    pub fn num_hypotheses(&self) -> usize {
+        self.hypotheses.len()
+    }
+
+    /// Returns true if this result has scores.
+    pub fn has_scores(&self) -> bool {
+        !self.scores.is_empty()
+    }
+
+    /// Returns true if this result has attention vectors.
+    pub fn has_attention(&self) -> bool {
+        !self.attention.is_empty()
+    }
+}
+
+#[inline]
+fn scoring_options_to_ffi(options: &ScoringOptions) -> ffi::TransScoringOptions {
+    ffi::TransScoringOptions {
+        max_input_length: options.max_input_length,
+        offset: options.offset,
+        max_batch_size: options.max_batch_size,
+        batch_type: match options.batch_type {
+            BatchType::Examples => ffi::TranslationBatchType::Examples,
+            BatchType::Tokens => ffi::TranslationBatchType::Tokens,
+        },
+    }
+}
+
+#[inline]
+fn ffi_end_token(end_token: Option<&EndToken>) -> ffi::TransEndToken {
+    match end_token {
+        None => ffi::TransEndToken {
+            kind: ffi::TransEndTokenKind::Default,
+            tokens: vec![],
+            ids: vec![],
+        },
+        Some(EndToken::Token(token)) => ffi::TransEndToken {
+            kind: ffi::TransEndTokenKind::Token,
+            tokens: vec![token.as_str()],
+            ids: vec![],
+        },
+        Some(EndToken::Tokens(tokens)) => ffi::TransEndToken {
+            kind: ffi::TransEndTokenKind::Tokens,
+            tokens: tokens.iter().map(|s| s.as_str()).collect(),
+            ids: vec![],
+        },
+        Some(EndToken::Ids(ids)) => ffi::TransEndToken {
+            kind: ffi::TransEndTokenKind::Ids,
+            tokens: vec![],
+            ids: ids.clone(),
+        },
+    }
+}
+
+#[inline]
+fn vec_ffi_vecstr<T: AsRef<str>>(src: &[Vec<T>]) -> Vec<ffi::TransVecStr> {
+    src.iter()
+        .map(|v| ffi::TransVecStr {
+            v: v.iter().map(|s| s.as_ref()).collect(),
+        })
+        .collect()
+}
+
+#[inline]
+fn vec_ffi_vecusize(src: &[Vec<usize>]) -> Vec<ffi::TransVecUSize> {
+    src.iter()
+        .map(|v| ffi::TransVecUSize { v: v.clone() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_rejects_invalid_options() {
+        assert!(TranslationOptions::builder().build().is_ok());
+        for builder in [
+            TranslationOptions::builder().beam_size(0),
+            TranslationOptions::builder().num_hypotheses(0),
+            TranslationOptions::builder().sampling_topp(1.5),
+            TranslationOptions::builder()
+                .min_decoding_length(16)
+                .max_decoding_length(8),
+        ] {
+            assert!(matches!(builder.build(), Err(Error::InvalidOptions(_))));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn options_round_trip() {
+        let options = TranslationOptions::builder()
+            .beam_size(4)
+            .end_token(EndToken::Ids(vec![2]))
+            .build()
+            .unwrap();
+        let json = serde_json::to_value(&options).unwrap();
+        let decoded = serde_json::from_value::<TranslationOptions<String>>(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        assert_eq!(decoded.end_token, Some(EndToken::Ids(vec![2])));
+
+        let partial =
+            serde_json::from_str::<TranslationOptions<String>>(r#"{"beam_size": 2}"#).unwrap();
+        assert_eq!(partial.beam_size, 2);
+        assert_eq!(partial.num_hypotheses, 1);
+    }
 }
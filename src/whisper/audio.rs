@@ -0,0 +1,192 @@
+//! Log-mel feature extraction compatible with the original Whisper implementation.
+
+use std::f64::consts::PI;
+
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+/// Sample rate of the audio expected by Whisper.
+pub const SAMPLE_RATE: usize = 16000;
+/// Number of samples in a 30-second chunk.
+pub const N_SAMPLES: usize = SAMPLE_RATE * 30;
+/// Number of frames in a 30-second chunk.
+pub const N_FRAMES: usize = N_SAMPLES / HOP_LENGTH;
+/// Number of samples between two successive frames.
+pub const HOP_LENGTH: usize = 160;
+
+const N_FFT: usize = 400;
+const N_FREQS: usize = N_FFT / 2 + 1;
+
+/// Returns the mel filterbank matrix of the shape `[n_mels, N_FFT / 2 + 1]`, as computed by
+/// `librosa.filters.mel(sr=16000, n_fft=400, n_mels=n_mels)`.
+pub fn mel_filters(n_mels: usize) -> Vec<f32> {
+    let fft_freqs = (0..N_FREQS)
+        .map(|i| i as f64 * SAMPLE_RATE as f64 / N_FFT as f64)
+        .collect::<Vec<_>>();
+
+    let max_mel = hz_to_mel(SAMPLE_RATE as f64 / 2.);
+    let mel_freqs = (0..n_mels + 2)
+        .map(|i| mel_to_hz(max_mel * i as f64 / (n_mels + 1) as f64))
+        .collect::<Vec<_>>();
+
+    let mut filters = vec![0.; n_mels * N_FREQS];
+    for i in 0..n_mels {
+        let lower_width = mel_freqs[i + 1] - mel_freqs[i];
+        let upper_width = mel_freqs[i + 2] - mel_freqs[i + 1];
+        // Slaney-style normalization to get an approximately constant energy per channel.
+        let norm = 2. / (mel_freqs[i + 2] - mel_freqs[i]);
+        for (j, f) in fft_freqs.iter().enumerate() {
+            let lower = (f - mel_freqs[i]) / lower_width;
+            let upper = (mel_freqs[i + 2] - f) / upper_width;
+            filters[i * N_FREQS + j] = (lower.min(upper).max(0.) * norm) as f32;
+        }
+    }
+    filters
+}
+
+/// Computes the log-mel spectrogram of the shape `[n_mels, N_FRAMES]` from 16 kHz mono samples.
+///
+/// The samples are padded or trimmed to 30 seconds, and `filters` is the matrix returned by
+/// [`mel_filters`].
+pub fn log_mel_spectrogram(samples: &[f32], filters: &[f32]) -> Vec<f32> {
+    let n_mels = filters.len() / N_FREQS;
+
+    let mut audio = samples[..samples.len().min(N_SAMPLES)].to_vec();
+    audio.resize(N_SAMPLES, 0.);
+
+    // Reflect padding, as done by `torch.stft(center=True)`.
+    let pad = N_FFT / 2;
+    let mut padded = Vec::with_capacity(N_SAMPLES + 2 * pad);
+    padded.extend((1..=pad).rev().map(|i| audio[i]));
+    padded.extend_from_slice(&audio);
+    padded.extend((N_SAMPLES - 1 - pad..N_SAMPLES - 1).rev().map(|i| audio[i]));
+
+    let window = (0..N_FFT)
+        .map(|i| (0.5 - 0.5 * (2. * PI * i as f64 / N_FFT as f64).cos()) as f32)
+        .collect::<Vec<_>>();
+    let fft = FftPlanner::new().plan_fft_forward(N_FFT);
+
+    let mut mel = vec![0f32; n_mels * N_FRAMES];
+    let mut buffer = vec![Complex::new(0f32, 0f32); N_FFT];
+    let mut power = vec![0f32; N_FREQS];
+    for t in 0..N_FRAMES {
+        let frame = &padded[t * HOP_LENGTH..t * HOP_LENGTH + N_FFT];
+        for ((b, x), w) in buffer.iter_mut().zip(frame).zip(&window) {
+            *b = Complex::new(x * w, 0.);
+        }
+        fft.process(&mut buffer);
+        for (p, b) in power.iter_mut().zip(&buffer) {
+            *p = b.norm_sqr();
+        }
+        for m in 0..n_mels {
+            mel[m * N_FRAMES + t] = filters[m * N_FREQS..(m + 1) * N_FREQS]
+                .iter()
+                .zip(&power)
+                .map(|(f, p)| f * p)
+                .sum();
+        }
+    }
+
+    for v in mel.iter_mut() {
+        *v = v.max(1e-10).log10();
+    }
+    let max = mel.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    for v in mel.iter_mut() {
+        *v = (v.max(max - 8.) + 4.) / 4.;
+    }
+    mel
+}
+
+const MIN_LOG_HZ: f64 = 1000.;
+const F_SP: f64 = 200. / 3.;
+
+#[inline]
+fn log_step() -> f64 {
+    6.4f64.ln() / 27.
+}
+
+/// Converts Hz to mels with the Slaney formula.
+fn hz_to_mel(freq: f64) -> f64 {
+    if freq >= MIN_LOG_HZ {
+        MIN_LOG_HZ / F_SP + (freq / MIN_LOG_HZ).ln() / log_step()
+    } else {
+        freq / F_SP
+    }
+}
+
+/// Converts mels to Hz with the Slaney formula.
+fn mel_to_hz(mel: f64) -> f64 {
+    let min_log_mel = MIN_LOG_HZ / F_SP;
+    if mel >= min_log_mel {
+        MIN_LOG_HZ * (log_step() * (mel - min_log_mel)).exp()
+    } else {
+        F_SP * mel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples of a sine wave at 16 kHz.
+    fn sine(freq: f32, seconds: usize) -> Vec<f32> {
+        (0..SAMPLE_RATE * seconds)
+            .map(|i| (2. * std::f32::consts::PI * freq * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn filters_have_one_row_per_mel() {
+        for n_mels in [80, 128] {
+            let filters = mel_filters(n_mels);
+            assert_eq!(filters.len(), n_mels * N_FREQS);
+            assert!(filters.iter().all(|f| *f >= 0.));
+            for (m, row) in filters.chunks(N_FREQS).enumerate() {
+                assert!(row.iter().sum::<f32>() > 0., "row {m} of {n_mels} is empty");
+            }
+        }
+    }
+
+    #[test]
+    fn filter_rows_have_constant_energy() {
+        // With the Slaney normalization, each row sums to about the inverse of the 40 Hz
+        // spacing of the FFT bins once the filters are wider than the bins.
+        let filters = mel_filters(80);
+        for (m, row) in filters.chunks(N_FREQS).enumerate() {
+            let sum = row.iter().sum::<f32>();
+            assert!((sum - 1. / 40.).abs() < 0.003, "row {m}: {sum}");
+        }
+    }
+
+    #[test]
+    fn silence_is_the_minimum_value() {
+        let filters = mel_filters(80);
+        let mel = log_mel_spectrogram(&vec![0.; SAMPLE_RATE], &filters);
+        assert_eq!(mel.len(), 80 * N_FRAMES);
+        // log10 of the 1e-10 floor, scaled as (v + 4) / 4.
+        assert!(mel.iter().all(|v| (*v + 1.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn tone_peaks_in_its_mel_band() {
+        let filters = mel_filters(80);
+        let mel = log_mel_spectrogram(&sine(1000., 1), &filters);
+        // A frame in the middle of the tone.
+        let t = 50;
+        let peak = (0..80)
+            .max_by(|a, b| mel[a * N_FRAMES + t].total_cmp(&mel[b * N_FRAMES + t]))
+            .unwrap();
+        let row = &filters[peak * N_FREQS..(peak + 1) * N_FREQS];
+        // The bin of 1 kHz is 1000 / 40 = 25.
+        assert!(row[25] > 0., "peak at mel {peak}");
+    }
+
+    #[test]
+    fn samples_are_truncated_to_30_seconds() {
+        let filters = mel_filters(80);
+        let samples = sine(440., 40);
+        let truncated = log_mel_spectrogram(&samples[..N_SAMPLES], &filters);
+        assert_eq!(log_mel_spectrogram(&samples, &filters), truncated);
+        assert_eq!(truncated.len(), 80 * N_FRAMES);
+    }
+}
@@ -0,0 +1,379 @@
+use std::path::Path;
+
+use tokenizers::Tokenizer;
+
+use crate::config::{Config, Device};
+use crate::error::{Error, Result};
+use crate::model::{Architecture, ModelDir};
+pub use self::audio::SAMPLE_RATE;
+use self::audio::{log_mel_spectrogram, mel_filters, HOP_LENGTH, N_FRAMES};
+use self::whisper::WhisperGenerationResult;
+pub use self::whisper::WhisperOptions;
+
+mod audio;
+mod whisper;
+
+const TOKENIZER_FILENAME: &str = "tokenizer.json";
+
+const SOT_TOKEN: &str = "<|startoftranscript|>";
+const TRANSCRIBE_TOKEN: &str = "<|transcribe|>";
+const NO_TIMESTAMPS_TOKEN: &str = "<|notimestamps|>";
+const TIMESTAMP_BEGIN_TOKEN: &str = "<|0.00|>";
+
+/// Number of alignment time indices per second.
+const TOKENS_PER_SECOND: f32 = (SAMPLE_RATE / HOP_LENGTH / 2) as f32;
+const MEDIAN_FILTER_WIDTH: i64 = 7;
+
+/// A speech recognition model with a tokenizer.
+pub struct Whisper {
+    whisper: self::whisper::Whisper,
+    tokenizer: Tokenizer,
+    mel_filters: Vec<f32>,
+}
+
+impl Whisper {
+    /// Initializes the model and tokenizer.
+    pub fn new<T: AsRef<Path>>(path: T, device: Device, config: Config) -> Result<Whisper> {
+        let dir = ModelDir::open_as(path, Architecture::Whisper)?;
+        Whisper::with_tokenizer(
+            dir.path(),
+            device,
+            config,
+            Tokenizer::from_file(dir.join(TOKENIZER_FILENAME))
+                .map_err(|err| Error::Tokenizer(err.to_string()))?,
+        )
+    }
+
+    /// Initializes the model with the given tokenizer.
+    pub fn with_tokenizer<T: AsRef<Path>>(
+        path: T,
+        device: Device,
+        config: Config,
+        tokenizer: Tokenizer,
+    ) -> Result<Whisper> {
+        let dir = ModelDir::open_as(path, Architecture::Whisper)?;
+        let whisper = self::whisper::Whisper::new(dir.path_str(), device, config)?;
+        let mel_filters = mel_filters(whisper.n_mels());
+        Ok(Whisper {
+            whisper,
+            tokenizer,
+            mel_filters,
+        })
+    }
+
+    /// Returns true if the model is multilingual.
+    pub fn is_multilingual(&self) -> bool {
+        self.whisper.is_multilingual()
+    }
+
+    /// Returns the number of languages supported by the model.
+    pub fn num_languages(&self) -> usize {
+        self.whisper.num_languages()
+    }
+
+    /// Computes the log-mel features of the given 16 kHz mono samples.
+    ///
+    /// Only the first 30 seconds are used; shorter inputs are padded with silence.
+    pub fn features(&self, samples: &[f32]) -> Vec<f32> {
+        log_mel_spectrogram(samples, &self.mel_filters)
+    }
+
+    /// Detects the language spoken in the given samples.
+    ///
+    /// Returns language codes such as `en` with their probability, sorted by decreasing
+    /// probability.
+    pub fn detect_language(&self, samples: &[f32]) -> Result<Vec<(String, f32)>> {
+        self.detect_language_from_features(&self.features(samples))
+    }
+
+    /// Transcribes the given 16 kHz mono samples.
+    ///
+    /// `language` is a language code such as `en`; it is detected from the samples if not given
+    /// and the model is multilingual. The scores and the probability of no speech are returned
+    /// if requested by `options`.
+    pub fn generate(
+        &self,
+        samples: &[f32],
+        language: Option<&str>,
+        options: &WhisperOptions,
+    ) -> Result<TranscribedText> {
+        let features = self.features(samples);
+
+        let mut prompt = self.sot_sequence(&features, language)?;
+        if !options.timestamps {
+            prompt.push(NO_TIMESTAMPS_TOKEN.to_string());
+        }
+        if let Some(prefix) = &options.prefix {
+            if options.timestamps {
+                prompt.push(TIMESTAMP_BEGIN_TOKEN.to_string());
+            }
+            prompt.extend(
+                self.tokenizer
+                    .encode(format!(" {}", prefix.trim()), false)
+                    .map_err(|err| Error::Encode(err.to_string()))?
+                    .get_tokens()
+                    .iter()
+                    .cloned(),
+            );
+        }
+
+        let output = self
+            .whisper
+            .generate(&features, self.shape(), &[prompt], options)?;
+        let Some(r) = output.into_iter().next() else {
+            return Err(Error::Ffi("no results are returned".to_string()));
+        };
+        // The timestamps are special tokens, kept in the texts when they are predicted.
+        decode(&self.tokenizer, r, !options.timestamps)
+    }
+
+    /// Aligns the words of the given transcription with the given 16 kHz mono samples.
+    ///
+    /// `language` is handled as in [`Whisper::generate`].
+    pub fn align(
+        &self,
+        samples: &[f32],
+        language: Option<&str>,
+        text: &str,
+    ) -> Result<Vec<WordTimestamp>> {
+        let features = self.features(samples);
+
+        let mut start_sequence = self.sot_sequence(&features, language)?;
+        start_sequence.push(NO_TIMESTAMPS_TOKEN.to_string());
+        let start_sequence = start_sequence
+            .iter()
+            .map(|token| self.token_id(token))
+            .collect::<Result<Vec<_>>>()?;
+
+        let text_tokens = self
+            .tokenizer
+            .encode(format!(" {}", text.trim()), false)
+            .map_err(|err| Error::Encode(err.to_string()))?
+            .get_ids()
+            .iter()
+            .map(|id| *id as usize)
+            .collect::<Vec<_>>();
+        let num_frames = (samples.len() / HOP_LENGTH).min(N_FRAMES);
+
+        let output = self.whisper.align(
+            &features,
+            self.shape(),
+            &start_sequence,
+            std::slice::from_ref(&text_tokens),
+            &[num_frames],
+            MEDIAN_FILTER_WIDTH,
+        )?;
+        let Some(r) = output.into_iter().next() else {
+            return Err(Error::Ffi("no results are returned".to_string()));
+        };
+
+        // Split the tokens into words; a word starts with a token beginning with a space.
+        let mut words: Vec<(String, usize)> = Vec::new();
+        for id in &text_tokens {
+            let piece = self
+                .tokenizer
+                .decode(&[*id as u32], false)
+                .map_err(|err| Error::Decode(err.to_string()))?;
+            match words.last_mut() {
+                Some((word, len)) if !piece.starts_with(' ') => {
+                    word.push_str(&piece);
+                    *len += 1;
+                }
+                _ => words.push((piece, 1)),
+            }
+        }
+
+        // Times at which the aligned text token changes.
+        let mut jump_times = Vec::new();
+        let mut prev = None;
+        for (text_index, time_index) in r.alignments {
+            if prev != Some(text_index) {
+                jump_times.push(time_index as f32 / TOKENS_PER_SECOND);
+                prev = Some(text_index);
+            }
+        }
+
+        let mut res = Vec::with_capacity(words.len());
+        let mut begin = 0;
+        for (word, len) in words {
+            let end = begin + len;
+            let probs = r
+                .text_token_probs
+                .get(begin..end.min(r.text_token_probs.len()))
+                .unwrap_or_default();
+            res.push(WordTimestamp {
+                word: word.trim().to_string(),
+                start: jump_times.get(begin).copied().unwrap_or_default(),
+                end: jump_times.get(end).copied().unwrap_or_default(),
+                probability: if probs.is_empty() {
+                    0.
+                } else {
+                    probs.iter().sum::<f32>() / probs.len() as f32
+                },
+            });
+            begin = end;
+        }
+        Ok(res)
+    }
+
+    fn detect_language_from_features(&self, features: &[f32]) -> Result<Vec<(String, f32)>> {
+        let Some(probs) = self
+            .whisper
+            .detect_language(features, self.shape())?
+            .into_iter()
+            .next()
+        else {
+            return Err(Error::Ffi("no results are returned".to_string()));
+        };
+        Ok(probs
+            .into_iter()
+            .map(|(token, prob)| {
+                let language = token
+                    .strip_prefix("<|")
+                    .and_then(|s| s.strip_suffix("|>"))
+                    .unwrap_or(&token)
+                    .to_string();
+                (language, prob)
+            })
+            .collect())
+    }
+
+    /// Returns the tokens starting a transcription in the given language.
+    fn sot_sequence(&self, features: &[f32], language: Option<&str>) -> Result<Vec<String>> {
+        if !self.is_multilingual() {
+            return Ok(vec![SOT_TOKEN.to_string()]);
+        }
+
+        let language = match language {
+            Some(language) => language.to_string(),
+            None => match self.detect_language_from_features(features)?.into_iter().next() {
+                Some((language, _)) => language,
+                None => return Err(Error::Ffi("failed to detect the language".to_string())),
+            },
+        };
+        Ok(vec![
+            SOT_TOKEN.to_string(),
+            format!("<|{language}|>"),
+            TRANSCRIBE_TOKEN.to_string(),
+        ])
+    }
+
+    fn token_id(&self, token: &str) -> Result<usize> {
+        self.tokenizer
+            .token_to_id(token)
+            .map(|id| id as usize)
+            .ok_or_else(|| Error::InvalidOptions(format!("unknown token: {token}")))
+    }
+
+    #[inline]
+    fn shape(&self) -> [usize; 3] {
+        [1, self.whisper.n_mels(), N_FRAMES]
+    }
+}
+
+/// Decodes the hypotheses of a generation result.
+fn decode(
+    tokenizer: &Tokenizer,
+    r: WhisperGenerationResult,
+    skip_special_tokens: bool,
+) -> Result<TranscribedText> {
+    let texts = r
+        .sequences_ids
+        .iter()
+        .map(|ids| {
+            let ids = ids.iter().map(|id| *id as u32).collect::<Vec<_>>();
+            tokenizer.decode(&ids, skip_special_tokens)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::Decode(err.to_string()))?;
+    Ok(TranscribedText {
+        texts,
+        tokens: r.sequences,
+        ids: r.sequences_ids,
+        scores: r.scores,
+        no_speech_prob: r.no_speech_prob,
+    })
+}
+
+/// The hypotheses transcribed from an audio.
+#[derive(Clone, Debug)]
+pub struct TranscribedText {
+    /// Decoded text of each hypothesis.
+    pub texts: Vec<String>,
+    /// Tokens of each hypothesis.
+    pub tokens: Vec<Vec<String>>,
+    /// Token IDs of each hypothesis.
+    pub ids: Vec<Vec<usize>>,
+    /// Score of each hypothesis (empty if `return_scores` was disabled).
+    pub scores: Vec<f32>,
+    /// Probability of the no speech token (0 if `return_no_speech_prob` was disabled).
+    pub no_speech_prob: f32,
+}
+
+impl TranscribedText {
+    /// Returns the text of the best hypothesis.
+    pub fn text(&self) -> &str {
+        self.texts.first().map_or("", |s| s.as_str())
+    }
+
+    /// Returns the score of the best hypothesis, if the scores were returned.
+    pub fn score(&self) -> Option<f32> {
+        self.scores.first().copied()
+    }
+}
+
+/// A word with its timestamps in seconds.
+#[derive(Clone, Debug)]
+pub struct WordTimestamp {
+    /// The word.
+    pub word: String,
+    /// Start time of the word.
+    pub start: f32,
+    /// End time of the word.
+    pub end: f32,
+    /// Average probability of the tokens of the word.
+    pub probability: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::AddedToken;
+
+    use super::*;
+
+    #[test]
+    fn hypotheses_are_decoded_with_the_scores() {
+        let vocab = ["<unk>", "Hello", "world", TIMESTAMP_BEGIN_TOKEN]
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), id as u32))
+            .collect();
+        let mut tokenizer = Tokenizer::new(WordLevel::builder().vocab(vocab).build().unwrap());
+        tokenizer.add_special_tokens(&[AddedToken::from(TIMESTAMP_BEGIN_TOKEN, true)]);
+        let result = || WhisperGenerationResult {
+            sequences: vec![
+                vec![
+                    TIMESTAMP_BEGIN_TOKEN.to_string(),
+                    "Hello".to_string(),
+                    "world".to_string(),
+                ],
+                vec![TIMESTAMP_BEGIN_TOKEN.to_string(), "Hello".to_string()],
+            ],
+            sequences_ids: vec![vec![3, 1, 2], vec![3, 1]],
+            scores: vec![-0.25, -1.],
+            no_speech_prob: 0.125,
+        };
+
+        let res = decode(&tokenizer, result(), true).unwrap();
+        assert_eq!(res.texts, ["Hello world", "Hello"]);
+        assert_eq!(res.text(), "Hello world");
+        assert_eq!(res.score(), Some(-0.25));
+        assert_eq!(res.no_speech_prob, 0.125);
+        assert_eq!(res.ids[1], [3, 1]);
+
+        // The timestamps are kept when they are predicted.
+        let res = decode(&tokenizer, result(), false).unwrap();
+        assert_eq!(res.text(), "<|0.00|> Hello world");
+    }
+}
@@ -0,0 +1,392 @@
+use cxx::UniquePtr;
+
+use crate::config::{ComputeType, Config, Device};
+use crate::device::check_config;
+use crate::error::{Error, Result};
+
+#[cxx::bridge]
+mod ffi {
+    struct WhisperVecStr<'a> {
+        v: Vec<&'a str>,
+    }
+
+    struct WhisperVecString {
+        v: Vec<String>,
+    }
+
+    struct WhisperVecUSize {
+        v: Vec<usize>,
+    }
+
+    enum WhisperComputeType {
+        Default,
+        Auto,
+        Float32,
+        Int8,
+        Int8Float16,
+        Int16,
+        Float16,
+        BFloat16,
+        Int8BFloat16,
+        Int8Float32,
+    }
+
+    struct WhisperConfig {
+        compute_type: WhisperComputeType,
+        device_indices: Vec<i32>,
+        num_threads_per_replica: usize,
+        max_queued_batches: i64,
+        cpu_core_offset: i32,
+    }
+
+    struct WhisperOptions {
+        beam_size: usize,
+        patience: f32,
+        length_penalty: f32,
+        repetition_penalty: f32,
+        no_repeat_ngram_size: usize,
+        max_length: usize,
+        sampling_topk: usize,
+        sampling_temperature: f32,
+        num_hypotheses: usize,
+        return_scores: bool,
+        return_no_speech_prob: bool,
+        max_initial_timestamp_index: usize,
+        suppress_blank: bool,
+        suppress_tokens: Vec<i32>,
+    }
+
+    struct WhisperGenerationResult {
+        sequences: Vec<WhisperVecString>,
+        sequences_ids: Vec<WhisperVecUSize>,
+        scores: Vec<f32>,
+        no_speech_prob: f32,
+    }
+
+    struct WhisperLanguageProb {
+        language: String,
+        prob: f32,
+    }
+
+    struct WhisperLanguageProbs {
+        v: Vec<WhisperLanguageProb>,
+    }
+
+    struct WhisperAlignment {
+        text_token_index: i64,
+        time_index: i64,
+    }
+
+    struct WhisperAlignmentResult {
+        alignments: Vec<WhisperAlignment>,
+        text_token_probs: Vec<f32>,
+    }
+
+    unsafe extern "C++" {
+        include!("../../include/whisper.h");
+
+        type Whisper;
+
+        fn new_whisper(
+            model_path: &str,
+            cuda: bool,
+            config: WhisperConfig,
+        ) -> Result<UniquePtr<Whisper>>;
+
+        fn is_multilingual(&self) -> bool;
+
+        fn n_mels(&self) -> usize;
+
+        fn num_languages(&self) -> usize;
+
+        fn generate(
+            &self,
+            features: &[f32],
+            shape: &[usize],
+            prompts: Vec<WhisperVecStr>,
+            options: WhisperOptions,
+        ) -> Result<Vec<WhisperGenerationResult>>;
+
+        fn detect_language(
+            &self,
+            features: &[f32],
+            shape: &[usize],
+        ) -> Result<Vec<WhisperLanguageProbs>>;
+
+        fn align(
+            &self,
+            features: &[f32],
+            shape: &[usize],
+            start_sequence: Vec<usize>,
+            text_tokens: Vec<WhisperVecUSize>,
+            num_frames: Vec<usize>,
+            median_filter_width: i64,
+        ) -> Result<Vec<WhisperAlignmentResult>>;
+    }
+}
+
+/// A speech recognition model.
+///
+/// The `features` taken by the methods are log-mel spectrograms of the shape
+/// `[batch_size, n_mels, n_frames]`, stored in row-major order.
+pub struct Whisper {
+    ptr: UniquePtr<ffi::Whisper>,
+}
+
+impl Whisper {
+    pub fn new<T: AsRef<str>>(
+        model_path: T,
+        device: Device,
+        config: Config,
+    ) -> Result<Whisper> {
+        let (device, compute_type) = check_config(device, &config)?;
+        Ok(Whisper {
+            ptr: ffi::new_whisper(
+                model_path.as_ref(),
+                device == Device::CUDA,
+                ffi::WhisperConfig {
+                    compute_type: match compute_type {
+                        ComputeType::Default => ffi::WhisperComputeType::Default,
+                        ComputeType::Auto => ffi::WhisperComputeType::Auto,
+                        ComputeType::Float32 => ffi::WhisperComputeType::Float32,
+                        ComputeType::Int8 => ffi::WhisperComputeType::Int8,
+                        ComputeType::Int8Float16 => ffi::WhisperComputeType::Int8Float16,
+                        ComputeType::Int16 => ffi::WhisperComputeType::Int16,
+                        ComputeType::Float16 => ffi::WhisperComputeType::Float16,
+                        ComputeType::BFloat16 => ffi::WhisperComputeType::BFloat16,
+                        ComputeType::Int8BFloat16 => ffi::WhisperComputeType::Int8BFloat16,
+                        ComputeType::Int8Float32 => ffi::WhisperComputeType::Int8Float32,
+                    },
+                    device_indices: config.device_indices,
+                    num_threads_per_replica: config.num_threads_per_replica,
+                    max_queued_batches: config.max_queued_batches,
+                    cpu_core_offset: config.cpu_core_offset,
+                },
+            )
+            .map_err(Error::model_load)?,
+        })
+    }
+
+    /// Returns true if this model is multilingual.
+    pub fn is_multilingual(&self) -> bool {
+        self.ptr.is_multilingual()
+    }
+
+    /// Returns the number of mel frequency bins expected by this model.
+    pub fn n_mels(&self) -> usize {
+        self.ptr.n_mels()
+    }
+
+    /// Returns the number of languages supported by this model.
+    pub fn num_languages(&self) -> usize {
+        self.ptr.num_languages()
+    }
+
+    /// Encodes the input features and generates from the given prompts.
+    ///
+    /// The prompts usually start with `<|startoftranscript|>`, followed by the language and
+    /// task tokens for multilingual models.
+    pub fn generate<T: AsRef<str>>(
+        &self,
+        features: &[f32],
+        shape: [usize; 3],
+        prompts: &[Vec<T>],
+        options: &WhisperOptions,
+    ) -> Result<Vec<WhisperGenerationResult>> {
+        Ok(self
+            .ptr
+            .generate(
+                features,
+                &shape,
+                prompts
+                    .iter()
+                    .map(|v| ffi::WhisperVecStr {
+                        v: v.iter().map(|s| s.as_ref()).collect(),
+                    })
+                    .collect(),
+                options.to_ffi(),
+            )?
+            .into_iter()
+            .map(WhisperGenerationResult::from)
+            .collect())
+    }
+
+    /// Returns the probability of each language token for each example of the batch, sorted by
+    /// decreasing probability.
+    pub fn detect_language(
+        &self,
+        features: &[f32],
+        shape: [usize; 3],
+    ) -> Result<Vec<Vec<(String, f32)>>> {
+        Ok(self
+            .ptr
+            .detect_language(features, &shape)?
+            .into_iter()
+            .map(|r| r.v.into_iter().map(|p| (p.language, p.prob)).collect())
+            .collect())
+    }
+
+    /// Computes the alignments between the text tokens and the audio frames.
+    ///
+    /// `start_sequence` is the prompt preceding the text tokens and `num_frames` is the number of
+    /// non-padding frames of each example.
+    pub fn align(
+        &self,
+        features: &[f32],
+        shape: [usize; 3],
+        start_sequence: &[usize],
+        text_tokens: &[Vec<usize>],
+        num_frames: &[usize],
+        median_filter_width: i64,
+    ) -> Result<Vec<WhisperAlignmentResult>> {
+        Ok(self
+            .ptr
+            .align(
+                features,
+                &shape,
+                start_sequence.to_vec(),
+                text_tokens
+                    .iter()
+                    .map(|v| ffi::WhisperVecUSize { v: v.clone() })
+                    .collect(),
+                num_frames.to_vec(),
+                median_filter_width,
+            )?
+            .into_iter()
+            .map(WhisperAlignmentResult::from)
+            .collect())
+    }
+}
+
+/// The set of Whisper options.
+#[derive(Clone, Debug)]
+pub struct WhisperOptions {
+    /// Beam size to use for beam search (set 1 to run greedy search).
+    pub beam_size: usize,
+    /// Beam search patience factor, as described in <https://arxiv.org/abs/2204.05424>.
+    /// The decoding will continue until beam_size*patience hypotheses are finished.
+    pub patience: f32,
+    /// Exponential penalty applied to the length during beam search.
+    pub length_penalty: f32,
+    /// Penalty applied to the score of previously generated tokens, as described in
+    /// <https://arxiv.org/abs/1909.05858> (set > 1 to penalize).
+    pub repetition_penalty: f32,
+    /// Prevent repetitions of ngrams with this size (set 0 to disable).
+    pub no_repeat_ngram_size: usize,
+    /// Maximum generation length.
+    pub max_length: usize,
+    /// Randomly sample from the top K candidates (set 0 to sample from the full output distribution).
+    pub sampling_topk: usize,
+    /// High temperature increase randomness.
+    pub sampling_temperature: f32,
+    /// Number of hypotheses to include in the result.
+    pub num_hypotheses: usize,
+    /// Include scores in the result.
+    pub return_scores: bool,
+    /// Include the probability of the no speech token in the result.
+    pub return_no_speech_prob: bool,
+    /// Maximum index of the first predicted timestamp.
+    pub max_initial_timestamp_index: usize,
+    /// Suppress blank outputs at the beginning of the sampling.
+    pub suppress_blank: bool,
+    /// List of token IDs to suppress.
+    /// -1 will suppress a default set of symbols as defined in the model `config.json` file.
+    pub suppress_tokens: Vec<i32>,
+    /// Predict timestamps along with the text. Used by [`crate::whisper::Whisper`] to build the
+    /// prompt.
+    pub timestamps: bool,
+    /// Text the transcription should start with. Used by [`crate::whisper::Whisper`] to build
+    /// the prompt.
+    pub prefix: Option<String>,
+}
+
+impl Default for WhisperOptions {
+    fn default() -> Self {
+        Self {
+            beam_size: 5,
+            patience: 1.,
+            length_penalty: 1.,
+            repetition_penalty: 1.,
+            no_repeat_ngram_size: 0,
+            max_length: 448,
+            sampling_topk: 1,
+            sampling_temperature: 1.,
+            num_hypotheses: 1,
+            return_scores: false,
+            return_no_speech_prob: false,
+            max_initial_timestamp_index: 50,
+            suppress_blank: true,
+            suppress_tokens: vec![-1],
+            timestamps: false,
+            prefix: None,
+        }
+    }
+}
+
+impl WhisperOptions {
+    #[inline]
+    fn to_ffi(&self) -> ffi::WhisperOptions {
+        ffi::WhisperOptions {
+            beam_size: self.beam_size,
+            patience: self.patience,
+            length_penalty: self.length_penalty,
+            repetition_penalty: self.repetition_penalty,
+            no_repeat_ngram_size: self.no_repeat_ngram_size,
+            max_length: self.max_length,
+            sampling_topk: self.sampling_topk,
+            sampling_temperature: self.sampling_temperature,
+            num_hypotheses: self.num_hypotheses,
+            return_scores: self.return_scores,
+            return_no_speech_prob: self.return_no_speech_prob,
+            max_initial_timestamp_index: self.max_initial_timestamp_index,
+            suppress_blank: self.suppress_blank,
+            suppress_tokens: self.suppress_tokens.clone(),
+        }
+    }
+}
+
+/// A Whisper generation result.
+#[derive(Debug)]
+pub struct WhisperGenerationResult {
+    /// Generated sequences of tokens.
+    pub sequences: Vec<Vec<String>>,
+    /// Generated sequences of token IDs.
+    pub sequences_ids: Vec<Vec<usize>>,
+    /// Score of each sequence (empty if `return_scores` was disabled).
+    pub scores: Vec<f32>,
+    /// Probability of the no speech token (0 if `return_no_speech_prob` was disabled).
+    pub no_speech_prob: f32,
+}
+
+impl From<ffi::WhisperGenerationResult> for WhisperGenerationResult {
+    fn from(res: ffi::WhisperGenerationResult) -> Self {
+        Self {
+            sequences: res.sequences.into_iter().map(|c| c.v).collect(),
+            sequences_ids: res.sequences_ids.into_iter().map(|c| c.v).collect(),
+            scores: res.scores,
+            no_speech_prob: res.no_speech_prob,
+        }
+    }
+}
+
+/// A Whisper alignment result.
+#[derive(Debug)]
+pub struct WhisperAlignmentResult {
+    /// Pairs of a text token index and a time index.
+    pub alignments: Vec<(i64, i64)>,
+    /// Probability of each text token.
+    pub text_token_probs: Vec<f32>,
+}
+
+impl From<ffi::WhisperAlignmentResult> for WhisperAlignmentResult {
+    fn from(res: ffi::WhisperAlignmentResult) -> Self {
+        Self {
+            alignments: res
+                .alignments
+                .into_iter()
+                .map(|a| (a.text_token_index, a.time_index))
+                .collect(),
+            text_token_probs: res.text_token_probs,
+        }
+    }
+}
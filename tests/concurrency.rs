@@ -0,0 +1,125 @@
+// concurrency.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Calls a single generator and translator from many threads at once.
+//!
+//! The tests need models, given by the environment variables:
+//!
+//! - `CTRANS2_TEST_GENERATOR`: a decoder-only model directory, e.g. converted from `gpt2`.
+//! - `CTRANS2_TEST_TRANSLATOR`: a sequence-to-sequence model directory, e.g. converted from
+//!   `Helsinki-NLP/opus-mt-en-de`.
+//!
+//! The tests are skipped when the variables are not set.
+
+use std::env;
+use std::sync::Arc;
+use std::thread;
+
+use ctrans2::{Config, Device, GenerationOptions, Generator, TranslationOptions, Translator};
+
+const NUM_THREADS: usize = 16;
+const NUM_ITERATIONS: usize = 8;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn generator_and_translator_are_send_sync() {
+    assert_send_sync::<Generator>();
+    assert_send_sync::<Translator>();
+}
+
+fn config() -> Config {
+    Config {
+        // Several replicas so that the batches actually run in parallel.
+        device_indices: vec![0; 4],
+        num_threads_per_replica: 1,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn generate_from_many_threads() {
+    let Ok(path) = env::var("CTRANS2_TEST_GENERATOR") else {
+        eprintln!("CTRANS2_TEST_GENERATOR is not set; skipped");
+        return;
+    };
+    let generator = Arc::new(Generator::new(path, Device::CPU, config()).unwrap());
+    let options = GenerationOptions {
+        max_length: 16,
+        ..Default::default()
+    };
+
+    // Greedy search is deterministic, so every thread must get the same results.
+    let prompts = vec!["Hello, my name is", "The capital of France is"];
+    let expected = generator.generate_batch(prompts.clone(), &options).unwrap();
+
+    let handles = (0..NUM_THREADS)
+        .map(|_| {
+            let generator = generator.clone();
+            let options = options.clone();
+            let prompts = prompts.clone();
+            thread::spawn(move || {
+                (0..NUM_ITERATIONS)
+                    .map(|_| generator.generate_batch(prompts.clone(), &options).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        for res in handle.join().unwrap() {
+            assert_eq!(
+                res.iter().map(|r| &r.ids).collect::<Vec<_>>(),
+                expected.iter().map(|r| &r.ids).collect::<Vec<_>>()
+            );
+        }
+    }
+}
+
+#[test]
+fn translate_from_many_threads() {
+    let Ok(path) = env::var("CTRANS2_TEST_TRANSLATOR") else {
+        eprintln!("CTRANS2_TEST_TRANSLATOR is not set; skipped");
+        return;
+    };
+    let translator = Arc::new(Translator::new(path, Device::CPU, config()).unwrap());
+    let options = TranslationOptions {
+        beam_size: 1,
+        max_decoding_length: 32,
+        ..Default::default()
+    };
+
+    let sources = vec!["Hello world!", "How are you?"];
+    let expected = translator
+        .translate_batch(sources.clone(), Vec::<Vec<String>>::new(), &options)
+        .unwrap();
+
+    let handles = (0..NUM_THREADS)
+        .map(|_| {
+            let translator = translator.clone();
+            let options = options.clone();
+            let sources = sources.clone();
+            thread::spawn(move || {
+                (0..NUM_ITERATIONS)
+                    .map(|_| {
+                        translator
+                            .translate_batch(sources.clone(), Vec::<Vec<String>>::new(), &options)
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        for res in handle.join().unwrap() {
+            assert_eq!(
+                res.iter().map(|r| r.text()).collect::<Vec<_>>(),
+                expected.iter().map(|r| r.text()).collect::<Vec<_>>()
+            );
+        }
+    }
+}
@@ -0,0 +1,140 @@
+// device.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Queries the devices and checks the configs before the models are constructed.
+//!
+//! The tests run on the CPU, and the CUDA ones are skipped on machines without a GPU.
+
+use std::collections::HashMap;
+
+use ctrans2::device::{get_cuda_device_count, get_supported_compute_types};
+use ctrans2::{ComputeType, Config, Device, Error, Generator, ModelSource};
+use tokenizers::models::wordlevel::WordLevel;
+use tokenizers::Tokenizer;
+
+/// Constructs a generator from an empty model, which fails once the config is accepted.
+fn new_generator(device: Device, config: Config) -> ctrans2::Result<Generator> {
+    Generator::with_tokenizer(
+        ModelSource::memory("empty", HashMap::new()),
+        device,
+        config,
+        Tokenizer::new(WordLevel::default()),
+    )
+}
+
+#[test]
+fn cpu_supports_float32() {
+    let types = get_supported_compute_types(Device::CPU, 0).unwrap();
+    assert!(types.contains(&ComputeType::Float32));
+    assert!(!types.contains(&ComputeType::Default));
+}
+
+#[test]
+fn auto_resolves_to_an_available_device() {
+    let expected = if get_cuda_device_count() > 0 {
+        Device::CUDA
+    } else {
+        Device::CPU
+    };
+    assert_eq!(Device::Auto.resolve(), expected);
+    assert_eq!(Device::CPU.resolve(), Device::CPU);
+    assert_eq!("auto".parse::<Device>().unwrap(), Device::Auto);
+}
+
+#[test]
+fn missing_cuda_device_is_rejected() {
+    let count = get_cuda_device_count() as i32;
+    assert!(matches!(
+        get_supported_compute_types(Device::CUDA, count),
+        Err(Error::DeviceUnavailable(_))
+    ));
+    let config = Config {
+        device_indices: vec![count],
+        ..Default::default()
+    };
+    assert!(matches!(
+        new_generator(Device::CUDA, config),
+        Err(Error::DeviceUnavailable(_))
+    ));
+}
+
+#[test]
+fn unsupported_compute_type_is_rejected() {
+    let supported = get_supported_compute_types(Device::CPU, 0).unwrap();
+    for compute_type in [
+        ComputeType::Float32,
+        ComputeType::Int8,
+        ComputeType::Int8Float16,
+        ComputeType::Int16,
+        ComputeType::Float16,
+        ComputeType::BFloat16,
+        ComputeType::Int8BFloat16,
+        ComputeType::Int8Float32,
+    ] {
+        let config = Config {
+            compute_type,
+            ..Default::default()
+        };
+        let res = new_generator(Device::CPU, config);
+        if supported.contains(&compute_type) {
+            // The config is accepted, and loading the empty model fails.
+            assert!(matches!(res, Err(Error::ModelLoad(_))), "{compute_type:?}");
+        } else {
+            assert!(
+                matches!(res, Err(Error::DeviceUnavailable(_))),
+                "{compute_type:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn auto_compute_type_is_accepted_by_any_device() {
+    for compute_type in [ComputeType::Default, ComputeType::Auto] {
+        let config = Config {
+            compute_type,
+            ..Default::default()
+        };
+        let res = new_generator(Device::CPU, config);
+        assert!(matches!(res, Err(Error::ModelLoad(_))), "{compute_type:?}");
+    }
+}
+
+#[test]
+fn compute_type_is_chosen_per_device() {
+    let config = Config {
+        compute_type: ComputeType::Float32,
+        device_compute_types: [(Device::CUDA, ComputeType::Int8Float16)].into(),
+        ..Default::default()
+    };
+    assert_eq!(config.compute_type_for(Device::CPU), ComputeType::Float32);
+    assert_eq!(
+        config.compute_type_for(Device::CUDA),
+        ComputeType::Int8Float16
+    );
+    assert_eq!(
+        config.compute_type_for(Device::Auto),
+        config.compute_type_for(Device::Auto.resolve())
+    );
+    assert_eq!(
+        "int8_bfloat16".parse::<ComputeType>().unwrap(),
+        ComputeType::Int8BFloat16
+    );
+}
+
+#[test]
+fn empty_device_indices_are_rejected() {
+    let config = Config {
+        device_indices: vec![],
+        ..Default::default()
+    };
+    assert!(matches!(
+        new_generator(Device::CPU, config),
+        Err(Error::InvalidOptions(_))
+    ));
+}
@@ -0,0 +1,62 @@
+// encoder.rs
+//
+// Copyright (c) 2023 Onojeta Brown
+//
+// This software is released under the MIT License.
+//
+// http://opensource.org/licenses/mit-license.php
+
+//! Calls the low-level encoder with tokens and token type IDs.
+//!
+//! The tests need a BERT model directory with its `tokenizer.json`, e.g. converted from
+//! `bert-base-uncased`, given by the environment variable `CTRANS2_TEST_ENCODER`. They are
+//! skipped when the variable is not set.
+
+use std::env;
+use std::path::Path;
+
+use ctrans2::encoder::encoder::Encoder;
+use ctrans2::{Config, Device};
+use tokenizers::Tokenizer;
+
+#[test]
+fn forward_batch_with_token_type_ids() {
+    let Ok(path) = env::var("CTRANS2_TEST_ENCODER") else {
+        eprintln!("CTRANS2_TEST_ENCODER is not set; skipped");
+        return;
+    };
+    let tokenizer = Tokenizer::from_file(Path::new(&path).join("tokenizer.json")).unwrap();
+    let encoder = Encoder::new(&path, Device::CPU, Config::default()).unwrap();
+
+    // A pair of sentences, whose second sentence has the token type 1.
+    let encoding = tokenizer
+        .encode(("How are you?", "I am fine."), true)
+        .unwrap();
+    let tokens = vec![encoding.get_tokens().to_vec()];
+    let ids = vec![encoding
+        .get_ids()
+        .iter()
+        .map(|id| *id as usize)
+        .collect::<Vec<_>>()];
+    let token_type_ids = vec![encoding
+        .get_type_ids()
+        .iter()
+        .map(|id| *id as usize)
+        .collect::<Vec<_>>()];
+    assert!(token_type_ids[0].contains(&1));
+
+    let output = encoder.forward_batch(&tokens, &token_type_ids).unwrap();
+    assert_eq!(output.last_hidden_state_shape[..2], [1, tokens[0].len()]);
+    assert_eq!(
+        output.last_hidden_state.len(),
+        output.last_hidden_state_shape.iter().product::<usize>()
+    );
+
+    // The tokens and their IDs give the same hidden states.
+    let from_ids = encoder.forward_batch_ids(&ids, &token_type_ids).unwrap();
+    assert_eq!(output.last_hidden_state, from_ids.last_hidden_state);
+
+    // The token types are passed to the model.
+    let without_types = encoder.forward_batch(&tokens, &[]).unwrap();
+    assert_ne!(output.last_hidden_state, without_types.last_hidden_state);
+}